@@ -7,7 +7,7 @@
 // from [this blog post](https://fgiesen.wordpress.com/2009/12/13/decoding-morton-codes/). 
 // See the comments there for a good explanation of how these work.
 //
-// We can encode up to 16-bit codes 2d and 10-bit codes in 3d. Note that the order is 
+// We can encode up to 16-bit codes 2d, 10-bit codes in 3d, and 8-bit codes in 4d. Note that the order is 
 // like a reflected Z, traversed from bottom left to top right : *bl*, *br*, *tl*, *tr*.
 
 export function encode2(x, y) { 
@@ -18,6 +18,10 @@ export function encode3(x, y, z) {
   return ((part1By2(z) << 2) + (part1By2(y) << 1) + part1By2(x)) >>> 0;; 
 }
 
+export function encode4(x, y, z, w) { 
+  return ((part1By3(w) << 3) + (part1By3(z) << 2) + (part1By3(y) << 1) + part1By3(x)) >>> 0;
+}
+
 
 export function decode2x(code) { 
   return compact1By1(code >> 0);
@@ -44,6 +48,90 @@ export function decode3z(code) {
   return compact1By2(code >> 2);
 }
 
+export function decode4x(code) { 
+  return compact1By3(code >> 0);
+}
+
+export function decode4y(code) { 
+  return compact1By3(code >> 1);
+}
+
+export function decode4z(code) { 
+  return compact1By3(code >> 2);
+}
+
+export function decode4w(code) { 
+  return compact1By3(code >> 3);
+}
+
+// Generic decoder for any number of dimensions: gathers every `numDims`-th bit of the code,
+// starting at bit `dim`, into the low bits of the result. For example, `deinterleave(code, 1, 2)`
+// is `decode2y(code)`. Unlike the 3D decoders, which ignore the top two bits of the code, this
+// uses all 32 bits, so `x` gets 11 bits in 3D. The fixed-dimension decoders above are faster,
+// since they use a fixed sequence of masks rather than looping over the bits, so this is meant
+// for dimensions they do not cover, eg. 5 dimensions of 6 bits each.
+export function deinterleave(code, dim, numDims) {
+  if (!(Number.isInteger(numDims) && numDims >= 1 && numDims <= 32 && Number.isInteger(dim) && dim >= 0 && dim < numDims)) {
+    throw new Error(`expected numDims in [1, 32] and dim in [0, numDims), got dim ${dim} and numDims ${numDims}`);
+  }
+  let result = 0;
+  for (let bit = dim, i = 0; bit < 32; bit += numDims, i++) {
+    result |= ((code >>> bit) & 1) << i;
+  }
+  return result >>> 0;
+}
+
+// Batch versions of the functions above, which encode or decode whole arrays in a tight loop.
+// The output arrays are optional; if they are provided, they must have the same length as the input.
+
+export function encode2Array(xs, ys, out = new Uint32Array(xs.length)) {
+  checkLengths('encode2Array', xs.length, ys, out);
+  for (let i = 0; i < xs.length; i++) {
+    out[i] = encode2(xs[i], ys[i]);
+  }
+  return out;
+}
+
+export function decode2Array(codes, outX = new Uint32Array(codes.length), outY = new Uint32Array(codes.length)) {
+  checkLengths('decode2Array', codes.length, outX, outY);
+  for (let i = 0; i < codes.length; i++) {
+    outX[i] = decode2x(codes[i]);
+    outY[i] = decode2y(codes[i]);
+  }
+  return [outX, outY];
+}
+
+export function encode3Array(xs, ys, zs, out = new Uint32Array(xs.length)) {
+  checkLengths('encode3Array', xs.length, ys, zs, out);
+  for (let i = 0; i < xs.length; i++) {
+    out[i] = encode3(xs[i], ys[i], zs[i]);
+  }
+  return out;
+}
+
+export function decode3Array(
+  codes, 
+  outX = new Uint32Array(codes.length), 
+  outY = new Uint32Array(codes.length), 
+  outZ = new Uint32Array(codes.length)
+) {
+  checkLengths('decode3Array', codes.length, outX, outY, outZ);
+  for (let i = 0; i < codes.length; i++) {
+    outX[i] = decode3x(codes[i]);
+    outY[i] = decode3y(codes[i]);
+    outZ[i] = decode3z(codes[i]);
+  }
+  return [outX, outY, outZ];
+}
+
+function checkLengths(name, length, ...arrays) {
+  for (const array of arrays) {
+    if (array.length !== length) {
+      throw new Error(`${name}: expected arrays of length ${length}, got an array of length ${array.length}`);
+    }
+  }
+}
+
 // Experimenting with byte interleaving since it is possible to implement using
 // wasm simd128 (swizzle, shuffle)
 function part8By8(x) {
@@ -90,6 +178,25 @@ function compact1By2(x) {
   return x;
 }
 
+// "Insert" three 0 bits after each of the 8 low bits of x.
+// Exported since it is useful on its own, eg. to spread a single coordinate for comparison against a code.
+export function part1By3(x) {
+  x &= 0x000000ff; // x = ---- ---- ---- ---- ---- ---- 7654 3210
+  x = (x ^ (x << 12)) & 0x000f000f; // x = ---- ---- ---- 7654 ---- ---- ---- 3210
+  x = (x ^ (x << 6)) & 0x03030303; // x = ---- --76 ---- --54 ---- --32 ---- --10
+  x = (x ^ (x << 3)) & 0x11111111; // x = ---7 ---6 ---5 ---4 ---3 ---2 ---1 ---0
+  return x;
+}
+
+// Inverse of part1By3: gather every fourth bit of x, starting with the lowest, into the low 8 bits.
+export function compact1By3(x) {
+  x &= 0x11111111; // x = ---7 ---6 ---5 ---4 ---3 ---2 ---1 ---0
+  x = (x ^ (x >> 3)) & 0x03030303; // x = ---- --76 ---- --54 ---- --32 ---- --10
+  x = (x ^ (x >> 6)) & 0x000f000f; // x = ---- ---- ---- 7654 ---- ---- ---- 3210
+  x = (x ^ (x >> 12)) & 0x000000ff; // x = ---- ---- ---- ---- ---- ---- 7654 3210
+  return x;
+}
+
 // From https://twitter.com/jonahharris/status/1337087177591820290/photo/1
 // Used with permission from Jonah, who can't remember where he got it but
 // says he obtained it under the BSD license.
@@ -112,3 +219,112 @@ export function litMaxBigMin(uMin, uMax) {
   return { litMax: uLitMax >>> 0, bigMin: uBigMin >>> 0 };
 }
 
+
+// The 3d version of litMaxBigMin. The algorithm is the same, except that the split
+// dimension is the one of the three whose interleaved bits include the MSB of the XOR.
+export function litMaxBigMin3(uMin, uMax) {
+  const xor = uMin ^ uMax;
+  const uMSBD = 1 << (31 - Math.clz32(xor)); // note: fails for xor = 0 (31-clz is negative)
+  const xMask = 0x09249249;
+  const yMask = 0x12492492;
+  const zMask = 0x24924924;
+  const splitMask = uMSBD & xMask ? xMask : uMSBD & yMask ? yMask : zMask;
+  const uMSMask = (uMSBD - 1) & splitMask;
+  const uLSMask = (uMSBD - 1) & ~splitMask;
+  const uBSCommon = uMin & ~(uMSBD + uMSBD - 1);
+  const uLitMax = uBSCommon | uMSMask | (uLSMask & uMax);
+  const uBigMin = uBSCommon | uMSBD | (uLSMask & uMin);
+  return { litMax: uLitMax >>> 0, bigMin: uBigMin >>> 0 };
+}
+
+// Split the 2d box given by half-open coordinate ranges `x` and `y` into the sorted, disjoint,
+// half-open ranges of 2d Morton codes that together cover exactly the cells of the box.
+// Adjacent ranges are coalesced, so consecutive ranges are always separated by a gap.
+// Coordinates are limited to 16 bits, so range ends may not exceed 2^16.
+//
+// The number of ranges is at most proportional to the perimeter of the box, but can be large
+// for thin or diagonal-straddling boxes. Use `splitBox2Count` to find out how many ranges
+// a box produces before materializing them.
+export function splitBox2(x, y) {
+  const ranges = [];
+  visitBox2(x, y, coalesceInto(ranges));
+  return ranges;
+}
+
+// Returns the number of ranges `splitBox2(x, y)` would return, without allocating them.
+// This takes time proportional to the number of ranges, but constant space.
+export function splitBox2Count(x, y) {
+  let count = 0;
+  let prevEnd = -1;
+  visitBox2(x, y, (start, end) => {
+    if (start !== prevEnd) count++;
+    prevEnd = end;
+  });
+  return count;
+}
+
+function visitBox2(x, y, emit) {
+  checkBoxRange('x', x, 16);
+  checkBoxRange('y', y, 16);
+  if (x.start >= x.end || y.start >= y.end) {
+    return;
+  }
+  const volume = (min, max) => 
+    (decode2x(max) - decode2x(min) + 1) * 
+    (decode2y(max) - decode2y(min) + 1);
+  visitBox(encode2(x.start, y.start), encode2(x.end - 1, y.end - 1), volume, litMaxBigMin, emit);
+}
+
+// Split the 3d box given by half-open coordinate ranges `x`, `y`, and `z` into the sorted,
+// disjoint, half-open ranges of 3d Morton codes that together cover exactly the cells of the box,
+// with the same guarantees as `splitBox2`.
+// Coordinates are limited to 10 bits, so range ends may not exceed 2^10.
+export function splitBox3(x, y, z) {
+  checkBoxRange('x', x, 10);
+  checkBoxRange('y', y, 10);
+  checkBoxRange('z', z, 10);
+  const ranges = [];
+  if (x.start >= x.end || y.start >= y.end || z.start >= z.end) {
+    return ranges;
+  }
+  const volume = (min, max) => 
+    (decode3x(max) - decode3x(min) + 1) * 
+    (decode3y(max) - decode3y(min) + 1) * 
+    (decode3z(max) - decode3z(min) + 1);
+  visitBox(encode3(x.start, y.start, z.start), encode3(x.end - 1, y.end - 1, z.end - 1), volume, litMaxBigMin3, coalesceInto(ranges));
+  return ranges;
+}
+
+function checkBoxRange(name, range, bitsPerAxis) {
+  const max = 2 ** bitsPerAxis;
+  if (!(Number.isInteger(range.start) && Number.isInteger(range.end) && 0 <= range.start && range.end <= max)) {
+    throw new Error(`expected the ${name} range to lie within [0, 2^${bitsPerAxis}], got [${range.start}, ${range.end})`);
+  }
+}
+
+// Calls `emit(start, end)` in ascending order for half-open code ranges that together cover the cells
+// of the box with corner codes `min` and `max` (inclusive). The box is split with `litMaxBigMin` until
+// each part is a contiguous range of codes, ie. until the number of codes between its corners equals its
+// volume. Each split clears the most significant bit in which the corners differ, so the recursion depth
+// is bounded by the number of code bits. Consecutive emitted ranges may be adjacent.
+function visitBox(min, max, volume, litMaxBigMin, emit) {
+  if (max - min + 1 === volume(min, max)) {
+    emit(min, max + 1);
+    return;
+  }
+  const split = litMaxBigMin(min, max);
+  visitBox(min, split.litMax, volume, litMaxBigMin, emit);
+  visitBox(split.bigMin, max, volume, litMaxBigMin, emit);
+}
+
+// Returns an `emit` function for `visitBox` that appends to `ranges`, merging adjacent ranges.
+function coalesceInto(ranges) {
+  return (start, end) => {
+    const prev = ranges[ranges.length - 1];
+    if (prev !== undefined && prev.end === start) {
+      prev.end = end;
+    } else {
+      ranges.push({ start, end });
+    }
+  };
+}
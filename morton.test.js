@@ -0,0 +1,175 @@
+import fc from 'fast-check';
+import { describe, expect, it } from 'vitest';
+import {
+  compact1By3, decode2, decode2Array, decode2x, decode2y, decode3x, decode3y, decode3z, decode3Array, decode4w, decode4x,
+  decode4y, decode4z, deinterleave, encode2, encode2Array, encode3, encode3Array, encode4, litMaxBigMin3, part1By3, splitBox2, splitBox2Count, splitBox3
+} from './morton.js';
+
+describe('morton', () => {
+  it('deinterleave matches the fixed-dimension decoders', () => {
+    fc.assert(fc.property(fc.integer({ min: 0, max: 2 ** 32 - 1 }), code => {
+      expect(deinterleave(code, 0, 2)).toBe(decode2x(code));
+      expect(deinterleave(code, 1, 2)).toBe(decode2y(code));
+      // the 3D decoders only use the low 30 bits of the code, 10 for each axis
+      const code3 = code & 0x3fffffff;
+      expect(deinterleave(code3, 0, 3)).toBe(decode3x(code));
+      expect(deinterleave(code3, 1, 3)).toBe(decode3y(code));
+      expect(deinterleave(code3, 2, 3)).toBe(decode3z(code));
+      expect(deinterleave(code, 0, 4)).toBe(decode4x(code));
+      expect(deinterleave(code, 3, 4)).toBe(decode4w(code));
+      expect(deinterleave(code, 0, 1)).toBe(code);
+    }), { numRuns: 1000 });
+    // five dimensions, of which the first two get 7 bits and the others 6
+    const coords = [0b1010101, 0b1111111, 0b101010, 0b000001, 0b111000];
+    let code = 0;
+    for (let i = 0; i < 32; i++) {
+      code |= ((coords[i % 5] >>> Math.floor(i / 5)) & 1) << i;
+    }
+    expect(coords.map((_, dim) => deinterleave(code >>> 0, dim, 5))).toEqual(coords);
+    expect(() => deinterleave(0, 2, 2)).toThrow('expected numDims');
+    expect(() => deinterleave(0, 0, 0)).toThrow('expected numDims');
+  });
+
+  it('part1By3 and compact1By3 round-trip', () => {
+    for (let x = 0; x < 2 ** 8; x++) {
+      const spread = part1By3(x);
+      // every bit of the input is moved to a multiple-of-four bit position
+      for (let i = 0; i < 8; i++) {
+        expect((spread >>> (4 * i)) & 0xf).toBe((x >>> i) & 1);
+      }
+      expect(compact1By3(spread)).toBe(x);
+    }
+    // high bits of the input and non-interleaved bits of the code are ignored
+    expect(part1By3(0x1ff)).toBe(part1By3(0xff));
+    expect(compact1By3(0xffffffff)).toBe(0xff);
+  });
+
+  it('encode4 and decode4 round-trip', () => {
+    for (let x = 0; x < 2 ** 8; x += 17) {
+      for (let y = 0; y < 2 ** 8; y += 13) {
+        for (const [z, w] of [[0, 0], [255, 255], [x, y], [y, x]]) {
+          const code = encode4(x, y, z, w);
+          expect(code).toBeGreaterThanOrEqual(0);
+          expect(decode4x(code)).toBe(x);
+          expect(decode4y(code)).toBe(y);
+          expect(decode4z(code)).toBe(z);
+          expect(decode4w(code)).toBe(w);
+        }
+      }
+    }
+    expect(encode4(255, 255, 255, 255)).toBe(0xffffffff);
+    expect(encode4(1, 0, 0, 0)).toBe(1);
+    expect(encode4(0, 0, 0, 1)).toBe(8);
+  });
+
+  it('batch encoding and decoding matches element-wise calls', () => {
+    // deterministic pseudo-random coordinates
+    let seed = 12345;
+    const random = (/** @type {number} */ bits) => {
+      seed = (Math.imul(seed, 1103515245) + 12345) >>> 0;
+      return seed >>> (32 - bits);
+    };
+    for (const length of [0, 1, 100]) {
+      const xs = Array.from({ length }, () => random(16));
+      const ys = Array.from({ length }, () => random(16));
+      const codes = encode2Array(xs, ys);
+      expect(Array.from(codes)).toEqual(xs.map((x, i) => encode2(x, ys[i])));
+      const [dx, dy] = decode2Array(codes);
+      expect(Array.from(dx)).toEqual(xs);
+      expect(Array.from(dy)).toEqual(ys);
+      expect(Array.from(codes, c => decode2(c))).toEqual(xs.map((x, i) => [x, ys[i]]));
+
+      const xs3 = xs.map(x => x >>> 6);
+      const ys3 = ys.map(y => y >>> 6);
+      const zs3 = Array.from({ length }, () => random(10));
+      const codes3 = encode3Array(xs3, ys3, zs3);
+      expect(Array.from(codes3)).toEqual(xs3.map((x, i) => encode3(x, ys3[i], zs3[i])));
+      const [dx3, dy3, dz3] = decode3Array(codes3);
+      expect(Array.from(dx3)).toEqual(Array.from(codes3, decode3x));
+      expect(Array.from(dy3)).toEqual(Array.from(codes3, decode3y));
+      expect(Array.from(dz3)).toEqual(Array.from(codes3, decode3z));
+      expect(Array.from(dz3)).toEqual(zs3);
+    }
+
+    // existing output arrays are filled in and returned
+    const out = new Uint32Array(2);
+    expect(encode2Array([1, 2], [3, 4], out)).toBe(out);
+    expect(Array.from(out)).toEqual([encode2(1, 3), encode2(2, 4)]);
+  });
+
+  it('batch functions reject arrays of different lengths', () => {
+    expect(() => encode2Array([1, 2], [3])).toThrow('encode2Array: expected arrays of length 2, got an array of length 1');
+    expect(() => encode2Array([1], [3], new Uint32Array(2))).toThrow('encode2Array');
+    expect(() => decode2Array([1, 2], new Uint32Array(1))).toThrow('decode2Array');
+    expect(() => encode3Array([1], [2], [])).toThrow('encode3Array');
+    expect(() => decode3Array([1], new Uint32Array(1), new Uint32Array(1), new Uint32Array(3))).toThrow('decode3Array');
+  });
+
+  it('litMaxBigMin3 splits along the dimension of the most significant differing bit', () => {
+    // the corners differ first in z, so the box is split into z = 0 and z = 1
+    const { litMax, bigMin } = litMaxBigMin3(encode3(0, 0, 0), encode3(1, 1, 1));
+    expect(litMax).toBe(encode3(1, 1, 0));
+    expect(bigMin).toBe(encode3(0, 0, 1));
+  });
+
+  it('splitBox3 covers exactly the cells of the box', () => {
+    const bounds = [[0, 1], [0, 4], [1, 3], [2, 7], [3, 4], [5, 5], [6, 9]];
+    for (const [x0, x1] of bounds) {
+      for (const [y0, y1] of bounds) {
+        for (const [z0, z1] of bounds) {
+          const ranges = splitBox3({ start: x0, end: x1 }, { start: y0, end: y1 }, { start: z0, end: z1 });
+          const expected = [];
+          for (let x = x0; x < x1; x++) {
+            for (let y = y0; y < y1; y++) {
+              for (let z = z0; z < z1; z++) {
+                expected.push(encode3(x, y, z));
+              }
+            }
+          }
+          expected.sort((a, b) => a - b);
+          const actual = ranges.flatMap(r => Array.from({ length: r.end - r.start }, (_, i) => r.start + i));
+          expect(actual).toEqual(expected);
+          // ranges are sorted, non-empty, and separated by gaps
+          for (let i = 1; i < ranges.length; i++) {
+            expect(ranges[i - 1].end).toBeLessThan(ranges[i].start);
+          }
+        }
+      }
+    }
+    // the full coordinate space is a single range
+    const full = { start: 0, end: 2 ** 10 };
+    expect(splitBox3(full, full, full)).toEqual([{ start: 0, end: 2 ** 30 }]);
+    expect(() => splitBox3({ start: 0, end: 2 ** 10 + 1 }, full, full)).toThrow('x range');
+  });
+
+  it('splitBox2 covers exactly the cells of the box with maximally coalesced ranges', () => {
+    // small boxes placed anywhere in the coordinate space, so that every bit level is exercised
+    const box = fc.tuple(fc.integer({ min: 0, max: 2 ** 16 - 1 }), fc.integer({ min: 0, max: 40 }))
+      .map(([start, width]) => ({ start, end: Math.min(start + width, 2 ** 16) }));
+    fc.assert(fc.property(box, box, (x, y) => {
+      const ranges = splitBox2(x, y);
+      let total = 0;
+      for (const [i, range] of ranges.entries()) {
+        // every code in every range decodes into the box
+        for (let code = range.start; code < range.end; code++) {
+          const [cx, cy] = decode2(code);
+          expect(x.start <= cx && cx < x.end && y.start <= cy && cy < y.end).toBe(true);
+        }
+        total += range.end - range.start;
+        // ranges are ascending, non-empty, and separated by gaps, so none could be merged
+        expect(range.start).toBeLessThan(range.end);
+        if (i > 0) expect(ranges[i - 1].end).toBeLessThan(range.start);
+      }
+      // together, the ranges cover every cell of the box
+      expect(total).toBe((x.end - x.start) * (y.end - y.start));
+      expect(splitBox2Count(x, y)).toBe(ranges.length);
+    }));
+
+    // the full coordinate space is a single range
+    const full = { start: 0, end: 2 ** 16 };
+    expect(splitBox2(full, full)).toEqual([{ start: 0, end: 2 ** 32 }]);
+    expect(splitBox2Count(full, full)).toBe(1);
+    expect(splitBox2({ start: 3, end: 3 }, full)).toEqual([]);
+    expect(() => splitBox2(full, { start: -1, end: 3 })).toThrow('y range');
+  });
+});
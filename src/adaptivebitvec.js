@@ -0,0 +1,131 @@
+import { assert, assertBitIndex, assertStrictlyIncreasing, assertUniverseSize } from './assert.js';
+import * as bits from './bits.js';
+import { DefaultSmallThreshold, DenseBitVecBuilder } from './densebitvec.js';
+import { ascending } from './sort.js';
+import { SparseBitVec } from './sparsebitvec.js';
+
+/**
+ * Builder that defers the choice of representation until build time, producing
+ * a `DenseBitVec` or a `SparseBitVec` depending on which is estimated to be smaller.
+ * 1-bits are collected as a list of indices, so building a dense bit vector costs
+ * an extra pass over them. Input with multiplicity always produces a `SparseBitVec`,
+ * since it is the only one of the two that supports it.
+ *
+ * The build options are passed along to `DenseBitVecBuilder` and are also used
+ * to estimate its size; `SparseBitVec` does not take any options.
+ * @implements {BitVecBuilder}
+ */
+export class AdaptiveBitVecBuilder {
+  /**
+   * @param {number} universeSize
+   */
+  constructor(universeSize) {
+    assertUniverseSize(universeSize);
+    this.universeSize = universeSize;
+    /** @type {number[]} */
+    this.ones = [];
+    this.isBuilt = false;
+  }
+
+  /**
+   * @param {number} index
+   */
+  one(index, count = 1) {
+    assert(!this.isBuilt, 'cannot modify a builder after it has been built');
+    assertBitIndex(index, this.universeSize);
+    for (let i = 0; i < count; i++) {
+      this.ones.push(index);
+    }
+  }
+
+  /**
+   * @param {DenseBuildOptions} [options]
+   */
+  build(options = {}) {
+    assert(!this.isBuilt, 'a builder can only be built once');
+    this.isBuilt = true;
+    this.ones.sort(ascending);
+    let hasMultiplicity = false;
+    for (let i = 1; i < this.ones.length; i++) {
+      hasMultiplicity ||= this.ones[i - 1] === this.ones[i];
+    }
+    if (hasMultiplicity) {
+      return new SparseBitVec(this.ones, this.universeSize);
+    }
+    return buildSmallest(this.universeSize, this.ones, options);
+  }
+
+  /**
+   * @param {number} universeSize
+   * @param {ArrayLike<number>} sortedOnes
+   * @param {DenseBuildOptions} [options]
+   */
+  static fromSortedOnes(universeSize, sortedOnes, options = {}) {
+    assertUniverseSize(universeSize);
+    DEBUG && assertStrictlyIncreasing(sortedOnes);
+    return buildSmallest(universeSize, sortedOnes, options);
+  }
+}
+
+/**
+ * @typedef {{
+ *   rank1SamplesPow2?: number,
+ *   selectSamplesPow2?: number,
+ *   smallThreshold?: number,
+ *   buildSelect0Samples?: boolean,
+ *   buildSelect1Samples?: boolean
+ * }} DenseBuildOptions
+ */
+
+/**
+ * Build whichever of a dense or sparse bit vector is estimated to be smaller,
+ * preferring the dense one in case of a tie since its operations are faster.
+ * @param {number} universeSize
+ * @param {ArrayLike<number>} sortedOnes - strictly increasing 1-bit positions
+ * @param {DenseBuildOptions} options
+ */
+function buildSmallest(universeSize, sortedOnes, options) {
+  const numOnes = sortedOnes.length;
+  if (sparseSizeInBits(universeSize, numOnes) < denseSizeInBits(universeSize, numOnes, options)) {
+    return new SparseBitVec(Array.from(sortedOnes), universeSize);
+  }
+  return DenseBitVecBuilder.fromSortedOnes(universeSize, sortedOnes, options);
+}
+
+/**
+ * Estimated size in bits of a `DenseBitVec` built with the given options,
+ * counting its data blocks and 32-bit samples but not its fixed-size fields.
+ * @param {number} universeSize
+ * @param {number} numOnes
+ * @param {DenseBuildOptions} options
+ */
+function denseSizeInBits(universeSize, numOnes, {
+  rank1SamplesPow2 = 10,
+  selectSamplesPow2 = 10,
+  smallThreshold = DefaultSmallThreshold,
+  buildSelect0Samples = true,
+  buildSelect1Samples = true
+} = {}) {
+  const dataBits = Math.ceil(universeSize / bits.BasicBlockSize) * bits.BasicBlockSize;
+  if (universeSize <= smallThreshold) {
+    return dataBits;
+  }
+  const numZeros = universeSize - numOnes;
+  const numRank1Samples = Math.ceil(universeSize / 2 ** rank1SamplesPow2);
+  const numSelect1Samples = buildSelect1Samples ? Math.ceil(numOnes / 2 ** selectSamplesPow2) : 0;
+  const numSelect0Samples = buildSelect0Samples ? Math.ceil(numZeros / 2 ** selectSamplesPow2) : 0;
+  return dataBits + 32 * (numRank1Samples + numSelect1Samples + numSelect0Samples);
+}
+
+/**
+ * Estimated size in bits of a `SparseBitVec` with `numOnes` unique 1-bits,
+ * counting its low bits and its dense high bit vector.
+ * @param {number} universeSize
+ * @param {number} numOnes
+ */
+function sparseSizeInBits(universeSize, numOnes) {
+  // These mirror the split point and high bit vector chosen by the `SparseBitVec` constructor.
+  const lowBitWidth = numOnes === 0 ? 0 : Math.floor(Math.log2(Math.max(1, universeSize / numOnes)));
+  const highLength = numOnes + (universeSize >>> lowBitWidth);
+  return numOnes * lowBitWidth + denseSizeInBits(highLength, numOnes, {});
+}
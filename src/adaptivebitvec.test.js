@@ -0,0 +1,38 @@
+import { describe, expect, test } from 'vitest';
+import { AdaptiveBitVecBuilder } from './adaptivebitvec.js';
+import { DenseBitVec } from './densebitvec.js';
+import { SparseBitVec } from './sparsebitvec.js';
+import { testBitVecType, testMultiBitVecType } from './testutils.js';
+
+testBitVecType(AdaptiveBitVecBuilder);
+testMultiBitVecType(AdaptiveBitVecBuilder);
+
+describe('AdaptiveBitVecBuilder', () => {
+  test('builds a sparse bit vector from sparse input', () => {
+    const builder = new AdaptiveBitVecBuilder(100_000);
+    for (let i = 0; i < 10; i++) builder.one(i * 9_999);
+    const bv = builder.build();
+    expect(bv instanceof SparseBitVec).toBe(true);
+    expect(bv.select1(9)).toBe(9 * 9_999);
+    expect(AdaptiveBitVecBuilder.fromSortedOnes(100_000, [1, 50_000]) instanceof SparseBitVec).toBe(true);
+  });
+
+  test('builds a dense bit vector from dense input', () => {
+    const builder = new AdaptiveBitVecBuilder(10_000);
+    for (let i = 0; i < 10_000; i += 2) builder.one(i);
+    const bv = builder.build();
+    expect(bv instanceof DenseBitVec).toBe(true);
+    expect(bv.rank1(10_000)).toBe(5_000);
+    expect(AdaptiveBitVecBuilder.fromSortedOnes(10, [1, 2, 3, 5, 8]) instanceof DenseBitVec).toBe(true);
+  });
+
+  test('builds a sparse bit vector from dense input with multiplicity', () => {
+    const builder = new AdaptiveBitVecBuilder(10);
+    for (let i = 0; i < 10; i++) builder.one(i);
+    builder.one(3);
+    const bv = builder.build();
+    expect(bv instanceof SparseBitVec).toBe(true);
+    expect(bv.hasMultiplicity).toBe(true);
+    expect(bv.rank1(4)).toBe(5);
+  });
+});
@@ -0,0 +1,56 @@
+import fc from 'fast-check';
+import { describe, expect, it } from 'vitest';
+import { AlignedPayload } from './alignedpayload.js';
+import './debug.js';
+import { Range } from './range.js';
+import { constructionStrategy, WaveletMatrix } from './waveletmatrix.js';
+
+/**
+ * Check that the payload of each node returned by `counts` is the payload of the elements
+ * in the query range with the node's symbol, in index order.
+ * @param {number[]} data
+ * @param {number} maxSymbol
+ */
+function checkAlignment(data, maxSymbol) {
+  const wm = new WaveletMatrix(data, maxSymbol);
+  const ids = Uint32Array.from(data, (_, i) => 1000 + i);
+  const typed = new AlignedPayload(wm, ids);
+  const labels = new AlignedPayload(wm, data.map((_, i) => `label ${i}`));
+  for (const range of [Range(0, data.length), Range(Math.floor(data.length / 3), Math.floor(data.length / 2))]) {
+    for (const node of wm.counts({ range })) {
+      const indices = [];
+      for (let i = range.start; i < range.end; i++) {
+        if (data[i] === node.symbol) indices.push(i);
+      }
+      expect(Array.from(typed.getForCounts(node))).toEqual(indices.map(i => ids[i]));
+      expect(labels.getForCounts(node)).toEqual(indices.map(i => `label ${i}`));
+    }
+  }
+  expect(Array.from(typed.getForBottomRange(Range(0, data.length))).sort((a, b) => a - b)).toEqual(Array.from(ids));
+}
+
+describe('AlignedPayload', () => {
+  it('aligns payload slices with counts nodes', () => {
+    fc.assert(fc.property(fc.array(fc.nat(1000), { maxLength: 300 }), data => {
+      checkAlignment(data, Math.max(0, ...data));
+    }));
+  });
+
+  it('aligns with both construction strategies', () => {
+    const n = 5000;
+    const small = Array.from({ length: n }, (_, i) => (Math.imul(i, 2654435761) >>> 0) % 13);
+    expect(constructionStrategy(small.length, 4)).toBe('histogram');
+    checkAlignment(small, 12);
+    const large = Array.from({ length: 100 }, (_, i) => (Math.imul(i, 2654435761) >>> 0) % 100000);
+    expect(constructionStrategy(large.length, 17)).toBe('sort');
+    checkAlignment(large, 99999);
+  });
+
+  it('validates its inputs', () => {
+    const wm = new WaveletMatrix([1, 2, 3]);
+    expect(() => new AlignedPayload(wm, [1, 2])).toThrow('expected the payload length (2) to equal the wavelet matrix length (3)');
+    const payload = new AlignedPayload(wm, [1, 2, 3]);
+    expect(payload.getForBottomRange(Range(3, 3))).toEqual([]);
+    expect(() => payload.getForBottomRange(Range(2, 4))).toThrow('expected a bottom-level range within [0, 3], got [2, 4)');
+  });
+});
@@ -43,6 +43,40 @@ export function assertInteger(x) {
   assert(Number.isInteger(x), () => `expected integer, got ${x}`);
 };
 
+/**
+ * Universe sizes are limited to 32 bits since bit indices are manipulated with 32-bit bitwise operations.
+ * Checking universe sizes at construction time also catches arguments passed in the wrong order,
+ * eg. an array of 1-bits in place of a universe size.
+ * @param {number} universeSize
+ */
+export function assertUniverseSize(universeSize) {
+  assert(
+    Number.isInteger(universeSize) && universeSize >= 0 && universeSize < 2 ** 32,
+    () => `expected universeSize to be an integer in [0, 2^32), got ${universeSize}`
+  );
+};
+
+/**
+ * Checks a 1-bit index passed to a bit vector builder. This is a hard check rather than a debug
+ * one since an invalid index would otherwise surface far from the call site, if at all, eg. as a
+ * bit vector whose counts are inconsistent with its universe size.
+ * @param {number} index
+ * @param {number} universeSize
+ */
+export function assertBitIndex(index, universeSize) {
+  assert(Number.isInteger(index) && index >= 0, () => `index (${index}) must be a non-negative integer`);
+  assert(index < universeSize, () => `index (${index}) cannot exceed universeSize (${universeSize})`);
+}
+
+/**
+ * @param {ArrayLike<number>} xs
+ */
+export function assertStrictlyIncreasing(xs) {
+  for (let i = 1; i < xs.length; i++) {
+    assert(xs[i - 1] < xs[i], () => `expected strictly increasing values, got ${xs[i - 1]} followed by ${xs[i]}`);
+  }
+};
+
 /**
  * @param {any} x
  */
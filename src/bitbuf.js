@@ -1,4 +1,4 @@
-import { assert, assertSafeInteger } from './assert.js';
+import { assert, assertSafeInteger, assertUniverseSize } from './assert.js';
 import * as bits from './bits.js';
 import { u32 } from './bits.js';
 import './debug.js';
@@ -13,29 +13,47 @@ export class BitBuf {
    * @param {number} universeSize - the length of this bit buffer, in bits
  */
   constructor(universeSize) {
-    assertSafeInteger(universeSize);
-    assert(universeSize >= 0);
+    assertUniverseSize(universeSize);
     const numBlocks = Math.ceil(universeSize / bits.BasicBlockSize);
 
-    /** @readonly */
+    // These properties are not marked readonly since they may change if the buffer is trimmed.
     this.blocks = new bits.BasicBlockArray(numBlocks);
-
-    /** @readonly */
     this.universeSize = universeSize;
-
-    /** @readonly */
     this.numBlocks = this.blocks.length;
-
-    const lastBlockOccupancy = universeSize % bits.BasicBlockSize;
     
     /** 
      * Number of trailing bits in the final block that do not belong to this buffer.
      * These bits should not be assumed to be in any particular condition (eg. the
      * padded bitvector may return them as 1-bits).
-     * @readonly */
-    this.numTrailingBits = lastBlockOccupancy === 0 ? 0 : bits.BasicBlockSize - lastBlockOccupancy;
+     */
+    this.numTrailingBits = numTrailingBits(universeSize);
   } 
 
+  /**
+   * Shrink this buffer to `newUniverseSize` bits, dropping any whole trailing blocks
+   * and clearing the bits beyond the new universe size in the final block.
+   * This is useful when the final length is not known up front, so that a buffer
+   * can be over-allocated and then trimmed before it is used to build a bit vector.
+   * @param {number} newUniverseSize
+   */
+  trimTo(newUniverseSize) {
+    assertSafeInteger(newUniverseSize);
+    assert(
+      newUniverseSize >= 0 && newUniverseSize <= this.universeSize,
+      () => `cannot trim a BitBuf with universeSize ${this.universeSize} to ${newUniverseSize}`
+    );
+    const numBlocks = Math.ceil(newUniverseSize / bits.BasicBlockSize);
+    if (numBlocks < this.numBlocks) {
+      this.blocks = this.blocks.slice(0, numBlocks);
+    }
+    this.universeSize = newUniverseSize;
+    this.numBlocks = numBlocks;
+    this.numTrailingBits = numTrailingBits(newUniverseSize);
+    if (numBlocks > 0) {
+      this.blocks[numBlocks - 1] &= bits.oneMask(bits.BasicBlockSize - this.numTrailingBits);
+    }
+  }
+
   /**
    * Set the bit at index `bitIndex` to a 1-bit.
    * @param {number} bitIndex
@@ -82,6 +100,17 @@ export class BitBuf {
     return this.blocks[index];
   }
 
+  /**
+   * Iterate over the `numBlocks` blocks of this buffer in order. Together with
+   * `numTrailingBits`, this allows the buffer to be serialized block by block.
+   * @returns {Generator<number>}
+   */
+  *iterBlocks() {
+    for (let i = 0; i < this.numBlocks; i++) {
+      yield this.blocks[i];
+    }
+  }
+
   /**
    * @param {number} threshold - a number in [0, 1] controlling when to zero-compress
    * if we "compressed" the blocks to less than `threshold` % of the original
@@ -101,6 +130,16 @@ export class BitBuf {
   }
 }
 
+/**
+ * Number of trailing bits in the final block of a buffer of the given universe size
+ * that do not belong to the buffer.
+ * @param {number} universeSize
+ */
+function numTrailingBits(universeSize) {
+  const lastBlockOccupancy = universeSize % bits.BasicBlockSize;
+  return lastBlockOccupancy === 0 ? 0 : bits.BasicBlockSize - lastBlockOccupancy;
+}
+
 /**
  * @param {number[] | Uint32Array} array
  * @param {number} value
@@ -184,6 +223,18 @@ export class PaddedBitBuf {
     else return this.blocks[index - this.left];
   }
 
+  /**
+   * Iterate over the blocks of the original buffer in order, yielding
+   * the padding block for blocks outside of the stored region.
+   * @returns {Generator<number>}
+   */
+  *iterBlocks() {
+    // Note that `left` may exceed `right` if the buffer consists entirely of padding.
+    for (let i = 0; i < this.numBlocks; i++) {
+      yield i < this.left || i >= this.right ? this.blockPadding : this.blocks[i - this.left];
+    }
+  }
+
   /** 
    * @param {number} bitIndex - bit index
    */
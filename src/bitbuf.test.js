@@ -1,6 +1,6 @@
 import { describe, expect, it, test } from 'vitest';
 import { BitBuf } from './bitbuf.js';
-import { bits } from './index.js';
+import * as bits from './bits.js';
 
 // todo: the padding code should be better-tested if we keep this approach.
 // tests in other files keep failing due to bugs in this code.
@@ -167,4 +167,112 @@ describe('BitBuf', () => {
     const zp = buf.maybePadded();
     expect(zp.blocks.length).toBe(0);
   });
+
+  describe('trimTo', () => {
+    /**
+     * Fill a buffer of size `universeSize` with every third bit set, trim it, and
+     * check that it matches a freshly-constructed buffer of the target size.
+     * @param {number} universeSize
+     * @param {number} newUniverseSize
+     */
+    function checkTrim(universeSize, newUniverseSize) {
+      const buf = new BitBuf(universeSize);
+      const expected = new BitBuf(newUniverseSize);
+      for (let i = 0; i < universeSize; i += 3) {
+        buf.setOne(i);
+        if (i < newUniverseSize) expected.setOne(i);
+      }
+      buf.trimTo(newUniverseSize);
+      expect(buf.universeSize).toBe(expected.universeSize);
+      expect(buf.numBlocks).toBe(expected.numBlocks);
+      expect(buf.numTrailingBits).toBe(expected.numTrailingBits);
+      expect(buf.blocks).toEqual(expected.blocks);
+      for (let i = 0; i < newUniverseSize; i++) {
+        expect(buf.get(i)).toBe(expected.get(i));
+      }
+      if (DEBUG) {
+        expect(() => buf.get(newUniverseSize)).toThrow();
+      }
+    }
+
+    it('trims at block boundaries', () => {
+      checkTrim(10 * bits.BasicBlockSize, 4 * bits.BasicBlockSize);
+      checkTrim(10 * bits.BasicBlockSize + 5, 10 * bits.BasicBlockSize);
+    });
+
+    it('trims mid-block', () => {
+      checkTrim(10 * bits.BasicBlockSize, 4 * bits.BasicBlockSize + 7);
+      checkTrim(10 * bits.BasicBlockSize, 10 * bits.BasicBlockSize - 1);
+      checkTrim(20, 13);
+    });
+
+    it('trims to zero', () => {
+      checkTrim(100, 0);
+      checkTrim(0, 0);
+    });
+
+    it('cannot grow the buffer', () => {
+      const buf = new BitBuf(10);
+      expect(() => buf.trimTo(11)).toThrow();
+      expect(() => buf.trimTo(-1)).toThrow();
+    });
+  });
+
+  describe('iterBlocks', () => {
+    it('yields the blocks of a BitBuf', () => {
+      const buf = new BitBuf(10 * bits.BasicBlockSize + 5);
+      buf.setOne(3);
+      buf.setOne(4 * bits.BasicBlockSize + 1);
+      buf.setOne(10 * bits.BasicBlockSize + 4);
+      expect(Array.from(buf.iterBlocks())).toEqual(Array.from(buf.blocks));
+      expect(Array.from(new BitBuf(0).iterBlocks())).toEqual([]);
+    });
+
+    it('yields only padding for a PaddedBitBuf that is entirely padding', () => {
+      const buf = new BitBuf(5 * bits.BasicBlockSize);
+      buf.blocks.fill(bits.oneMask(bits.BasicBlockSize));
+      const zp = buf.maybePadded();
+      expect(zp.blocks.length).toBe(0);
+      expect(Array.from(zp.iterBlocks())).toEqual(Array.from(buf.blocks));
+      expect(Array.from(new BitBuf(3 * bits.BasicBlockSize).maybePadded().iterBlocks())).toEqual([0, 0, 0]);
+    });
+
+    it('reconstructs the original blocks from a PaddedBitBuf', () => {
+      const universeSize = 20 * bits.BasicBlockSize + 7;
+      for (const fill of [0, bits.oneMask(bits.BasicBlockSize)]) {
+        const buf = new BitBuf(universeSize);
+        buf.blocks.fill(fill);
+        // clear the trailing bits so that the original buffer is canonical
+        buf.trimTo(universeSize);
+        for (const i of [5 * bits.BasicBlockSize + 2, 12 * bits.BasicBlockSize + 9]) {
+          if (fill === 0) buf.setOne(i);
+          else buf.setZero(i);
+        }
+        const zp = buf.maybePadded();
+        expect(zp.blocks.length).toBeLessThan(buf.numBlocks);
+        const blocks = Array.from(zp.iterBlocks());
+        expect(blocks).toHaveLength(buf.numBlocks);
+        const trailingMask = bits.oneMask(bits.BasicBlockSize - buf.numTrailingBits);
+        for (let i = 0; i < buf.numBlocks; i++) {
+          const mask = i === buf.numBlocks - 1 ? trailingMask : bits.oneMask(bits.BasicBlockSize);
+          expect(bits.u32(blocks[i] & mask)).toBe(bits.u32(buf.blocks[i] & mask));
+          expect(blocks[i]).toBe(zp.getBlock(i));
+        }
+      }
+    });
+  });
+
+  if (DEBUG) {
+    it('bounds-checks padded buffers against the original universe (in debug mode)', () => {
+      const buf = new BitBuf(10 * bits.BasicBlockSize + 5);
+      buf.setOne(3 * bits.BasicBlockSize);
+      const zp = buf.maybePadded();
+      // the padding extends to the end of the original universe, but no further
+      expect(zp.get(buf.universeSize - 1)).toBe(0);
+      expect(zp.getBlock(buf.numBlocks - 1)).toBe(0);
+      expect(() => zp.get(buf.universeSize)).toThrow();
+      expect(() => zp.getBlock(buf.numBlocks)).toThrow();
+      expect(() => zp.getBlock(-1)).toThrow();
+    });
+  }
 });
@@ -0,0 +1,71 @@
+import { assert } from './assert.js';
+
+/**
+ * Growable table of named `Uint32Array` columns, filled one row at a time, for handing query
+ * results to consumers that work with parallel arrays (eg. a renderer or another thread) without
+ * converting them entry by entry. Each column's storage is reallocated as it grows, and `column`
+ * returns a view of its filled prefix, so the view is invalidated by subsequent pushes.
+ */
+export class Columns {
+  /**
+   * @param {string[]} names
+   * @param {number} [capacity] - initial number of rows to allocate space for
+   */
+  constructor(names, capacity = 16) {
+    assert(new Set(names).size === names.length, () => `expected unique column names, got ${names}`);
+    this.names = names;
+    this.length = 0;
+    this.data = names.map(() => new Uint32Array(Math.max(1, capacity)));
+  }
+
+  /**
+   * Append a row, with one value per column in the order of `names`.
+   * @param {number[]} values
+   */
+  push(...values) {
+    DEBUG && assert(values.length === this.names.length, () => `expected ${this.names.length} values, got ${values.length}`);
+    if (this.length === this.data[0].length) {
+      this.data = this.data.map(column => {
+        const grown = new Uint32Array(2 * column.length);
+        grown.set(column);
+        return grown;
+      });
+    }
+    for (let i = 0; i < values.length; i++) {
+      this.data[i][this.length] = values[i];
+    }
+    this.length++;
+  }
+
+  /**
+   * Returns a view of the filled part of the named column.
+   * @param {string} name
+   */
+  column(name) {
+    const i = this.names.indexOf(name);
+    assert(i !== -1, () => `no column named ${name}`);
+    return this.data[i].subarray(0, this.length);
+  }
+
+  /**
+   * Sort the rows in place by the values of the named columns, comparing by the first column
+   * and breaking ties with the following ones. The sort is stable.
+   * @param {string[]} names
+   */
+  sortBy(...names) {
+    const keys = names.map(name => this.column(name));
+    const permutation = Array.from({ length: this.length }, (_, i) => i).sort((a, b) => {
+      for (const key of keys) {
+        if (key[a] !== key[b]) return key[a] - key[b];
+      }
+      return a - b;
+    });
+    this.data = this.data.map(column => {
+      const sorted = new Uint32Array(column.length);
+      for (let i = 0; i < permutation.length; i++) {
+        sorted[i] = column[permutation[i]];
+      }
+      return sorted;
+    });
+  }
+}
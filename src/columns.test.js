@@ -0,0 +1,25 @@
+import { describe, expect, test } from 'vitest';
+import { Columns } from './columns.js';
+import './debug.js';
+
+describe('Columns', () => {
+  test('push, column and sortBy', () => {
+    const columns = new Columns(['a', 'b'], 1);
+    const rows = [[3, 1], [1, 7], [3, 0], [2, 5], [1, 2]];
+    for (const row of rows) columns.push(...row);
+    expect(columns.length).toBe(rows.length);
+    expect(columns.column('a')).toEqual(new Uint32Array([3, 1, 3, 2, 1]));
+    expect(columns.column('b')).toEqual(new Uint32Array([1, 7, 0, 5, 2]));
+
+    columns.sortBy('a');
+    // the sort is stable
+    expect(columns.column('a')).toEqual(new Uint32Array([1, 1, 2, 3, 3]));
+    expect(columns.column('b')).toEqual(new Uint32Array([7, 2, 5, 1, 0]));
+
+    columns.sortBy('a', 'b');
+    expect(columns.column('b')).toEqual(new Uint32Array([2, 7, 5, 0, 1]));
+
+    expect(() => columns.column('c')).toThrow('no column named c');
+    expect(() => new Columns(['a', 'a'])).toThrow('unique column names');
+  });
+});
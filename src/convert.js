@@ -0,0 +1,36 @@
+import { assert } from './assert.js';
+import { DenseBitVec, DenseBitVecBuilder } from './densebitvec.js';
+import { SparseBitVec } from './sparsebitvec.js';
+
+// Conversions between dense and sparse bit vectors. These live in their own module because
+// `SparseBitVec` is built on top of `DenseBitVec`, so the reverse import would make the two
+// bit vector modules depend on each other.
+
+/**
+ * Returns a sparse bit vector with the same 1-bits, which answers the same queries,
+ * eg. to compress a bit vector that turned out to be sparse. See `DenseBitVec.isSparse`.
+ * @param {DenseBitVec} bv
+ */
+export function toSparse(bv) {
+  const ones = new Uint32Array(bv.numOnes);
+  for (let n = 0; n < bv.numOnes; n++) {
+    ones[n] = bv.select1(n);
+  }
+  return new SparseBitVec(ones, bv.universeSize);
+}
+
+/**
+ * Returns a dense bit vector with the same 1-bits, which answers the same queries.
+ * Bit vectors with multiplicity cannot be represented densely.
+ * @param {SparseBitVec} bv
+ * @param {object} [options] - options passed to the dense builder's `build` method
+ * @returns {DenseBitVec}
+ */
+export function toDense(bv, options = {}) {
+  assert(!bv.hasMultiplicity, 'cannot convert a bit vector with multiplicity to a dense bit vector');
+  const builder = new DenseBitVecBuilder(bv.universeSize);
+  for (let n = 0; n < bv.numOnes; n++) {
+    builder.one(bv.select1(n));
+  }
+  return builder.build(options);
+}
@@ -0,0 +1,26 @@
+import { describe, expect, test } from 'vitest';
+import { toDense, toSparse } from './convert.js';
+import { DenseBitVec } from './densebitvec.js';
+import { SparseBitVec } from './sparsebitvec.js';
+
+describe('convert', () => {
+  test('converts to and from dense bit vectors', () => {
+    for (const universeSize of [0, 1, 31, 32, 33, 1000]) {
+      for (const step of [1, 2, 7, 100]) {
+        const ones = Array.from({ length: Math.ceil(universeSize / step) }, (_, i) => i * step);
+        const sparse = new SparseBitVec(ones, universeSize);
+        const dense = toDense(sparse);
+        expect(dense instanceof DenseBitVec).toBe(true);
+        const roundTripped = toSparse(dense);
+        for (const bv of [dense, roundTripped]) {
+          expect(bv.universeSize).toBe(universeSize);
+          expect(Array.from({ length: bv.numOnes }, (_, n) => bv.select1(n))).toEqual(ones);
+          for (let i = 0; i <= universeSize; i++) {
+            expect(bv.rank1(i)).toBe(sparse.rank1(i));
+          }
+        }
+      }
+    }
+    expect(() => toDense(new SparseBitVec([1, 1], 3))).toThrow('cannot convert a bit vector with multiplicity');
+  });
+});
@@ -0,0 +1,149 @@
+import { assert } from './assert.js';
+
+/**
+ * Difference between two sets of symbol counts, such as the results of `symbolCounts` for
+ * consecutive frames of an interaction, in a compact columnar form. When most counts are
+ * unchanged between frames, sending the delta rather than the full result saves both
+ * serialization and transfer time, and the receiver reconstructs the new result with `apply`.
+ *
+ * Counts are represented as arrays of `[symbol, count]` pairs in strictly ascending symbol
+ * order, so that the difference can be computed with a linear merge. The delta records a
+ * fingerprint of the counts it was computed from, and `apply` throws if it is given
+ * different counts, which catches deltas applied out of order or to the wrong result.
+ */
+export class CountsDelta {
+  /**
+   * Compute the delta that turns `prev` into `next`.
+   * @param {[number, number][]} prev
+   * @param {[number, number][]} next
+   */
+  static diff(prev, next) {
+    assertSortedBySymbol(prev);
+    assertSortedBySymbol(next);
+    const delta = new CountsDelta(prev.length, fingerprint(prev));
+    let i = 0;
+    let j = 0;
+    while (i < prev.length || j < next.length) {
+      if (j === next.length || (i < prev.length && prev[i][0] < next[j][0])) {
+        delta.removed.push(prev[i][0]);
+        i++;
+      } else if (i === prev.length || next[j][0] < prev[i][0]) {
+        delta.added.push(next[j][0], next[j][1]);
+        j++;
+      } else {
+        if (prev[i][1] !== next[j][1]) {
+          delta.changed.push(next[j][0], next[j][1]);
+        }
+        i++;
+        j++;
+      }
+    }
+    return delta;
+  }
+
+  /**
+   * @param {number} baseLength - number of entries in the counts the delta applies to
+   * @param {number} baseFingerprint - fingerprint of those counts
+   */
+  constructor(baseLength, baseFingerprint) {
+    this.baseLength = baseLength;
+    this.baseFingerprint = baseFingerprint;
+    /**
+     * Symbols that are absent from the base, interleaved with their counts: `[symbol, count, symbol, count, ...]`
+     * @type {number[]}
+     */
+    this.added = [];
+    /**
+     * Symbols of the base that are absent after the delta is applied
+     * @type {number[]}
+     */
+    this.removed = [];
+    /**
+     * Symbols of the base whose counts differ, interleaved with their new counts, like `added`
+     * @type {number[]}
+     */
+    this.changed = [];
+  }
+
+  /**
+   * Whether applying this delta leaves the counts unchanged.
+   */
+  isEmpty() {
+    return this.added.length === 0 && this.removed.length === 0 && this.changed.length === 0;
+  }
+
+  /**
+   * Return the delta as `Uint32Array` columns, eg. for transfer to another thread.
+   */
+  columns() {
+    const { added, removed, changed } = this;
+    return {
+      addedSymbols: Uint32Array.from({ length: added.length / 2 }, (_, i) => added[2 * i]),
+      addedCounts: Uint32Array.from({ length: added.length / 2 }, (_, i) => added[2 * i + 1]),
+      removedSymbols: Uint32Array.from(removed),
+      changedSymbols: Uint32Array.from({ length: changed.length / 2 }, (_, i) => changed[2 * i]),
+      changedCounts: Uint32Array.from({ length: changed.length / 2 }, (_, i) => changed[2 * i + 1]),
+    };
+  }
+
+  /**
+   * Return the counts that result from applying this delta to `base`, which must be the
+   * counts the delta was computed from. `base` is not modified.
+   * @param {[number, number][]} base
+   * @returns {[number, number][]}
+   */
+  apply(base) {
+    assert(
+      base.length === this.baseLength && fingerprint(base) === this.baseFingerprint,
+      'the delta was computed from different counts than the ones it is applied to'
+    );
+    const { added, removed, changed } = this;
+    /** @type {[number, number][]} */
+    const result = [];
+    let a = 0;
+    let r = 0;
+    let c = 0;
+    for (const [symbol, count] of base) {
+      for (; a < added.length && added[a] < symbol; a += 2) {
+        result.push([added[a], added[a + 1]]);
+      }
+      if (r < removed.length && removed[r] === symbol) {
+        r++;
+      } else if (c < changed.length && changed[c] === symbol) {
+        result.push([symbol, changed[c + 1]]);
+        c += 2;
+      } else {
+        result.push([symbol, count]);
+      }
+    }
+    for (; a < added.length; a += 2) {
+      result.push([added[a], added[a + 1]]);
+    }
+    return result;
+  }
+}
+
+/**
+ * 32-bit FNV-1a hash of the symbols and counts.
+ * @param {[number, number][]} counts
+ */
+function fingerprint(counts) {
+  let hash = 0x811c9dc5;
+  for (const [symbol, count] of counts) {
+    hash = Math.imul(hash ^ symbol, 0x01000193);
+    hash = Math.imul(hash ^ count, 0x01000193);
+  }
+  return hash >>> 0;
+}
+
+/**
+ * @param {[number, number][]} counts
+ */
+function assertSortedBySymbol(counts) {
+  for (let i = 1; i < counts.length; i++) {
+    assert(
+      counts[i - 1][0] < counts[i][0],
+      () => `expected counts in strictly ascending symbol order, got symbol ${counts[i - 1][0]} followed by ${counts[i][0]}`
+    );
+  }
+}
@@ -0,0 +1,61 @@
+import fc from 'fast-check';
+import { describe, expect, it } from 'vitest';
+import { CountsDelta } from './countsdelta.js';
+import './debug.js';
+import { WaveletMatrix } from './waveletmatrix.js';
+
+/**
+ * Arbitrary counts as `[symbol, count]` pairs in ascending symbol order
+ */
+const arbitraryCounts = fc.array(fc.tuple(fc.nat(50), fc.integer({ min: 1, max: 5 })), { maxLength: 40 })
+  .map(pairs => Array.from(new Map(pairs)).sort((a, b) => a[0] - b[0]));
+
+describe('CountsDelta', () => {
+  it('round-trips', () => {
+    fc.assert(fc.property(arbitraryCounts, arbitraryCounts, (prev, next) => {
+      const delta = CountsDelta.diff(prev, next);
+      expect(delta.apply(prev)).toEqual(next);
+      const { addedSymbols, addedCounts, removedSymbols, changedSymbols, changedCounts } = delta.columns();
+      expect(addedSymbols.length).toBe(addedCounts.length);
+      expect(changedSymbols.length).toBe(changedCounts.length);
+      // each symbol is in the delta at most once, and unchanged symbols are not in it at all
+      const prevCounts = new Map(prev);
+      const nextCounts = new Map(next);
+      const numChanged = Array.from(nextCounts).filter(([symbol, count]) => prevCounts.get(symbol) !== count).length;
+      const numRemoved = prev.filter(([symbol]) => !nextCounts.has(symbol)).length;
+      expect(addedSymbols.length + changedSymbols.length).toBe(numChanged);
+      expect(removedSymbols.length).toBe(numRemoved);
+    }));
+  });
+
+  it('is empty for identical counts', () => {
+    fc.assert(fc.property(arbitraryCounts, counts => {
+      const delta = CountsDelta.diff(counts, counts.map(([symbol, count]) => [symbol, count]));
+      expect(delta.isEmpty()).toBe(true);
+      expect(delta.apply(counts)).toEqual(counts);
+    }));
+  });
+
+  it('tracks counts across consecutive ranges', () => {
+    const data = Array.from({ length: 500 }, (_, i) => (i * 37 + (i >> 3)) % 29);
+    const wm = new WaveletMatrix(data);
+    let current = wm.symbolCounts({ range: { start: 0, end: 100 } });
+    for (let start = 5; start < 400; start += 5) {
+      const next = wm.symbolCounts({ range: { start, end: start + 100 } });
+      const delta = CountsDelta.diff(current, next);
+      current = delta.apply(current);
+      expect(current).toEqual(next);
+    }
+  });
+
+  it('detects misuse', () => {
+    const a = [[1, 2], [3, 4]];
+    const b = [[1, 2], [3, 5]];
+    const delta = CountsDelta.diff(a, [[2, 1]]);
+    expect(() => delta.apply(b)).toThrow('the delta was computed from different counts');
+    expect(() => delta.apply([[1, 2]])).toThrow('the delta was computed from different counts');
+    // applying the same delta twice is also caught, since the result differs from the base
+    expect(() => delta.apply(delta.apply(a))).toThrow('the delta was computed from different counts');
+    expect(() => CountsDelta.diff([[3, 1], [1, 1]], [])).toThrow('expected counts in strictly ascending symbol order, got symbol 3 followed by 1');
+  });
+});
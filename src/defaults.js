@@ -1,5 +1,6 @@
-import { assert } from './assert.js';
-import { partitionPoint } from './bits.js';
+import { assert, assertStrictlyIncreasing } from './assert.js';
+import { sampleSortedDistinct } from './sample.js';
+import { BasicBlockArray, basicBlockBitOffset, basicBlockIndex, BasicBlockSize, partitionPoint } from './bits.js';
 
 // This module provides default implementations for some bit vector functions.
 // They're meant to be used only when we need a somewhat slow fallback.
@@ -44,7 +45,7 @@ export function trySelect0(bv, n) {
  */
 export function trySelect1(bv, n) {
   assert(!bv.hasMultiplicity, 'cannot take select1 in the presence of multiplicity (repeated elements)');
-  if (n < 0 || n >= bv.numZeros) {
+  if (n < 0 || n >= bv.numOnes) {
     return null;
   }
   // Binary search over rank1 to determine the position of the n-th 1-bit.
@@ -52,6 +53,95 @@ export function trySelect1(bv, n) {
   return index - 1;
 }
 
+/**
+ * Like `rank1`, but also reports whether the index was clamped to the universe:
+ * `'start'` for negative indices, `'end'` for indices at or beyond the universe size
+ * (for which the count is `numOnes`), and `null` otherwise.
+ * @param {BitVec} bv
+ * @param {number} index
+ * @returns {RankResult}
+ */
+export function rank1Checked(bv, index) {
+  if (index < 0) {
+    return { count: 0, clamped: 'start' };
+  } else if (index >= bv.universeSize) {
+    return { count: bv.numOnes, clamped: 'end' };
+  }
+  return { count: bv.rank1(index), clamped: null };
+}
+
+/**
+ * Count the 1-bits in the inclusive index range `[first, last]`, or 0 if `last < first`.
+ * The range may extend beyond the universe on either side, so `last` can be the largest 32-bit
+ * index (2^32 - 1) even though the largest universe only has indices up to 2^32 - 2.
+ * @param {BitVec} bv
+ * @param {number} first
+ * @param {number} last
+ */
+export function countOnesInInclusive(bv, first, last) {
+  if (last < first) {
+    return 0;
+  }
+  return bv.rank1Checked(last + 1).count - bv.rank1Checked(first).count;
+}
+
+/**
+ * Returns the positions of `min(k, numOnes)` 1-bits sampled uniformly without replacement,
+ * in ascending order and determined by `seed`. The ordinals of the sampled 1-bits are
+ * sorted before selecting them so that the selects visit the bit vector in order.
+ * In the presence of multiplicity, each copy of a repeated element is a separate 1-bit,
+ * so a position may be returned more than once.
+ * @param {BitVec} bv
+ * @param {number} k
+ * @param {number} seed
+ */
+export function sampleOnes(bv, k, seed) {
+  return Array.from(sampleSortedDistinct(bv.numOnes, k, seed), n => bv.select1(n));
+}
+
+/**
+ * Iterate over the maximal runs of 0-bits within the index range, in ascending order, as
+ * `{ start, end }` ranges. Runs that extend beyond the range are clipped to it.
+ * Each run is found with a rank and a select operation, so this takes time proportional
+ * to the number of runs rather than to the length of the range.
+ * @param {BitVec} bv
+ * @param {{ start: number, end: number }} range
+ * @returns {Generator<{ start: number, end: number }>}
+ */
+export function* zeroRuns(bv, range) {
+  const end = Math.min(range.end, bv.universeSize);
+  let index = Math.max(range.start, 0);
+  while (index < end) {
+    // The first 1-bit at or after `index`, which is also correct in the presence of multiplicity
+    const nextOne = bv.trySelect1(bv.rank1(index)) ?? bv.universeSize;
+    if (nextOne > index) {
+      yield { start: index, end: Math.min(nextOne, end) };
+    }
+    if (nextOne >= end) {
+      break;
+    }
+    // Skip past the run of 1-bits starting at `nextOne`. Without select0, we step over
+    // the 1-bits one at a time.
+    index = bv.hasMultiplicity ? nextOne + 1 : bv.trySelect0(bv.rank0(nextOne)) ?? bv.universeSize;
+  }
+}
+
+/**
+ * Return the first maximal run of 0-bits within the index range whose length (after clipping
+ * to the range) is at least `minLength`, or null if there is none. Stops at the first such run.
+ * @param {BitVec} bv
+ * @param {{ start: number, end: number }} range
+ * @param {number} minLength
+ */
+export function firstZeroRunAtLeast(bv, range, minLength) {
+  for (const run of bv.zeroRuns(range)) {
+    if (run.end - run.start >= minLength) {
+      return run;
+    }
+  }
+  return null;
+}
+
 /**
  * Get the value of the bit at the specified index (0 or 1).
  * Note: This is rather inefficient since it does two rank calls,
@@ -63,7 +153,7 @@ export function trySelect1(bv, n) {
  * @param {number} index
  */
 export function get(bv, index) {
-  assert(index >= 0 && index <= bv.universeSize);
+  assert(index >= 0 && index < bv.universeSize, () => `index (${index}) is out of bounds for universeSize (${bv.universeSize})`);
   const value = bv.rank1(index + 1) - bv.rank1(index);
   if (DEBUG && !bv.hasMultiplicity) {
     assert(value === 0 || value === 1, () => `expected 0 or 1, got ${value}`);
@@ -93,4 +183,38 @@ export function select1(bv, n) {
     throw new Error(`n (${n}) is not a valid 1-bit index`);
   }
   return result;
-}
\ No newline at end of file
+}
+
+/**
+ * Return the occupancy of the bit vector (whether each bit is set, ignoring multiplicity)
+ * as an array of packed basic blocks covering the universe, with the unused high bits of
+ * the final block set to zero.
+ * 
+ * @param {BitVec} bv
+ */
+export function occupancyBlocks(bv) {
+  const blocks = new BasicBlockArray(Math.ceil(bv.universeSize / BasicBlockSize));
+  for (let n = 0; n < bv.numOnes; n++) {
+    const index = bv.select1(n);
+    blocks[basicBlockIndex(index)] |= 1 << basicBlockBitOffset(index);
+  }
+  return blocks;
+}
+
+/**
+ * Build a bit vector from an ascending array of unique 1-bit positions
+ * by setting each 1-bit in turn on a freshly-constructed builder.
+ * 
+ * @param {BitVecBuilderConstructable} Builder
+ * @param {number} universeSize
+ * @param {ArrayLike<number>} sortedOnes
+ * @param {object} options - options passed to the builder's `build` method
+ */
+export function fromSortedOnes(Builder, universeSize, sortedOnes, options) {
+  DEBUG && assertStrictlyIncreasing(sortedOnes);
+  const builder = new Builder(universeSize);
+  for (let i = 0; i < sortedOnes.length; i++) {
+    builder.one(sortedOnes[i]);
+  }
+  return builder.build(options);
+}
@@ -41,7 +41,7 @@ import * as defaults from './defaults';
 // - If a hint is present, it is used instead of the rank or select block
 // - Document the meaning of the bit vec interface elements. Incl select0. Can we have a selectUnique, for bit vecs that store occupancy and count data separately?
 
-import { assert, assertDefined, assertSafeInteger, log } from "./assert.js";
+import { assert, assertBitIndex, assertDefined, assertSafeInteger, assertUniverseSize, log } from "./assert.js";
 import { BitBuf, PaddedBitBuf } from './bitbuf.js';
 import * as bits from './bits.js';
 import { u32 } from './bits.js';
@@ -57,14 +57,16 @@ export class DenseBitVecBuilder {
   constructor(universeSize) {
     this.buf = new BitBuf(universeSize);
     this.universeSize = universeSize;
+    this.isBuilt = false;
   }
 
   /**
    * @param {number} index
    */
   one(index, count = 1) {
+    assert(!this.isBuilt, 'cannot modify a builder after it has been built');
     assert(count === 1);
-    assert(index < this.universeSize, () => `index (${index}) cannot exceed universeSize (${this.universeSize})`);
+    assertBitIndex(index, this.universeSize);
     
     // we do this to catch errors, and to be compatible with the multiset case
     // where setting a bit multiple times should add to its multiplicity.
@@ -72,11 +74,73 @@ export class DenseBitVecBuilder {
     this.buf.setOne(index);
   }
 
-  build({ rank1SamplesPow2 = 10, selectSamplesPow2 = 10 } = {}) {
-    return new DenseBitVec(this.buf.maybePadded(), rank1SamplesPow2, selectSamplesPow2);
+  /**
+   * Shrink the universe of the bit vector being built to `universeSize`,
+   * discarding any 1-bits at or above the new universe size.
+   * @param {number} universeSize
+   */
+  trimTo(universeSize) {
+    assert(!this.isBuilt, 'cannot modify a builder after it has been built');
+    this.buf.trimTo(universeSize);
+    this.universeSize = universeSize;
+  }
+
+  build({
+    rank1SamplesPow2 = 10,
+    selectSamplesPow2 = 10,
+    smallThreshold = DefaultSmallThreshold,
+    buildSelect0Samples = true,
+    buildSelect1Samples = true
+  } = {}) {
+    assert(!this.isBuilt, 'a builder can only be built once');
+    this.isBuilt = true;
+    return new DenseBitVec(
+      this.buf.maybePadded(),
+      rank1SamplesPow2,
+      selectSamplesPow2,
+      smallThreshold,
+      buildSelect0Samples,
+      buildSelect1Samples
+    );
+  }
+
+  /**
+   * @param {number} universeSize
+   * @param {ArrayLike<number>} sortedOnes
+   */
+  static fromSortedOnes(universeSize, sortedOnes, options = {}) {
+    return defaults.fromSortedOnes(DenseBitVecBuilder, universeSize, sortedOnes, options);
   }
 }
 
+/**
+ * Bit vectors with a universe size at or below this threshold (in bits) do not store rank
+ * or select samples by default, since for so few blocks a popcount scan is both faster and smaller.
+ */
+export const DefaultSmallThreshold = 4096;
+
+/**
+ * Density (fraction of 1-bits) below which `DenseBitVec.isSparse` reports that a sparse
+ * representation is likely to be smaller. A `SparseBitVec` needs about `2 + log2(1 / density)`
+ * bits per 1-bit, which matches the dense representation's one bit per bit at a density of 1/4,
+ * so we use half of that to leave room for the sampling overhead on both sides.
+ */
+export const DefaultSparseDensityThreshold = 1 / 8;
+
+/**
+ * Default sample budget for `DenseBitVec.withAutoSamples`, as a fraction of the data size.
+ * This is the overhead of the default sample rates of 2^10, where the rank samples take
+ * 32 bits per 1024 bits and the select0 and select1 samples together take another 32,
+ * for about 6%.
+ */
+export const DefaultMaxSampleOverhead = 1 / 16;
+
+/**
+ * Finest sample rate considered by `DenseBitVec.withAutoSamples`; at finer rates,
+ * the samples start to rival the data in size and queries barely get faster.
+ */
+const MinAutoSamplesPow2 = 8;
+
 /** 
  * Dense bit vector with rank and select, based on the ideas described in the paper
  * 
@@ -94,8 +158,21 @@ export class DenseBitVec {
    * @param {BitBuf | PaddedBitBuf} data - bit buffer containing the underlying bit data
    * @param {number} rank1SamplesPow2 - power of 2 of the rank sample rate
    * @param {number} selectSamplesPow2 - power of 2 of the select sample rate for both select0 and select1
+   * @param {number} smallThreshold - if the universe size is at or below this number of bits, no samples
+   * are stored and all operations scan the basic blocks from the start
+   * @param {boolean} buildSelect0Samples - if false, no select0 samples are stored, saving space at the
+   * cost of slower select0 operations, which will instead start scanning from the nearest rank sample
+   * @param {boolean} buildSelect1Samples - if false, no select1 samples are stored, saving space at the
+   * cost of slower select1 and rank1 operations
    */
-  constructor(data, rank1SamplesPow2, selectSamplesPow2) {
+  constructor(
+    data,
+    rank1SamplesPow2,
+    selectSamplesPow2,
+    smallThreshold = DefaultSmallThreshold,
+    buildSelect0Samples = true,
+    buildSelect1Samples = true
+  ) {
     // todo: 
     // - kw args for sampling rates, with 2^10 being default
     // - Accept s0Pow2, s1Pow2 instead of ssPow2 in order to control the space usage; 
@@ -110,132 +187,42 @@ export class DenseBitVec {
     assert(rank1SamplesPow2 <= 31, 'rank1SamplesPow2 must be less than 32');
     assert(selectSamplesPow2 <= 31, 'selectSamplesPow2 must be less than 32');
 
-    const select1SampleRate = u32(1 << selectSamplesPow2); // Sample every `select1SampleRate` 1-bits
-    const select0SampleRate = u32(1 << selectSamplesPow2); // Sample every `select0SampleRate` 0-bits
-    const rank1SampleRate = u32(1 << rank1SamplesPow2); // Sample every `rank1SampleRate` bits
-
-    // Each rank sample identifies a particular basic block. 
-    // 
-
-    // Rank samples are sampled every `rank1SamplingRate` bits, where `rank1SamplingRate` is a positive multiple of
-    // the bit width of a basic block. For example, if `rank1SamplingRate` is 64 and the basic
-    // block width is 32, then the rank samples will tell us about the 0th, 2nd, 4th, 6th, ... basic block.
-    //
-    // A rank sample `rank1Samples[i]` tells us about the basic block `data.blocks[i << (srPow2 - bits.BLOCK_BITS_LOG2)]`.
-    //
-    // If `rank1Samples[i] has value `v`, this means that there are `v` 1-bits preceding that basic block.
-    // Rank samples represent the number of 1-bits up to but not including a basic block.
-    const rank1Samples = []; 
-
-    // Each select1 sample identifies a particular basic block.
-    //
-    // Select samples are sampled every `select1SampleRate` 1-bits, where `rank1SamplingRate` is a positive multiple of
-    // the bit width of a basic block. Unlike rank blocks, which start sampling from 0 (representing the 
-    // `rank1SamplingRate*i + 0`-th bits), select blocks start sampling from 1, and thus represent the
-    // `select1SamplingRate*i + 1`-th bits.
-    // For example, if `select1SamplingRate` is 64, then the select1 samples will identify the basic blocks
-    // that contain the 0+1 = 1st, 64+1 = 65th, 2*64+1 = 129th, 3*64+1 = 193rd, ... bits.
-    // Since the sampling rate is a positive multiple of the basic block, two select blocks will never point 
-    // to the same basic block.
-    const select1Samples = []; 
-    const select0Samples = []; 
-
-    // Select1 samples represent the number of 1-bits up to but not including a basic block.
-    // For example, if `select1SamplingRate`
-    // is 64, then the select1 samples will tell us about the basic blocks containing the 1st
-    // A select sample `select1Samples[i]` tells us about the basic block that contains the
-    // `selectSamplingRate * i + 1`-th 1-bit.
-
-    let cumulativeOnes = 0; // 1-bits preceding the current raw block
-    let cumulativeBits = 0; // bits preceding the current raw block
-    let zerosThreshold = 0; // take a select0 sample at the (zerosThreshold+1)th 1-bit
-    let onesThreshold = 0; // take a select1 sample at the (onesThreshold+1)th 1-bit
-
-    const basicBlocksPerRank1Sample = rank1SampleRate >>> bits.BasicBlockSizePow2;
-
-    const maxBlockIndex = data.numBlocks - 1;
-    for (let blockIndex = 0; blockIndex < data.numBlocks; blockIndex++) {
-      const block = data.getBlock(blockIndex);
-      if (blockIndex % basicBlocksPerRank1Sample === 0) {
-        rank1Samples.push(cumulativeOnes);
-      }
-
-      let blockOnes = bits.popcount(block);
-      let blockZeros = bits.BasicBlockSize - blockOnes;
-      // Don't count trailing ones or zeros in the final data block towards the 0/1 count
-      if (blockIndex === maxBlockIndex) {
-        const numNonTrailingBits = bits.BasicBlockSize - data.numTrailingBits;
-        const trailingBits = block & ~bits.oneMask(numNonTrailingBits);
-        const trailingBitsOnes = bits.popcount(trailingBits);
-        const trailingBitsZeros = data.numTrailingBits - trailingBitsOnes;
-
-        blockOnes -= trailingBitsOnes;
-        blockZeros -= trailingBitsZeros;
-      }
-      const cumulativeZeros = cumulativeBits - cumulativeOnes;
-
-
-      // Sample 1-bits for the select1 index
-      if (cumulativeOnes + blockOnes > onesThreshold) {
-        // Take a select1 sample, which consists of two parts:
-        // 1. The cumulative number of bits preceding this basic block, ie. the left-shifted block index.
-        //    This is `cumulativeBits`, defined above, and is stored in the high bits.
-        // 2. A correction factor storing the number of 1-bits preceding the (ss1 * i + 1)-th 1-bit within this
-        //    basic block, which we can use to determine the number of 1-bits preceding this basic block.
-        //    Effectively, this is a way for us to store samples that are slightly offset from the strictly
-        //    regular select sampling scheme, enabling us to keep the select samples aligned to basic blocks.
-        //    This is `correction`, and is stored in the low bits.
-        const correction = onesThreshold - cumulativeOnes;
-        // Since cumulativeBits is a multiple of the basic block size,
-        // these two values should never overlap in their bit ranges.
-        DEBUG && assert((cumulativeBits & correction) === 0);
-        // Add the select sample and bump the onesThreshold.
-        select1Samples.push(cumulativeBits | correction);
-        onesThreshold += select1SampleRate;
-      }
-
-      // Sample 0-bits for the select0 index.
-      // This `if` block has the same structure as the one above which samples 1-bits.
-      if (cumulativeZeros + blockZeros > zerosThreshold) {
-        const correction = zerosThreshold - cumulativeZeros;
-        DEBUG && assert((cumulativeBits & correction) === 0);
-        select0Samples.push(cumulativeBits | correction);
-        zerosThreshold += select0SampleRate;
-      }
-
-      cumulativeOnes += blockOnes;
-      cumulativeBits += bits.BasicBlockSize;
-    }
+    // Small bit vectors skip sampling entirely; see `DefaultSmallThreshold`.
+    const isSmall = data.universeSize <= smallThreshold;
+    const { rank1Samples, select0Samples, select1Samples, numOnes } =
+      buildSamples(data, rank1SamplesPow2, selectSamplesPow2, isSmall, buildSelect0Samples, buildSelect1Samples);
 
     /** @readonly */
     this.data = data;
 
     /** @readonly */
-    this.rank1SamplesPow2 = rank1SamplesPow2;
+    this.isSmall = isSmall;
 
     /** @readonly */
-    this.select0SamplesPow2 = selectSamplesPow2;
+    this.hasSelect0Samples = buildSelect0Samples;
 
     /** @readonly */
-    this.select1SamplesPow2 = selectSamplesPow2;
+    this.hasSelect1Samples = buildSelect1Samples;
 
     /** @readonly */
-    this.rank1Samples = new Uint32Array(rank1Samples);
+    this.rank1SamplesPow2 = rank1SamplesPow2;
 
     /** @readonly */
-    this.select0Samples = new Uint32Array(select0Samples);
+    this.select0SamplesPow2 = selectSamplesPow2;
 
     /** @readonly */
-    this.select1Samples = new Uint32Array(select1Samples);
+    this.select1SamplesPow2 = selectSamplesPow2;
 
-    /** @readonly */
-    this.basicBlocksPerRank1SamplePow2 = rank1SamplesPow2 - bits.BasicBlockSizePow2;
+    // The samples and counts are updated by `rebuildSamples`
+    this.rank1Samples = rank1Samples;
+    this.select0Samples = select0Samples;
+    this.select1Samples = select1Samples;
 
     /** @readonly */
-    this.numOnes = cumulativeOnes;
+    this.basicBlocksPerRank1SamplePow2 = rank1SamplesPow2 - bits.BasicBlockSizePow2;
 
-    /** @readonly */
-    this.numZeros = data.universeSize - cumulativeOnes;
+    this.numOnes = numOnes;
+    this.numZeros = data.universeSize - numOnes;
 
     /** @readonly */
     this.universeSize = data.universeSize;
@@ -243,13 +230,81 @@ export class DenseBitVec {
     /** @readonly */
     this.hasMultiplicity = false;
 
-    /** @readonly */
     this.numUniqueOnes = this.numOnes;
-    
-    /** @readonly */
     this.numUniqueZeros = this.numZeros;
   }
 
+  /**
+   * Recompute the rank and select samples (and the 1-bit and 0-bit counts) from the current
+   * contents of the bit buffer, with the same sample rates. Call this after modifying the blocks
+   * of the underlying `BitBuf` in place, eg. when combining bit vectors with set operations, since
+   * the samples otherwise describe the old contents. A `PaddedBitBuf` cannot be modified.
+   */
+  rebuildSamples() {
+    const { rank1Samples, select0Samples, select1Samples, numOnes } = buildSamples(
+      this.data,
+      this.rank1SamplesPow2,
+      this.select1SamplesPow2,
+      this.isSmall,
+      this.hasSelect0Samples,
+      this.hasSelect1Samples
+    );
+    this.rank1Samples = rank1Samples;
+    this.select0Samples = select0Samples;
+    this.select1Samples = select1Samples;
+    this.numOnes = numOnes;
+    this.numZeros = this.universeSize - numOnes;
+    this.numUniqueOnes = this.numOnes;
+    this.numUniqueZeros = this.numZeros;
+  }
+
+  /**
+   * Construct a dense bit vector with the finest rank and select sample rates whose samples fit
+   * within `maxOverhead` times the size of the bit data, as a convenience over choosing the powers
+   * of two by hand. Since every bit is either a 0-bit or a 1-bit, the select samples take about
+   * as much space at any density, and with the default budget most bit vectors get the default
+   * rates; the exception is bit vectors just above the small threshold, which get coarser rates
+   * since each sample array is rounded up to a whole sample.
+   * @param {BitBuf | PaddedBitBuf} data
+   * @param {Object} [options]
+   * @param {number} [options.maxOverhead] - maximum size of the samples as a fraction of the data size
+   */
+  static withAutoSamples(data, { maxOverhead = DefaultMaxSampleOverhead } = {}) {
+    let numOnes = 0;
+    const maxBlockIndex = data.numBlocks - 1;
+    for (let i = 0; i < data.numBlocks; i++) {
+      let block = data.getBlock(i);
+      // Don't count trailing ones in the final data block, which may be set in a one-padded buffer
+      if (i === maxBlockIndex) {
+        block &= bits.oneMask(bits.BasicBlockSize - data.numTrailingBits);
+      }
+      numOnes += bits.popcount(block);
+    }
+    const numZeros = data.universeSize - numOnes;
+    const dataBits = data.numBlocks * bits.BasicBlockSize;
+    // Each sample is a 32-bit integer, and each sample array is rounded up to a whole sample
+    const rankSampleBits = (/** @type {number} */ pow2) => 32 * Math.ceil(data.universeSize / 2 ** pow2);
+    const selectSampleBits = (/** @type {number} */ pow2) => 32 * (Math.ceil(numOnes / 2 ** pow2) + Math.ceil(numZeros / 2 ** pow2));
+
+    // Starting from the finest rates, repeatedly halve whichever kind of samples takes more space
+    // until the total fits within the budget (or both rates are as coarse as they can be).
+    let rank1SamplesPow2 = MinAutoSamplesPow2;
+    let selectSamplesPow2 = MinAutoSamplesPow2;
+    while (rankSampleBits(rank1SamplesPow2) + selectSampleBits(selectSamplesPow2) > maxOverhead * dataBits) {
+      const coarsenRank = rankSampleBits(rank1SamplesPow2) >= selectSampleBits(selectSamplesPow2);
+      if (coarsenRank && rank1SamplesPow2 < 31) {
+        rank1SamplesPow2++;
+      } else if (selectSamplesPow2 < 31) {
+        selectSamplesPow2++;
+      } else if (rank1SamplesPow2 < 31) {
+        rank1SamplesPow2++;
+      } else {
+        break;
+      }
+    }
+    return new DenseBitVec(data, rank1SamplesPow2, selectSamplesPow2);
+  }
+
   /**
    * 
    * Note: This will use select1 samples (but not select0 samples) to skip basic blocks if possible.
@@ -262,6 +317,10 @@ export class DenseBitVec {
       return this.numOnes;
     }
 
+    if (this.isSmall) {
+      return this.smallRank1(index);
+    }
+
     // todo: investigate whether we can provide a 'hint' argument of a start block
     // that would allow us to skip the rank/select memory fetches if querying
     // another 1-bit close by. As another way to do a 'batch' operation for a
@@ -282,7 +341,7 @@ export class DenseBitVec {
     let selectBasicBlockIndex = rankBasicBlockIndex;
     let selectPrecedingCount = count;
     let selectCount = selectPrecedingCount + selectSampleRate;
-    while (selectCount < this.numOnes && selectBasicBlockIndex < lastBasicBlockIndex) {
+    while (this.hasSelect1Samples && selectCount < this.numOnes && selectBasicBlockIndex < lastBasicBlockIndex) {
       const { 
         precedingCount: selectPrecedingCount,
         basicBlockIndex: selectBasicBlockIndex
@@ -310,9 +369,13 @@ export class DenseBitVec {
    */
   trySelect1(n) {
     if (n < 0 || n >= this.numOnes) return null;
+    if (this.isSmall) return this.smallSelect(n, 0);
 
-    // Grab the basic block and count information from the select sample
-    let { basicBlockIndex, precedingCount: count } = this.selectSample(n, this.select1Samples, this.select1SamplesPow2);
+    // Grab the basic block and count information from the select sample,
+    // or from the nearest preceding rank sample if there are no select samples
+    let { basicBlockIndex, precedingCount: count } = this.hasSelect1Samples
+      ? this.selectSample(n, this.select1Samples, this.select1SamplesPow2)
+      : this.rankSample(n, rankIndex => this.rank1Samples[rankIndex]);
     assert(count <= n);
 
     if (DEBUG) {
@@ -353,9 +416,13 @@ export class DenseBitVec {
    */
   trySelect0(n) {
     if (n < 0 || n >= this.numZeros) return null;
+    if (this.isSmall) return this.smallSelect(n, bits.oneMask(bits.BasicBlockSize));
 
-    // Grab the basic block and count information from the select sample
-    let { basicBlockIndex, precedingCount: count } = this.selectSample(n, this.select0Samples, this.select0SamplesPow2);
+    // Grab the basic block and count information from the select sample,
+    // or from the nearest preceding rank sample if there are no select samples
+    let { basicBlockIndex, precedingCount: count } = this.hasSelect0Samples
+      ? this.selectSample(n, this.select0Samples, this.select0SamplesPow2)
+      : this.rankSample(n, rankIndex => u32(rankIndex << this.rank1SamplesPow2) - this.rank1Samples[rankIndex]);
     assert(count <= n);
 
     if (DEBUG) {
@@ -363,7 +430,9 @@ export class DenseBitVec {
       assert(prevRankIndex < this.rank1Samples.length);
     }
 
-    // Scan rank blocks to skip past multiple basic blocks at a time
+    // Scan rank blocks to skip past multiple basic blocks at a time.
+    // Rank samples are only taken at the start of existing basic blocks, so the number of bits
+    // preceding a sample never exceeds the universe size and the trailing bits are never counted.
     let rankIndex = (basicBlockIndex >>> this.basicBlocksPerRank1SamplePow2) + 1;
     while (rankIndex < this.rank1Samples.length) {
       let nextCount = u32(rankIndex << this.rank1SamplesPow2) - this.rank1Samples[rankIndex];
@@ -393,6 +462,42 @@ export class DenseBitVec {
     return basicBlockBitIndex + bitOffset;
   }
 
+  /**
+   * Implements rank1 for small bit vectors by counting 1-bits from the start.
+   * @param {number} index - an index in [0, universeSize)
+   */
+  smallRank1(index) {
+    const lastBasicBlockIndex = bits.basicBlockIndex(index);
+    let count = 0;
+    for (let i = 0; i < lastBasicBlockIndex; i++) {
+      count += bits.popcount(this.data.getBlock(i));
+    }
+    const maskedBlock = this.data.getBlock(lastBasicBlockIndex) & bits.oneMask(bits.basicBlockBitOffset(index));
+    return count + bits.popcount(maskedBlock);
+  }
+
+  /**
+   * Implements select for small bit vectors by scanning basic blocks from the start.
+   * Each block is XORed with `flip` before counting, so that the same code can be used
+   * to select 1-bits (with `flip` equal to zero) and 0-bits (with all basic block bits set).
+   * @param {number} n - a valid 1-bit (or 0-bit) index
+   * @param {number} flip
+   */
+  smallSelect(n, flip) {
+    let count = 0;
+    let basicBlockIndex = 0;
+    let basicBlock = 0;
+    while (basicBlockIndex < this.data.numBlocks) {
+      basicBlock = u32(this.data.getBlock(basicBlockIndex) ^ flip);
+      const nextCount = count + bits.popcount(basicBlock);
+      if (nextCount > n) break;
+      count = nextCount;
+      basicBlockIndex++;
+    }
+    const basicBlockBitIndex = u32(basicBlockIndex << bits.BasicBlockSizePow2);
+    return basicBlockBitIndex + bits.select1(basicBlock, n - count);
+  }
+
   /**
    * @param {number} index
    */
@@ -414,6 +519,22 @@ export class DenseBitVec {
     return defaults.select1(this, n);
   }
 
+  /**
+   * Fallback for `selectSample` when there are no select samples. Returns the basic block index
+   * and preceding count of the last rank sample preceded by at most `n` bits of the relevant kind.
+   * @param {number} n - we are looking for the n-th bit of the particular kind (1-bit or 0-bit)
+   * @param {(rankIndex: number) => number} precedingCount - the number of bits of the relevant kind
+   * preceding the basic block of the given rank sample
+   */
+  rankSample(n, precedingCount) {
+    // The first rank sample always has a preceding count of zero, so the index is non-negative.
+    const rankIndex = bits.partitionPoint(this.rank1Samples.length, i => precedingCount(i) <= n) - 1;
+    return {
+      basicBlockIndex: u32(rankIndex << this.basicBlocksPerRank1SamplePow2),
+      precedingCount: precedingCount(rankIndex)
+    };
+  }
+
   /**
    * @param {number} n - we are looking for the n-th bit of the particular kind (1-bit or 0-bit)
    * @param {number} sampleRate - power of 2 of the select sample rate
@@ -460,6 +581,148 @@ export class DenseBitVec {
     return defaults.get(this, index);
   }
 
+  /**
+   * @param {number} index
+   */
+  rank1Checked(index) {
+    return defaults.rank1Checked(this, index);
+  }
+
+  /**
+   * @param {number} first
+   * @param {number} last
+   */
+  countOnesInInclusive(first, last) {
+    return defaults.countOnesInInclusive(this, first, last);
+  }
+
+  /**
+   * @param {number} k
+   * @param {number} seed
+   */
+  sampleOnes(k, seed) {
+    return defaults.sampleOnes(this, k, seed);
+  }
+
+  /**
+   * Scans the basic blocks for 0-bits and 1-bits in turn, skipping over all-one
+   * and all-zero blocks a block at a time.
+   * @param {{ start: number, end: number }} range
+   * @returns {Generator<{ start: number, end: number }>}
+   */
+  *zeroRuns(range) {
+    const end = Math.min(range.end, this.universeSize);
+    let index = Math.max(range.start, 0);
+    while (index < end) {
+      const start = nextSetBit(this.data, index, bits.oneMask(bits.BasicBlockSize));
+      if (start >= end) {
+        break;
+      }
+      index = Math.min(nextSetBit(this.data, start, 0), end);
+      yield { start, end: index };
+    }
+  }
+
+  /**
+   * @param {{ start: number, end: number }} range
+   * @param {number} minLength
+   */
+  firstZeroRunAtLeast(range, minLength) {
+    return defaults.firstZeroRunAtLeast(this, range, minLength);
+  }
+
+  /**
+   * Returns a copy of this bit vector with a different universe size, padded with 0-bits if it
+   * is larger, or truncated if it is smaller, in which case the 1-bits at or above the new
+   * universe size are dropped. This is useful to bring bit vectors from different sources to a
+   * common universe size. The copy uses the same sample rates, and the default small threshold.
+   * @param {number} universeSize
+   */
+  resize(universeSize) {
+    assertUniverseSize(universeSize);
+    const buf = new BitBuf(universeSize);
+    const numBlocks = Math.min(buf.numBlocks, this.data.numBlocks);
+    for (let i = 0; i < numBlocks; i++) {
+      buf.blocks[i] = this.data.getBlock(i);
+    }
+    // Clear the bits at or above the smaller of the two universe sizes in the last copied
+    // block, which may be set if the new universe is smaller or if the trailing bits of
+    // the final block are set, as they are when a `PaddedBitBuf` is padded with 1-bits.
+    const end = Math.min(universeSize, this.universeSize);
+    const offset = bits.basicBlockBitOffset(end);
+    if (offset > 0) {
+      buf.blocks[bits.basicBlockIndex(end)] &= bits.oneMask(offset);
+    }
+    return new DenseBitVec(
+      buf.maybePadded(),
+      this.rank1SamplesPow2,
+      this.select1SamplesPow2,
+      DefaultSmallThreshold,
+      this.hasSelect0Samples,
+      this.hasSelect1Samples
+    );
+  }
+
+  /**
+   * Returns the bits of this bit vector packed into bytes in the layout used by the `bit-vec`
+   * Rust crate (`BitVec::to_bytes` and `BitVec::from_bytes`), for moving data to and from that
+   * crate: bit `i` is stored in byte `i >> 3` with the first bit in the most significant position.
+   * The bits in the final byte beyond the universe size are 0. See `DenseBitVec.fromBytes`.
+   */
+  toBytes() {
+    const bytes = new Uint8Array(Math.ceil(this.universeSize / 8));
+    for (let i = 0; i < bytes.length; i++) {
+      const block = this.data.getBlock(i >>> 2);
+      bytes[i] = bits.reverseLowBits((block >>> (8 * (i & 3))) & 0xff, 8);
+    }
+    const offset = this.universeSize & 7;
+    if (offset > 0) {
+      bytes[bytes.length - 1] &= 0xff << (8 - offset);
+    }
+    return bytes;
+  }
+
+  /**
+   * Construct a bit vector from bytes in the layout returned by `toBytes`. The universe size
+   * defaults to 8 bits per byte, as with `BitVec::from_bytes` in the `bit-vec` crate, and may be
+   * smaller to drop the trailing bits of the final byte. The options are those of `DenseBitVecBuilder.build`.
+   * @param {ArrayLike<number>} bytes
+   * @param {number} [universeSize]
+   * @param {Parameters<DenseBitVecBuilder['build']>[0]} [options]
+   */
+  static fromBytes(bytes, universeSize = 8 * bytes.length, options = {}) {
+    assert(
+      Math.ceil(universeSize / 8) === bytes.length,
+      () => `expected ${Math.ceil(universeSize / 8)} bytes for universeSize ${universeSize}, got ${bytes.length}`
+    );
+    const builder = new DenseBitVecBuilder(universeSize);
+    const blocks = builder.buf.blocks;
+    for (let i = 0; i < bytes.length; i++) {
+      blocks[i >>> 2] |= bits.reverseLowBits(bytes[i], 8) << (8 * (i & 3));
+    }
+    const offset = bits.basicBlockBitOffset(universeSize);
+    if (offset > 0) {
+      blocks[blocks.length - 1] &= bits.oneMask(offset);
+    }
+    return builder.build(options);
+  }
+
+  /**
+   * Fraction of bits in the universe that are 1-bits, or 0 for an empty universe.
+   */
+  density() {
+    return this.universeSize === 0 ? 0 : this.numOnes / this.universeSize;
+  }
+
+  /**
+   * Whether this bit vector's density is below `threshold`, in which case
+   * converting it to a `SparseBitVec` with `convert.toSparse` will likely save space.
+   * See `DefaultSparseDensityThreshold`.
+   */
+  isSparse(threshold = DefaultSparseDensityThreshold) {
+    return this.density() < threshold;
+  }
+
   /**
    * Track and return array accesses to samples and data blocks incurred
    * during the execution of `f`. The log is passed to `f` so that it can
@@ -500,3 +763,145 @@ export class DenseBitVec {
     return log;
   }
 };
+
+/**
+ * Compute the rank and select samples of a dense bit vector, along with its number of 1-bits.
+ * See the `DenseBitVec` constructor for the meaning of the parameters.
+ * @param {BitBuf | PaddedBitBuf} data
+ * @param {number} rank1SamplesPow2
+ * @param {number} selectSamplesPow2
+ * @param {boolean} isSmall - if true, no samples are taken
+ * @param {boolean} buildSelect0Samples
+ * @param {boolean} buildSelect1Samples
+ */
+function buildSamples(data, rank1SamplesPow2, selectSamplesPow2, isSmall, buildSelect0Samples, buildSelect1Samples) {
+  const select1SampleRate = u32(1 << selectSamplesPow2); // Sample every `select1SampleRate` 1-bits
+  const select0SampleRate = u32(1 << selectSamplesPow2); // Sample every `select0SampleRate` 0-bits
+  const rank1SampleRate = u32(1 << rank1SamplesPow2); // Sample every `rank1SampleRate` bits
+
+  // Each rank sample identifies a particular basic block. 
+  // 
+
+  // Rank samples are sampled every `rank1SamplingRate` bits, where `rank1SamplingRate` is a positive multiple of
+  // the bit width of a basic block. For example, if `rank1SamplingRate` is 64 and the basic
+  // block width is 32, then the rank samples will tell us about the 0th, 2nd, 4th, 6th, ... basic block.
+  //
+  // A rank sample `rank1Samples[i]` tells us about the basic block `data.blocks[i << (srPow2 - bits.BLOCK_BITS_LOG2)]`.
+  //
+  // If `rank1Samples[i] has value `v`, this means that there are `v` 1-bits preceding that basic block.
+  // Rank samples represent the number of 1-bits up to but not including a basic block.
+  const rank1Samples = []; 
+
+  // Each select1 sample identifies a particular basic block.
+  //
+  // Select samples are sampled every `select1SampleRate` 1-bits, where `rank1SamplingRate` is a positive multiple of
+  // the bit width of a basic block. Unlike rank blocks, which start sampling from 0 (representing the 
+  // `rank1SamplingRate*i + 0`-th bits), select blocks start sampling from 1, and thus represent the
+  // `select1SamplingRate*i + 1`-th bits.
+  // For example, if `select1SamplingRate` is 64, then the select1 samples will identify the basic blocks
+  // that contain the 0+1 = 1st, 64+1 = 65th, 2*64+1 = 129th, 3*64+1 = 193rd, ... bits.
+  // Since the sampling rate is a positive multiple of the basic block, two select blocks will never point 
+  // to the same basic block.
+  const select1Samples = []; 
+  const select0Samples = []; 
+
+  // Select1 samples represent the number of 1-bits up to but not including a basic block.
+  // For example, if `select1SamplingRate`
+  // is 64, then the select1 samples will tell us about the basic blocks containing the 1st
+  // A select sample `select1Samples[i]` tells us about the basic block that contains the
+  // `selectSamplingRate * i + 1`-th 1-bit.
+
+  let cumulativeOnes = 0; // 1-bits preceding the current raw block
+  let cumulativeBits = 0; // bits preceding the current raw block
+  let zerosThreshold = 0; // take a select0 sample at the (zerosThreshold+1)th 1-bit
+  let onesThreshold = 0; // take a select1 sample at the (onesThreshold+1)th 1-bit
+
+  const basicBlocksPerRank1Sample = rank1SampleRate >>> bits.BasicBlockSizePow2;
+
+  const maxBlockIndex = data.numBlocks - 1;
+  for (let blockIndex = 0; blockIndex < data.numBlocks; blockIndex++) {
+    const block = data.getBlock(blockIndex);
+    if (!isSmall && blockIndex % basicBlocksPerRank1Sample === 0) {
+      rank1Samples.push(cumulativeOnes);
+    }
+
+    let blockOnes = bits.popcount(block);
+    let blockZeros = bits.BasicBlockSize - blockOnes;
+    // Don't count trailing ones or zeros in the final data block towards the 0/1 count
+    if (blockIndex === maxBlockIndex) {
+      const numNonTrailingBits = bits.BasicBlockSize - data.numTrailingBits;
+      const trailingBits = block & ~bits.oneMask(numNonTrailingBits);
+      const trailingBitsOnes = bits.popcount(trailingBits);
+      const trailingBitsZeros = data.numTrailingBits - trailingBitsOnes;
+
+      blockOnes -= trailingBitsOnes;
+      blockZeros -= trailingBitsZeros;
+    }
+    const cumulativeZeros = cumulativeBits - cumulativeOnes;
+
+
+    // Sample 1-bits for the select1 index
+    if (!isSmall && buildSelect1Samples && cumulativeOnes + blockOnes > onesThreshold) {
+      // Take a select1 sample, which consists of two parts:
+      // 1. The cumulative number of bits preceding this basic block, ie. the left-shifted block index.
+      //    This is `cumulativeBits`, defined above, and is stored in the high bits.
+      // 2. A correction factor storing the number of 1-bits preceding the (ss1 * i + 1)-th 1-bit within this
+      //    basic block, which we can use to determine the number of 1-bits preceding this basic block.
+      //    Effectively, this is a way for us to store samples that are slightly offset from the strictly
+      //    regular select sampling scheme, enabling us to keep the select samples aligned to basic blocks.
+      //    This is `correction`, and is stored in the low bits.
+      const correction = onesThreshold - cumulativeOnes;
+      // Since cumulativeBits is a multiple of the basic block size,
+      // these two values should never overlap in their bit ranges.
+      DEBUG && assert((cumulativeBits & correction) === 0);
+      // Add the select sample and bump the onesThreshold.
+      select1Samples.push(cumulativeBits | correction);
+      onesThreshold += select1SampleRate;
+    }
+
+    // Sample 0-bits for the select0 index.
+    // This `if` block has the same structure as the one above which samples 1-bits.
+    if (!isSmall && buildSelect0Samples && cumulativeZeros + blockZeros > zerosThreshold) {
+      const correction = zerosThreshold - cumulativeZeros;
+      DEBUG && assert((cumulativeBits & correction) === 0);
+      select0Samples.push(cumulativeBits | correction);
+      zerosThreshold += select0SampleRate;
+    }
+
+    cumulativeOnes += blockOnes;
+    cumulativeBits += bits.BasicBlockSize;
+  }
+
+  return {
+    rank1Samples: new Uint32Array(rank1Samples),
+    select0Samples: new Uint32Array(select0Samples),
+    select1Samples: new Uint32Array(select1Samples),
+    numOnes: cumulativeOnes,
+  };
+}
+
+/**
+ * Index of the first bit at or after `index` that is set in the block XORed with `flip`,
+ * ie. the first 1-bit (with `flip` equal to zero) or 0-bit (with all basic block bits set),
+ * or the universe size if there is none.
+ * @param {BitBuf | PaddedBitBuf} data
+ * @param {number} index
+ * @param {number} flip
+ */
+function nextSetBit(data, index, flip) {
+  let blockIndex = bits.basicBlockIndex(index);
+  if (blockIndex >= data.numBlocks) {
+    return data.universeSize;
+  }
+  // Mask out the bits preceding `index` in its basic block
+  let block = u32((data.getBlock(blockIndex) ^ flip) & ~bits.oneMask(bits.basicBlockBitOffset(index)));
+  while (block === 0) {
+    blockIndex++;
+    if (blockIndex >= data.numBlocks) {
+      return data.universeSize;
+    }
+    block = u32(data.getBlock(blockIndex) ^ flip);
+  }
+  // Clamp to the universe size since the trailing bits of the final block may be set
+  return Math.min(u32(blockIndex << bits.BasicBlockSizePow2) + bits.trailing0(block), data.universeSize);
+}
@@ -1,6 +1,7 @@
 import fc from 'fast-check';
 import { describe, expect, it, test } from 'vitest';
-import { DenseBitVec, DenseBitVecBuilder } from './densebitvec';
+import { BitBuf, PaddedBitBuf } from './bitbuf.js';
+import { DefaultMaxSampleOverhead, DefaultSmallThreshold, DenseBitVec, DenseBitVecBuilder } from './densebitvec';
 import { testBitVecType } from './testutils.js';
 
 // - test with VERY different block sizes (not just 5 and 6)
@@ -11,9 +12,303 @@ describe('DenseBitVec in varying sampling configurations', () => {
       test(`(${rankSamplesPow2}, ${selectSamplesPow2})`, () => {
         testBitVecType(DenseBitVecBuilder, {
           rankSamplesPow2,
-          selectSamplesPow2
+          selectSamplesPow2,
+          // test the sampled implementation even for small universes
+          smallThreshold: 0
         });
       });
     }
   }
 });
+
+describe('DenseBitVec without select samples', () => {
+  for (const buildSelect0Samples of [true, false]) {
+    for (const buildSelect1Samples of [true, false]) {
+      test(`(select0: ${buildSelect0Samples}, select1: ${buildSelect1Samples})`, () => {
+        testBitVecType(DenseBitVecBuilder, {
+          rank1SamplesPow2: 6,
+          selectSamplesPow2: 5,
+          smallThreshold: 0,
+          buildSelect0Samples,
+          buildSelect1Samples
+        });
+      });
+    }
+  }
+
+  test('stores only the requested select samples', () => {
+    const builder = new DenseBitVecBuilder(10000);
+    for (let i = 0; i < 10000; i += 3) builder.one(i);
+    const bv = builder.build({ smallThreshold: 0, buildSelect0Samples: false });
+    expect(bv.select0Samples.length).toBe(0);
+    expect(bv.select1Samples.length).toBeGreaterThan(0);
+    expect(bv.select0(0)).toBe(1);
+    expect(bv.select0(bv.numZeros - 1)).toBe(9998);
+  });
+});
+
+describe('DenseBitVec without samples', () => {
+  // force the small implementation for all test sizes
+  testBitVecType(DenseBitVecBuilder, { smallThreshold: 2 ** 32 });
+
+  test('stores no samples below the threshold', () => {
+    const builder = new DenseBitVecBuilder(100);
+    builder.one(10);
+    const bv = builder.build({ smallThreshold: 100 });
+    expect(bv.isSmall).toBe(true);
+    expect(bv.rank1Samples.length).toBe(0);
+    expect(bv.select0Samples.length).toBe(0);
+    expect(bv.select1Samples.length).toBe(0);
+  });
+
+  test('stores samples above the threshold', () => {
+    const builder = new DenseBitVecBuilder(101);
+    builder.one(10);
+    const bv = builder.build({ smallThreshold: 100 });
+    expect(bv.isSmall).toBe(false);
+    expect(bv.rank1Samples.length).toBeGreaterThan(0);
+  });
+});
+
+describe('DenseBitVec.withAutoSamples', () => {
+  test('keeps the samples within the overhead budget', () => {
+    for (const universeSize of [0, 100, 4097, 5000, 10_000, 100_000, 1_000_000]) {
+      for (const step of [1, 2, 3, 50, 1000]) {
+        const buf = new BitBuf(universeSize);
+        for (let i = 0; i < universeSize; i += step) buf.setOne(i);
+        const bv = DenseBitVec.withAutoSamples(buf);
+        const sampleBits = 32 * (bv.rank1Samples.length + bv.select0Samples.length + bv.select1Samples.length);
+        expect(sampleBits).toBeLessThanOrEqual(DefaultMaxSampleOverhead * buf.numBlocks * 32);
+        expect(bv.numOnes).toBe(Math.ceil(universeSize / step));
+        if (universeSize > 0) {
+          expect(bv.select1(bv.numOnes - 1)).toBe((bv.numOnes - 1) * step);
+          expect(bv.rank1(universeSize)).toBe(bv.numOnes);
+        }
+      }
+    }
+  });
+
+  test('chooses the default rates for large vectors and coarser ones for small vectors', () => {
+    const half = new BitBuf(1 << 20);
+    for (let i = 0; i < 1 << 20; i += 2) half.setOne(i);
+    const halfBv = DenseBitVec.withAutoSamples(half);
+    expect(halfBv.rank1SamplesPow2).toBe(10);
+    expect(halfBv.select1SamplesPow2).toBe(10);
+    const small = new BitBuf(4200);
+    for (let i = 0; i < 4200; i += 2) small.setOne(i);
+    const smallBv = DenseBitVec.withAutoSamples(small);
+    expect(smallBv.rank1SamplesPow2 + smallBv.select1SamplesPow2).toBeGreaterThan(20);
+    // a larger budget buys finer samples
+    const fine = DenseBitVec.withAutoSamples(half, { maxOverhead: 1 / 4 });
+    expect(fine.rank1SamplesPow2).toBeLessThan(10);
+  });
+
+  test('does not count the trailing bits of a one-padded buffer', () => {
+    for (const [universeSize, firstOne] of [[100, 10], [4200, 10], [10_000, 1]]) {
+      const buf = new BitBuf(universeSize);
+      for (let i = firstOne; i < universeSize; i++) buf.setOne(i);
+      const padded = new PaddedBitBuf(buf);
+      // the final block is partial, and its trailing bits are set by the one-padding
+      expect(padded.padding).toBe(1);
+      expect(padded.numTrailingBits).toBeGreaterThan(0);
+      const bv = DenseBitVec.withAutoSamples(padded);
+      const expected = DenseBitVec.withAutoSamples(buf);
+      expect(bv.numOnes).toBe(universeSize - firstOne);
+      expect(bv.rank1SamplesPow2).toBe(expected.rank1SamplesPow2);
+      expect(bv.select1SamplesPow2).toBe(expected.select1SamplesPow2);
+    }
+  });
+});
+
+describe('DenseBitVec select0 near the end of the universe', () => {
+  // Mostly-full bit vectors whose few 0-bits lie in the final block, which is partially
+  // occupied, so that select0 has to skip over rank samples right up to the last block.
+  test('matches the brute-force 0-bit positions', () => {
+    for (const trailing of [1, 7, 63]) {
+      const universeSize = 300 * 32 - trailing;
+      for (const zeros of [[universeSize - 1], [universeSize - 3, universeSize - 2], [0, universeSize - 40, universeSize - 5]]) {
+        const buf = new BitBuf(universeSize);
+        for (let i = 0; i < universeSize; i++) {
+          if (!zeros.includes(i)) buf.setOne(i);
+        }
+        for (const data of [buf, buf.maybePadded()]) {
+          for (const [rank1SamplesPow2, selectSamplesPow2] of [[5, 5], [5, 10], [7, 5], [10, 10]]) {
+            for (const buildSelect0Samples of [true, false]) {
+              const bv = new DenseBitVec(data, rank1SamplesPow2, selectSamplesPow2, 0, buildSelect0Samples);
+              expect(bv.numZeros).toBe(zeros.length);
+              for (let n = 0; n < zeros.length; n++) {
+                expect(bv.select0(n)).toBe(zeros[n]);
+              }
+              expect(bv.trySelect0(zeros.length)).toBeNull();
+            }
+          }
+        }
+      }
+    }
+  });
+});
+
+describe('DenseBitVec.rebuildSamples', () => {
+  test('matches a bit vector built from scratch after in-place modification', () => {
+    const universeSize = 20_005;
+    for (const [rank1SamplesPow2, selectSamplesPow2, smallThreshold] of [[5, 5, 0], [10, 10, DefaultSmallThreshold], [8, 6, 2 ** 32]]) {
+      const buf = new BitBuf(universeSize);
+      for (let i = 0; i < universeSize; i += 3) buf.setOne(i);
+      const bv = new DenseBitVec(buf, rank1SamplesPow2, selectSamplesPow2, smallThreshold);
+      // xor every block with a pattern, as a set operation combinator would
+      for (let i = 0; i < buf.numBlocks; i++) {
+        buf.blocks[i] ^= i % 5 === 0 ? 0xffff00ff : 0x0f0f0f0f;
+      }
+      buf.trimTo(universeSize); // clear the trailing bits of the final block
+      bv.rebuildSamples();
+      const expected = new DenseBitVec(buf, rank1SamplesPow2, selectSamplesPow2, smallThreshold);
+      expect(bv.rank1Samples).toEqual(expected.rank1Samples);
+      expect(bv.select0Samples).toEqual(expected.select0Samples);
+      expect(bv.select1Samples).toEqual(expected.select1Samples);
+      expect(bv.numOnes).toBe(expected.numOnes);
+      expect(bv.numZeros).toBe(expected.numZeros);
+      for (let n = 0; n < bv.numOnes; n += 97) expect(bv.select1(n)).toBe(expected.select1(n));
+      for (let n = 0; n < bv.numZeros; n += 97) expect(bv.select0(n)).toBe(expected.select0(n));
+    }
+  });
+});
+
+describe('DenseBitVec.resize', () => {
+  test('pads with 0-bits and truncates 1-bits', () => {
+    const universeSize = 1000;
+    const ones = Array.from({ length: universeSize }, (_, i) => i).filter(i => i % 7 === 0 || i > 950);
+    for (const bv of [DenseBitVecBuilder.fromSortedOnes(universeSize, ones), new DenseBitVec(DenseBitVecBuilder.fromSortedOnes(universeSize, ones).data, 5, 5, 0)]) {
+      for (const newSize of [0, 1, 31, 32, 33, 500, 960, 999, 1000, 1001, 1024, 5000]) {
+        const resized = bv.resize(newSize);
+        expect(resized.universeSize).toBe(newSize);
+        const kept = ones.filter(i => i < newSize);
+        expect(resized.numOnes).toBe(kept.length);
+        expect(resized.numZeros).toBe(newSize - kept.length);
+        for (let i = 0; i <= Math.min(newSize, universeSize); i++) {
+          expect(resized.rank1(i)).toBe(bv.rank1(i));
+        }
+        for (let n = 0; n < kept.length; n++) {
+          expect(resized.select1(n)).toBe(kept[n]);
+        }
+        if (newSize > universeSize) {
+          expect(resized.rank1(newSize)).toBe(bv.numOnes);
+          expect(resized.select0(resized.numZeros - 1)).toBe(newSize - 1);
+        }
+      }
+    }
+    expect(() => DenseBitVecBuilder.fromSortedOnes(10, [1]).resize(-1)).toThrow('universeSize');
+  });
+});
+
+describe('DenseBitVec.toBytes and fromBytes', () => {
+  test('use the bit-vec byte layout', () => {
+    // the example from the documentation of `BitVec::from_bytes` in the bit-vec crate
+    const bv = DenseBitVec.fromBytes([0b10100000, 0b00010010]);
+    expect(bv.universeSize).toBe(16);
+    expect(Array.from({ length: bv.numOnes }, (_, n) => bv.select1(n))).toEqual([0, 2, 11, 14]);
+    expect(Array.from(bv.toBytes())).toEqual([0b10100000, 0b00010010]);
+    expect(Array.from(DenseBitVecBuilder.fromSortedOnes(10, [0, 9]).toBytes())).toEqual([0b10000000, 0b01000000]);
+    expect(() => DenseBitVec.fromBytes([1, 2], 17)).toThrow('expected 3 bytes for universeSize 17, got 2');
+  });
+
+  test('round-trip', () => {
+    fc.assert(fc.property(fc.array(fc.boolean(), { maxLength: 300 }), fc.boolean(), (bools, padded) => {
+      const universeSize = bools.length;
+      const ones = bools.flatMap((b, i) => (b ? [i] : []));
+      const bv = padded
+        ? new DenseBitVec(DenseBitVecBuilder.fromSortedOnes(universeSize, ones).data, 5, 5, 0)
+        : DenseBitVecBuilder.fromSortedOnes(universeSize, ones);
+      const bytes = bv.toBytes();
+      expect(bytes.length).toBe(Math.ceil(universeSize / 8));
+      const copy = DenseBitVec.fromBytes(bytes, universeSize, { smallThreshold: 0, rank1SamplesPow2: 5, selectSamplesPow2: 5 });
+      expect(copy.universeSize).toBe(universeSize);
+      expect(copy.numOnes).toBe(ones.length);
+      for (let i = 0; i <= universeSize; i++) {
+        expect(copy.rank1(i)).toBe(bv.rank1(i));
+      }
+      // the trailing bits of the final byte belong to the byte-sized universe and are 0
+      const widened = DenseBitVec.fromBytes(bytes);
+      expect(widened.universeSize).toBe(8 * bytes.length);
+      expect(widened.numOnes).toBe(ones.length);
+    }));
+  });
+});
+
+describe('DenseBitVec.sampleOnes', () => {
+  test('is roughly uniform', () => {
+    const ones = Array.from({ length: 20 }, (_, i) => 3 * i + (i % 2));
+    const bv = DenseBitVecBuilder.fromSortedOnes(100, ones);
+    const numTrials = 2000;
+    const k = 5;
+    /** @type {Map<number, number>} */
+    const counts = new Map();
+    for (let seed = 0; seed < numTrials; seed++) {
+      for (const i of bv.sampleOnes(k, seed)) {
+        counts.set(i, (counts.get(i) ?? 0) + 1);
+      }
+    }
+    expect(Array.from(counts.keys()).sort((a, b) => a - b)).toEqual(ones);
+    const expected = numTrials * k / ones.length;
+    let chiSquare = 0;
+    for (const count of counts.values()) {
+      chiSquare += (count - expected) ** 2 / expected;
+    }
+    // 19 degrees of freedom; the 99.9th percentile of the chi-square distribution is about 43.8
+    expect(chiSquare).toBeLessThan(43.8);
+  });
+});
+
+describe('DenseBitVecBuilder', () => {
+  test('trimTo', () => {
+    // over-allocate, then trim to the final size before building
+    const builder = new DenseBitVecBuilder(1000);
+    builder.one(3);
+    builder.one(50);
+    builder.one(70);
+    builder.trimTo(64);
+    const bv = builder.build();
+    expect(bv.universeSize).toBe(64);
+    expect(bv.numOnes).toBe(2);
+    expect(bv.select1(1)).toBe(50);
+    expect(bv.numZeros).toBe(62);
+  });
+});
+
+describe('DenseBitVec', () => {
+  test('density', () => {
+    const empty = new DenseBitVecBuilder(0).build();
+    expect(empty.density()).toBe(0);
+    expect(empty.isSparse()).toBe(true);
+
+    const fullBuilder = new DenseBitVecBuilder(100);
+    for (let i = 0; i < 100; i++) fullBuilder.one(i);
+    const full = fullBuilder.build();
+    expect(full.density()).toBe(1);
+    expect(full.isSparse()).toBe(false);
+
+    const builder = new DenseBitVecBuilder(100);
+    for (let i = 0; i < 100; i += 10) builder.one(i);
+    const bv = builder.build();
+    expect(bv.density()).toBe(0.1);
+    expect(bv.isSparse()).toBe(true);
+    expect(bv.isSparse(0.1)).toBe(false);
+  });
+
+  test('handles the maximum universe size', () => {
+    // note: this allocates a 512 MB bit buffer
+    const universeSize = 2 ** 32 - 1;
+    const builder = new DenseBitVecBuilder(universeSize);
+    builder.one(universeSize - 1);
+    const bv = builder.build();
+    expect(bv.numOnes).toBe(1);
+    expect(bv.numZeros).toBe(universeSize - 1);
+    expect(bv.rank1(universeSize)).toBe(1);
+    expect(bv.rank1(universeSize - 1)).toBe(0);
+    expect(bv.select1(0)).toBe(universeSize - 1);
+    expect(bv.trySelect1(1)).toBe(null);
+    // the last 0-bit immediately precedes the 1-bit
+    expect(bv.select0(universeSize - 2)).toBe(universeSize - 2);
+    expect(bv.trySelect0(universeSize - 1)).toBe(null);
+    expect(bv.rank0(universeSize)).toBe(universeSize - 1);
+  });
+});
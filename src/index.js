@@ -1,13 +1,30 @@
 /// <reference path="types.d.ts"/>
 
+// The public API of the library. Everything that users may import is exported from here,
+// explicitly by name, so that the modules behind it can be reorganized without breaking
+// imports; `index.test.js` checks that this list does not change by accident.
+// Internal helpers such as `PaddedBitBuf`, `IntBuf`, and the `defaults` fallbacks are not
+// exported. `BitBuf` is, since it is the input to the `DenseBitVec` constructor.
+
 import './debug.js';
 
+export { AdaptiveBitVecBuilder } from './adaptivebitvec.js';
+export { AlignedPayload } from './alignedpayload.js';
 export { BitBuf } from './bitbuf.js';
+export { Columns } from './columns.js';
+export { CountsDelta } from './countsdelta.js';
+export * as convert from './convert.js';
 export * as bits from './bits.js';
 export { DenseBitVec, DenseBitVecBuilder } from './densebitvec.js';
+export { IntSet } from './intset.js';
+export * as morton from '../morton.js';
 export { MultiBitVec, MultiBitVecBuilder } from './multibitvec.js';
+export { RemappedWaveletMatrix } from './remappedwaveletmatrix.js';
 export { RLEBitVec, RLEBitVecBuilder, RLERunBuilder } from './rlebitvec.js';
+export { QuantizedWaveletMatrix, Quantizer } from './quantizedwaveletmatrix.js';
+export * as query from './query.js';
+export { Range } from './range.js';
 export { SortedArrayBitVec, SortedArrayBitVecBuilder } from './sortedarraybitvec.js';
 export { SparseBitVec, SparseBitVecBuilder } from './sparsebitvec.js';
-export { WaveletMatrix } from './waveletmatrix.js';
-
+export { Traversal } from './traversal.js';
+export { coalesceRanges, joinCounts, mortonLevelMasks, progressToSlot, ProgressSlotScale, WaveletMatrix } from './waveletmatrix.js';
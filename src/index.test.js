@@ -0,0 +1,58 @@
+import { describe, expect, it } from 'vitest';
+import * as api from './index.js';
+
+// Changes to the public API should be deliberate, so adding or removing an export
+// requires updating this list.
+describe('public API', () => {
+  it('exports exactly the intended names', () => {
+    expect(Object.keys(api).sort()).toEqual([
+      'AdaptiveBitVecBuilder',
+      'AlignedPayload',
+      'BitBuf',
+      'Columns',
+      'CountsDelta',
+      'DenseBitVec',
+      'DenseBitVecBuilder',
+      'IntSet',
+      'MultiBitVec',
+      'MultiBitVecBuilder',
+      'ProgressSlotScale',
+      'QuantizedWaveletMatrix',
+      'Quantizer',
+      'RLEBitVec',
+      'RLEBitVecBuilder',
+      'RLERunBuilder',
+      'Range',
+      'RemappedWaveletMatrix',
+      'SortedArrayBitVec',
+      'SortedArrayBitVecBuilder',
+      'SparseBitVec',
+      'SparseBitVecBuilder',
+      'Traversal',
+      'WaveletMatrix',
+      'bits',
+      'coalesceRanges',
+      'convert',
+      'joinCounts',
+      'morton',
+      'mortonLevelMasks',
+      'progressToSlot',
+      'query',
+    ]);
+    for (const [name, value] of Object.entries(api)) {
+      expect(value === undefined ? name : null).toBeNull();
+    }
+  });
+
+  it('can be used through the exported names alone', () => {
+    const { DenseBitVecBuilder, WaveletMatrix, Range, morton, query } = api;
+    const builder = new DenseBitVecBuilder(10);
+    builder.one(3);
+    const mask = builder.build();
+    const xs = [1, 5, 2, 7], ys = [0, 3, 3, 1];
+    const ctx = new query.QueryContext({ x: new WaveletMatrix(xs), y: new WaveletMatrix(ys) });
+    expect(ctx.count(query.Rect('x', Range(2, 8), 'y', Range(1, 4)))).toBe(3);
+    expect(mask.rank1(10)).toBe(1);
+    expect(morton.decode2x(morton.encode2(5, 9))).toBe(5);
+  });
+});
@@ -1,11 +1,17 @@
 import { assert, assertInteger, assertSafeInteger } from "./assert.js";
 import * as bits from './bits.js';
 
+/**
+ * The maximum number of blocks in an IntBuf, based on the maximum length of a typed array.
+ */
+const MaxBlocks = 2 ** 32 - 1;
+
 /**
  * Fixed-size buffer of fixed-width integers. Designed to be written once and read many times.
  * A newly constructed IntBuf will have the specified length and all elements will be initialized to zero.
  * Elements can be added by pushing them onto the vector, which will add that element from the front at the lowest available index.
  * In typical use, the vector will be initialized and and then precisely `length` elements will be pushed.
+ * Elements can also be overwritten in place with `set`.
 */
 export class IntBuf {
   /**
@@ -23,9 +29,16 @@ export class IntBuf {
     // our algorithms assume this cannot happen. 
     assert(bitWidth <= 2 * bits.BasicBlockSize);
     assertInteger(length);
+    assert(length >= 0);
 
+    // Note that the total number of bits may exceed 2^32, so bit offsets are computed
+    // with floating-point arithmetic rather than 32-bit bitwise operations.
     const lengthInBits = length * bitWidth;
     const numBlocks = Math.ceil(lengthInBits / bits.BasicBlockSize);
+    assert(
+      numBlocks <= MaxBlocks,
+      () => `an IntBuf of ${length} ${bitWidth}-bit elements requires ${numBlocks} blocks, which exceeds the maximum of ${MaxBlocks}`
+    );
 
     /** @readonly */
     this.data = new Uint32Array(numBlocks);
@@ -64,24 +77,58 @@ export class IntBuf {
     }
     assert(this.writeCursor < this.lengthInBits, 'cannot push into a full IntBuf');
 
-    const index = bits.basicBlockIndex(this.writeCursor);
+    const index = bitIndexToBlockIndex(this.writeCursor);
     const offset = bits.basicBlockBitOffset(this.writeCursor);
 
     // Number of bits available in the current block
     const numAvailableBits = bits.BasicBlockSize - offset;
 
-    DEBUG && assert(index < this.length);
+    DEBUG && assert(index < this.data.length);
     this.data[index] |= value << offset;
     
     // If needed, write any remaining bits into the next block.
     if (numAvailableBits < this.bitWidth) {
-      DEBUG && assert(index + 1 < this.length);
+      DEBUG && assert(index + 1 < this.data.length);
       this.data[index + 1] = value >>> numAvailableBits;
     }
 
     this.writeCursor += this.bitWidth;
   }
 
+  /**
+   * Overwrite the value at `index`. This can be used instead of or after pushing, but values
+   * beyond the write cursor should not be set before they are pushed, since `push` assumes that
+   * the bits it writes to are zero.
+   * @param {number} index
+   * @param {number} value
+   */
+  set(index, value) {
+    assert(Number.isInteger(index) && 0 <= index && index < this.length, () => `index (${index}) is out of bounds for length (${this.length})`);
+    assert(Number.isInteger(value) && 0 <= value && value <= this.lowBitMask, () => `value (${value}) does not fit in ${this.bitWidth} bits`);
+
+    // If the bit width is zero, the only value is zero and there is nothing to write.
+    if (this.bitWidth === 0) {
+      return;
+    }
+
+    const bitIndex = index * this.bitWidth;
+    const blockIndex = bitIndexToBlockIndex(bitIndex);
+    const offset = bits.basicBlockBitOffset(bitIndex);
+
+    // Number of bits available in the current block
+    const numAvailableBits = bits.BasicBlockSize - offset;
+
+    // Clear the old value's bits before setting the new ones. Shifting the mask left
+    // discards the bits that do not fit in this block.
+    this.data[blockIndex] = (this.data[blockIndex] & ~(this.lowBitMask << offset)) | (value << offset);
+
+    // If needed, write the remaining bits into the bottom of the next block.
+    if (numAvailableBits < this.bitWidth) {
+      const numRemainingBits = this.bitWidth - numAvailableBits;
+      this.data[blockIndex + 1] = (this.data[blockIndex + 1] & ~bits.oneMask(numRemainingBits)) | (value >>> numAvailableBits);
+    }
+  }
+
   /**
    * @param {number} index
    */
@@ -94,20 +141,20 @@ export class IntBuf {
     }
 
     const bitIndex = index * this.bitWidth;
-    const blockIndex = bits.basicBlockIndex(bitIndex);
+    const blockIndex = bitIndexToBlockIndex(bitIndex);
     const offset = bits.basicBlockBitOffset(bitIndex);
 
     // Number of bits available in the current block
     const numAvailableBits = bits.BasicBlockSize - offset;
 
-    DEBUG && assert(blockIndex < this.length);
+    DEBUG && assert(blockIndex < this.data.length);
     let value = (this.data[blockIndex] & (this.lowBitMask << offset)) >>> offset;
 
     // If needed, extract the remaining bits from the bottom of the next block
 
     if (numAvailableBits < this.bitWidth) {
       const numRemainingBits = this.bitWidth - numAvailableBits;
-      DEBUG && assert(blockIndex + 1 < this.length);
+      DEBUG && assert(blockIndex + 1 < this.data.length);
       const highBits = this.data[blockIndex + 1] & bits.oneMask(numRemainingBits);
       value |= highBits << numAvailableBits;
     }
@@ -115,3 +162,121 @@ export class IntBuf {
     return value;
   }
 }
+
+/**
+ * Returns the index of the block containing the bit at `bitIndex`. Unlike `bits.basicBlockIndex`,
+ * this is correct for bit indices of 2^32 and above, which can occur in large IntBufs.
+ * (`bits.basicBlockBitOffset` does not have this issue, since 2^32 is a multiple of the block size.)
+ * @param {number} bitIndex
+ */
+export function bitIndexToBlockIndex(bitIndex) {
+  return Math.floor(bitIndex / bits.BasicBlockSize);
+}
+
+/**
+ * Fixed-size buffer of fixed-width unsigned integers of up to 64 bits, stored as `bigint`s.
+ * This is the 64-bit counterpart of `IntBuf`, for values that do not fit in 32 bits, such as
+ * 64-bit symbols or Morton codes. Values are packed into 64-bit words and may straddle two words.
+ * It has the same write-once interface as `IntBuf`: elements are pushed in order and read with `get`.
+ */
+export class IntBuf64 {
+  /**
+   * @param {number} length - the length of the IntBuf64 (in elements)
+   * @param {number} bitWidth
+   */
+  constructor(length, bitWidth) {
+    assertInteger(bitWidth);
+    assert(bitWidth >= 0 && bitWidth <= 64, 'bitWidth must be in [0, 64]');
+    assertInteger(length);
+    assert(length >= 0);
+
+    const lengthInBits = length * bitWidth;
+    const numWords = Math.ceil(lengthInBits / 64);
+    assert(
+      numWords <= MaxBlocks,
+      () => `an IntBuf64 of ${length} ${bitWidth}-bit elements requires ${numWords} words, which exceeds the maximum of ${MaxBlocks}`
+    );
+
+    /** @readonly */
+    this.data = new BigUint64Array(numWords);
+
+    /** @readonly */
+    this.bitWidth = bitWidth;
+
+    /** @readonly */
+    this.length = length;
+
+    /** @readonly */
+    this.lengthInBits = lengthInBits;
+
+    /** @readonly */
+    this.lowBitMask = (1n << BigInt(bitWidth)) - 1n;
+
+    this.writeCursor = 0; // in bits
+  }
+
+  /**
+   * Push a value into the IntBuf64.
+   * Will throw an error if there is no room to store the value,
+   * except that any number of zeros can be pushed when the bit width is zero.
+   * @param {bigint} value
+   */
+  push(value) {
+    DEBUG && assert(typeof value === 'bigint', 'value must be a bigint');
+    DEBUG && assert(value >= 0n, 'value must be positive');
+    DEBUG && assert(value <= this.lowBitMask, 'value does not fit the bit width');
+
+    if (this.bitWidth === 0) {
+      assert(value === 0n, 'value must be zero if the bit width is zero');
+      return;
+    }
+    assert(this.writeCursor < this.lengthInBits, 'cannot push into a full IntBuf64');
+
+    const index = Math.floor(this.writeCursor / 64);
+    const offset = this.writeCursor % 64;
+
+    // Number of bits available in the current word
+    const numAvailableBits = 64 - offset;
+
+    DEBUG && assert(index < this.data.length);
+    // Bits shifted beyond the top of the word are discarded by the typed array
+    this.data[index] |= BigInt.asUintN(64, value << BigInt(offset));
+
+    // If needed, write any remaining bits into the next word.
+    if (numAvailableBits < this.bitWidth) {
+      DEBUG && assert(index + 1 < this.data.length);
+      this.data[index + 1] = value >> BigInt(numAvailableBits);
+    }
+
+    this.writeCursor += this.bitWidth;
+  }
+
+  /**
+   * @param {number} index
+   */
+  get(index) {
+    DEBUG && assert(0 <= index && index < this.length, 'index must be in bounds');
+
+    if (this.bitWidth === 0) {
+      return 0n;
+    }
+
+    const bitIndex = index * this.bitWidth;
+    const wordIndex = Math.floor(bitIndex / 64);
+    const offset = bitIndex % 64;
+
+    // Number of bits available in the current word
+    const numAvailableBits = 64 - offset;
+
+    DEBUG && assert(wordIndex < this.data.length);
+    let value = (this.data[wordIndex] >> BigInt(offset)) & this.lowBitMask;
+
+    // If needed, extract the remaining bits from the bottom of the next word
+    if (numAvailableBits < this.bitWidth) {
+      DEBUG && assert(wordIndex + 1 < this.data.length);
+      value |= (this.data[wordIndex + 1] << BigInt(numAvailableBits)) & this.lowBitMask;
+    }
+
+    return value;
+  }
+}
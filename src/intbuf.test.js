@@ -1,7 +1,7 @@
 import { describe, expect, it, test } from 'vitest';
 import * as bits from './bits.js';
 import './debug.js';
-import { IntBuf } from "./intbuf.js";
+import { bitIndexToBlockIndex, IntBuf, IntBuf64 } from "./intbuf.js";
 
 describe('IntBuf', () => { 
   if (DEBUG) {
@@ -69,3 +69,153 @@ describe('IntBuf', () => {
     }
   });
 });
+
+describe('IntBuf beyond 2^32 bits', () => {
+  it('computes block indices and offsets on both sides of bit index 2^32', () => {
+    // This checks the offset math used by push, set, and get without allocating a large buffer.
+    expect(bitIndexToBlockIndex(2 ** 32 - 1)).toBe(2 ** 27 - 1);
+    expect(bitIndexToBlockIndex(2 ** 32)).toBe(2 ** 27);
+    expect(bitIndexToBlockIndex(2 ** 32 + 33)).toBe(2 ** 27 + 1);
+    // the 32-bit version wraps around at 2^32
+    expect(bits.basicBlockIndex(2 ** 32 + 33)).toBe(1);
+    for (const bitWidth of [1, 7, 31, 32]) {
+      const boundary = Math.floor(2 ** 32 / bitWidth);
+      for (let index = boundary - 3; index <= boundary + 3; index++) {
+        const bitIndex = index * bitWidth;
+        const blockIndex = bitIndexToBlockIndex(bitIndex);
+        const offset = bits.basicBlockBitOffset(bitIndex);
+        expect(blockIndex * bits.BasicBlockSize + offset).toBe(bitIndex);
+        expect(offset).toBeLessThan(bits.BasicBlockSize);
+      }
+    }
+  });
+
+  // This allocates roughly 512 MB, so it only runs when EXPENSIVE_TESTS is set.
+  it.skipIf(!process.env.EXPENSIVE_TESTS)('reads and writes values on both sides of bit index 2^32', () => {
+    // Enough 31-bit values to cross the 2^32 bit boundary at an offset that is
+    // not aligned to a block.
+    const bitWidth = 31;
+    const length = Math.floor(2 ** 32 / bitWidth) + 4;
+    const xs = new IntBuf(length, bitWidth);
+    expect(xs.lengthInBits).toBeGreaterThan(2 ** 32);
+
+    // Skip ahead to a few elements before the boundary rather than pushing every value.
+    const start = Math.floor(2 ** 32 / bitWidth) - 3;
+    xs.writeCursor = start * bitWidth;
+    const values = [2 ** 31 - 1, 12345, 2 ** 30 + 7, 1, 2 ** 31 - 2, 0, 99];
+    expect(start + values.length).toBe(length);
+    for (const value of values) {
+      xs.push(value);
+    }
+    values.forEach((value, i) => {
+      expect(xs.get(start + i)).toBe(value);
+    });
+    // the low blocks are untouched, ie. the bit indices did not wrap around
+    expect(xs.data[0]).toBe(0);
+    expect(xs.data[1]).toBe(0);
+    expect(() => xs.push(0)).toThrow();
+  });
+
+  it('rejects IntBufs that are too large to allocate', () => {
+    expect(() => new IntBuf(2 ** 40, 20)).toThrow('exceeds the maximum');
+  });
+});
+
+describe('IntBuf.set', () => {
+  it('overwrites values without disturbing their neighbors', () => {
+    // bit widths that straddle block boundaries in different ways, including a full block
+    for (const bitWidth of [1, 3, 5, 7, 13, 31, 32]) {
+      const length = 100;
+      const max = bits.oneMask(bitWidth);
+      const initial = Array.from({ length }, (_, i) => (Math.imul(i, 0x9e3779b1) & max) >>> 0);
+      const xs = new IntBuf(length, bitWidth);
+      for (const x of initial) xs.push(x);
+      const expected = initial.slice();
+      for (let i = 0; i < length; i += 3) {
+        for (const value of [0, max, (Math.imul(i, 40503) & max) >>> 0]) {
+          xs.set(i, value);
+          expected[i] = value;
+          for (let j = Math.max(0, i - 2); j < Math.min(length, i + 3); j++) {
+            expect(xs.get(j)).toBe(expected[j]);
+          }
+        }
+      }
+      expect(Array.from({ length }, (_, i) => xs.get(i))).toEqual(expected);
+    }
+  });
+
+  it('can be used without pushing', () => {
+    const xs = new IntBuf(10, 11);
+    xs.set(9, 2047);
+    xs.set(2, 5);
+    expect(Array.from({ length: 10 }, (_, i) => xs.get(i))).toEqual([0, 0, 5, 0, 0, 0, 0, 0, 0, 2047]);
+    new IntBuf(3, 0).set(1, 0);
+  });
+
+  it('rejects invalid indices and values', () => {
+    const xs = new IntBuf(4, 5);
+    expect(() => xs.set(4, 0)).toThrow('index (4) is out of bounds for length (4)');
+    expect(() => xs.set(0, 32)).toThrow('value (32) does not fit in 5 bits');
+    expect(() => xs.set(0, -1)).toThrow('value (-1) does not fit in 5 bits');
+    expect(() => new IntBuf(3, 0).set(0, 1)).toThrow('value (1) does not fit in 0 bits');
+  });
+});
+
+describe('IntBuf64', () => {
+  // xorshift64 for reproducible random 64-bit values
+  let state = 0x9e3779b97f4a7c15n;
+  function random64() {
+    state ^= BigInt.asUintN(64, state << 13n);
+    state ^= state >> 7n;
+    state ^= BigInt.asUintN(64, state << 17n);
+    return state;
+  }
+
+  it('round-trips random values at widths up to 64 bits', () => {
+    // 33 and 63 bits make values straddle word boundaries at varying offsets
+    for (const bitWidth of [1, 7, 32, 33, 63, 64]) {
+      const length = 100;
+      const xs = new IntBuf64(length, bitWidth);
+      const mask = (1n << BigInt(bitWidth)) - 1n;
+      const values = Array.from({ length }, () => random64() & mask);
+      // include the extreme values
+      values[0] = mask;
+      values[1] = 0n;
+      for (const value of values) {
+        xs.push(value);
+      }
+      values.forEach((value, i) => {
+        expect(xs.get(i)).toBe(value);
+      });
+      expect(() => xs.push(0n)).toThrow('full');
+    }
+  });
+
+  it('reads a value straddling two words', () => {
+    const xs = new IntBuf64(2, 40);
+    xs.push(0n);
+    // occupies bits [40, 80): its low 24 bits fill the top of the first word
+    // and its high 16 bits the bottom of the second
+    xs.push(0xab_cdef_0123n);
+    expect(xs.get(1)).toBe(0xab_cdef_0123n);
+    expect(xs.data[0] >> 40n).toBe(0xef_0123n);
+    expect(xs.data[1]).toBe(0xabcdn);
+  });
+
+  it('handles zero bit widths', () => {
+    const xs = new IntBuf64(3, 0);
+    xs.push(0n);
+    expect(xs.get(2)).toBe(0n);
+    expect(() => new IntBuf64(1, 65)).toThrow('bitWidth');
+  });
+
+  if (DEBUG) {
+    it('rejects values that do not fit (in debug mode)', () => {
+      const xs = new IntBuf64(2, 40);
+      expect(() => xs.push(1n << 40n)).toThrow('bit width');
+      expect(() => xs.push(-1n)).toThrow('positive');
+      // @ts-ignore because the argument is deliberately of the wrong type
+      expect(() => xs.push(1)).toThrow('bigint');
+    });
+  }
+});
@@ -0,0 +1,63 @@
+import { assert } from './assert.js';
+import { ascending } from './sort.js';
+import { SparseBitVec } from './sparsebitvec.js';
+
+/**
+ * Immutable ordered set of non-negative integers in [0, universeSize), backed by a SparseBitVec.
+ * Packages the common "ordered integer set" use case of rank and select with a set-like interface.
+ * The set cannot be modified once it has been constructed.
+ */
+export class IntSet {
+  /**
+   * @param {ArrayLike<number>} values - set elements in any order; duplicates are ignored
+   * @param {number} universeSize - one more than the largest value that may be in the set
+   */
+  constructor(values, universeSize) {
+    const sorted = Array.from(values).sort(ascending);
+    // Remove duplicates in-place
+    let n = 0;
+    for (let i = 0; i < sorted.length; i++) {
+      if (i === 0 || sorted[i] !== sorted[i - 1]) {
+        sorted[n++] = sorted[i];
+      }
+    }
+    sorted.length = n;
+
+    /** @readonly */
+    this.bv = new SparseBitVec(sorted, universeSize);
+    assert(!this.bv.hasMultiplicity);
+
+    /** @readonly */
+    this.size = this.bv.numOnes;
+
+    /** @readonly */
+    this.universeSize = universeSize;
+  }
+
+  /**
+   * Returns true if `value` is an element of this set.
+   * @param {number} value
+   */
+  contains(value) {
+    if (!Number.isInteger(value) || value < 0 || value >= this.universeSize) {
+      return false;
+    }
+    return this.bv.get(value) === 1;
+  }
+
+  /**
+   * Returns the smallest element greater than or equal to `value`, or `null` if there is none.
+   * @param {number} value
+   */
+  successor(value) {
+    return this.bv.trySelect1(this.bv.rank1(value));
+  }
+
+  /**
+   * Returns the largest element less than or equal to `value`, or `null` if there is none.
+   * @param {number} value
+   */
+  predecessor(value) {
+    return this.bv.trySelect1(this.bv.rank1(value + 1) - 1);
+  }
+}
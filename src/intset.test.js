@@ -0,0 +1,49 @@
+import { describe, expect, it, test } from 'vitest';
+import './debug.js';
+import { IntSet } from './intset.js';
+
+describe('IntSet', () => {
+  const universeSize = 100;
+  const set = new IntSet([40, 10, 90, 10, 55], universeSize);
+
+  it('ignores duplicates', () => {
+    expect(set.size).toBe(4);
+  });
+
+  it('contains', () => {
+    expect(set.contains(10)).toBe(true);
+    expect(set.contains(40)).toBe(true);
+    expect(set.contains(11)).toBe(false);
+    expect(set.contains(0)).toBe(false);
+    expect(set.contains(-1)).toBe(false);
+    expect(set.contains(universeSize)).toBe(false);
+  });
+
+  it('successor', () => {
+    expect(set.successor(-5)).toBe(10); // below the universe
+    expect(set.successor(0)).toBe(10); // below the minimum
+    expect(set.successor(10)).toBe(10);
+    expect(set.successor(11)).toBe(40);
+    expect(set.successor(90)).toBe(90); // at the maximum
+    expect(set.successor(91)).toBe(null); // above the maximum
+    expect(set.successor(universeSize + 5)).toBe(null); // above the universe
+  });
+
+  it('predecessor', () => {
+    expect(set.predecessor(-5)).toBe(null); // below the universe
+    expect(set.predecessor(9)).toBe(null); // below the minimum
+    expect(set.predecessor(10)).toBe(10); // at the minimum
+    expect(set.predecessor(39)).toBe(10);
+    expect(set.predecessor(40)).toBe(40);
+    expect(set.predecessor(99)).toBe(90); // above the maximum
+    expect(set.predecessor(universeSize + 5)).toBe(90); // above the universe
+  });
+
+  it('handles the empty set', () => {
+    const set = new IntSet([], 10);
+    expect(set.size).toBe(0);
+    expect(set.contains(0)).toBe(false);
+    expect(set.successor(0)).toBe(null);
+    expect(set.predecessor(9)).toBe(null);
+  });
+});
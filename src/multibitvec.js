@@ -1,10 +1,17 @@
-import { assert, assertSafeInteger } from './assert.js';
+import { assert, assertBitIndex, assertSafeInteger } from './assert.js';
 import { BitBuf } from './bitbuf.js';
+import { BasicBlockArray, BasicBlockSize, oneMask } from './bits.js';
 import * as defaults from './defaults';
-import { DenseBitVec } from './densebitvec.js';
+import { DefaultSmallThreshold, DenseBitVec } from './densebitvec.js';
 import { ascending } from './sort.js';
 import { SparseBitVec } from './sparsebitvec.js';
 
+/** The keys accepted in the `occupancy` options of `MultiBitVecBuilder.build` */
+const DenseOptionKeys = ['rank1SamplesPow2', 'selectSamplesPow2', 'smallThreshold', 'buildSelect0Samples', 'buildSelect1Samples'];
+
+/** The keys accepted in the `counts` options of `MultiBitVecBuilder.build` */
+const SparseOptionKeys = ['lowBitWidth', 'rank1SamplesPow2', 'selectSamplesPow2'];
+
 /**
  * @implements {BitVecBuilder}
  */
@@ -18,20 +25,39 @@ export class MultiBitVecBuilder {
      * Stores a map from 1-bit index to its multiplicity (count).
      * @type Map<number, number> */
     this.counts = new Map();
+    this.isBuilt = false;
   }
 
   /**
    * @param {number} index
    */
   one(index, count = 1) {
+    assert(!this.isBuilt, 'cannot modify a builder after it has been built');
+    assertBitIndex(index, this.buf.universeSize);
     assert(count > 0);
     assertSafeInteger(count);
     this.buf.setOne(index);
     this.counts.set(index, (this.counts.get(index) ?? 0) + count);
   }
 
-  build({ occupancyRank1SamplesPow2 = 10, occupancySelectSamplesPow2 = 10 } = {}) {
-    console.log();
+  /**
+   * The options are nested by component: `occupancy` configures the dense bit vector that marks
+   * the indices with nonzero multiplicity, which every rank and select consults, and `counts`
+   * configures the sparse bit vector of cumulative counts.
+   * @param {{
+   *   occupancy?: import('./adaptivebitvec.js').DenseBuildOptions,
+   *   counts?: import('./sparsebitvec.js').SparseBuildOptions,
+   * }} [options]
+   */
+  build(options = {}) {
+    assert(!this.isBuilt, 'a builder can only be built once');
+    // Reject unknown keys, which would otherwise be silently ignored. This includes the flat
+    // sample options like `occupancyRank1SamplesPow2` that predate the nested options.
+    assertKnownOptions(options, ['occupancy', 'counts'], 'options');
+    const { occupancy: occupancyOptions = {}, counts: countsOptions = {} } = options;
+    assertKnownOptions(occupancyOptions, DenseOptionKeys, 'occupancy options');
+    assertKnownOptions(countsOptions, SparseOptionKeys, 'counts options');
+    this.isBuilt = true;
     // sort 
     const entries = Array.from(this.counts.entries()).sort((a, b) => ascending(a[0], b[0]));
     const cumulativeCounts = new Float64Array(entries.map(kv => kv[1]));
@@ -40,10 +66,47 @@ export class MultiBitVecBuilder {
       cumulativeCounts[i] += cumulativeCounts[i - 1];
     }
 
-    const occupancy = new DenseBitVec(this.buf.maybePadded(), occupancyRank1SamplesPow2, occupancySelectSamplesPow2);
-    const multiplicity = new SparseBitVec(cumulativeCounts, len > 0 ? cumulativeCounts[len - 1] + 1 : 0);
+    const {
+      rank1SamplesPow2 = 10,
+      selectSamplesPow2 = 10,
+      smallThreshold = DefaultSmallThreshold,
+      buildSelect0Samples = true,
+      buildSelect1Samples = true
+    } = occupancyOptions;
+    const occupancy = new DenseBitVec(
+      this.buf.maybePadded(),
+      rank1SamplesPow2,
+      selectSamplesPow2,
+      smallThreshold,
+      buildSelect0Samples,
+      buildSelect1Samples
+    );
+    const multiplicity = new SparseBitVec(cumulativeCounts, len > 0 ? cumulativeCounts[len - 1] + 1 : 0, countsOptions);
     return new MultiBitVec(occupancy, multiplicity);
   }
+
+  /**
+   * @param {number} universeSize
+   * @param {ArrayLike<number>} sortedOnes
+   */
+  static fromSortedOnes(universeSize, sortedOnes, options = {}) {
+    return defaults.fromSortedOnes(MultiBitVecBuilder, universeSize, sortedOnes, options);
+  }
+
+  /**
+   * Build a multi bit vector from `[index, count]` pairs, eg. from the entries of a `Map`
+   * from 1-bit index to multiplicity. The pairs can be in any order, and the counts of
+   * repeated indices add up, as with repeated calls to `one`.
+   * @param {number} universeSize
+   * @param {Iterable<[number, number]>} pairs
+   */
+  static fromPairs(universeSize, pairs, options = {}) {
+    const builder = new MultiBitVecBuilder(universeSize);
+    for (const [index, count] of pairs) {
+      builder.one(index, count);
+    }
+    return builder.build(options);
+  }
 }
 
 /**
@@ -148,4 +211,71 @@ export class MultiBitVec {
   get(index) {
     return defaults.get(this, index);
   }
-}
\ No newline at end of file
+
+  /**
+   * @param {number} index
+   */
+  rank1Checked(index) {
+    return defaults.rank1Checked(this, index);
+  }
+
+  /**
+   * @param {number} first
+   * @param {number} last
+   */
+  countOnesInInclusive(first, last) {
+    return defaults.countOnesInInclusive(this, first, last);
+  }
+
+  /**
+   * @param {number} k
+   * @param {number} seed
+   */
+  sampleOnes(k, seed) {
+    return defaults.sampleOnes(this, k, seed);
+  }
+
+  /**
+   * @param {{ start: number, end: number }} range
+   */
+  zeroRuns(range) {
+    return defaults.zeroRuns(this, range);
+  }
+
+  /**
+   * @param {{ start: number, end: number }} range
+   * @param {number} minLength
+   */
+  firstZeroRunAtLeast(range, minLength) {
+    return defaults.firstZeroRunAtLeast(this, range, minLength);
+  }
+
+  /**
+   * Return the occupancy of this bit vector (which bits are set, ignoring multiplicity)
+   * as packed basic blocks, eg. for export to a plain bitmap format.
+   */
+  toOccupancyBlocks() {
+    const occupancy = this.occupancy;
+    if (!(occupancy instanceof DenseBitVec)) {
+      return defaults.occupancyBlocks(occupancy);
+    }
+    const blocks = new BasicBlockArray(occupancy.data.iterBlocks());
+    // The trailing bits of a padded buffer may be set, so clear them.
+    if (blocks.length > 0) {
+      blocks[blocks.length - 1] &= oneMask(BasicBlockSize - occupancy.data.numTrailingBits);
+    }
+    return blocks;
+  }
+}
+
+/**
+ * Throws an error if `options` has any keys not in `knownKeys`.
+ * @param {Object} options
+ * @param {string[]} knownKeys
+ * @param {string} name - name of the options object, for the error message
+ */
+function assertKnownOptions(options, knownKeys, name) {
+  for (const key of Object.keys(options)) {
+    assert(knownKeys.includes(key), () => `unknown key in ${name}: ${key} (expected one of ${knownKeys.join(', ')})`);
+  }
+}
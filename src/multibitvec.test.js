@@ -1,10 +1,21 @@
 import { describe, expect, it, test } from 'vitest';
+import * as bits from './bits.js';
 import { MultiBitVecBuilder } from './multibitvec.js';
 import { testBitVecType, testMultiBitVecType } from './testutils.js';
 
 testBitVecType(MultiBitVecBuilder);
 testMultiBitVecType(MultiBitVecBuilder);
 
+describe('MultiBitVec with nested options', () => {
+  for (const options of [
+    { occupancy: { rank1SamplesPow2: 5, selectSamplesPow2: 5, smallThreshold: 0 }, counts: { lowBitWidth: 0 } },
+    { occupancy: { buildSelect0Samples: false, smallThreshold: 0 }, counts: { lowBitWidth: 7, rank1SamplesPow2: 5, selectSamplesPow2: 6 } },
+  ]) {
+    testBitVecType(MultiBitVecBuilder, options);
+    testMultiBitVecType(MultiBitVecBuilder, options);
+  }
+});
+
 describe('MultiBitVec', () => {
   test('can contain (very large) multitudes', () => {
     const builder = new MultiBitVecBuilder(6);
@@ -21,4 +32,87 @@ describe('MultiBitVec', () => {
     expect(bv.rank1(6)).toBe(3e9);
     expect(bv.rank1(7)).toBe(3e9);
   });
-});
\ No newline at end of file
+
+  test('toOccupancyBlocks', () => {
+    const universeSize = 10 * bits.BasicBlockSize + 5;
+    // the second builder sets every bit so that the occupancy is padded with 1-bits
+    for (const step of [7, 1]) {
+      const builder = new MultiBitVecBuilder(universeSize);
+      for (let i = 0; i < universeSize; i += step) {
+        builder.one(i, 1 + (i % 3));
+      }
+      const bv = builder.build();
+      const blocks = bv.toOccupancyBlocks();
+      expect(blocks.length).toBe(Math.ceil(universeSize / bits.BasicBlockSize));
+      for (let i = 0; i < blocks.length * bits.BasicBlockSize; i++) {
+        const bit = (blocks[bits.basicBlockIndex(i)] >>> bits.basicBlockBitOffset(i)) & 1;
+        expect(bit).toBe(i < universeSize && bv.get(i) > 0 ? 1 : 0);
+      }
+    }
+  });
+
+  test('passes nested options to its components', () => {
+    const pairs = /** @type {[number, number][]} */ (Array.from({ length: 300 }, (_, i) => [3 * i, 1 + (i % 4)]));
+    const bv = MultiBitVecBuilder.fromPairs(1000, pairs, {
+      occupancy: { rank1SamplesPow2: 6, selectSamplesPow2: 7, smallThreshold: 0, buildSelect0Samples: false },
+      counts: { lowBitWidth: 2, rank1SamplesPow2: 8, selectSamplesPow2: 9 },
+    });
+    expect(bv.occupancy.rank1SamplesPow2).toBe(6);
+    expect(bv.occupancy.select1SamplesPow2).toBe(7);
+    expect(bv.occupancy.isSmall).toBe(false);
+    expect(bv.occupancy.hasSelect0Samples).toBe(false);
+    expect(bv.multiplicity.lowBitWidth).toBe(2);
+    expect(bv.multiplicity.high.rank1SamplesPow2).toBe(8);
+    expect(bv.multiplicity.high.select1SamplesPow2).toBe(9);
+
+    // the defaults are unchanged
+    const defaults = MultiBitVecBuilder.fromPairs(1000, pairs);
+    expect(defaults.occupancy.rank1SamplesPow2).toBe(10);
+    expect(defaults.multiplicity.high.rank1SamplesPow2).toBe(10);
+    for (let i = 0; i <= 1000; i++) {
+      expect(bv.rank1(i)).toBe(defaults.rank1(i));
+    }
+    expect(() => MultiBitVecBuilder.fromPairs(10, [[1, 1]], { counts: { lowBitWidth: 32 } })).toThrow('expected lowBitWidth to be an integer in [0, 32), got 32');
+  });
+
+  test('rejects unknown options', () => {
+    /** @type {any[]} */
+    const invalid = [
+      // the flat options that predate the nested ones
+      { occupancyRank1SamplesPow2: 6 },
+      { rank1SamplesPow2: 6 },
+      { occupancy: { rank1SamplesPow2: 6 }, countsSelectSamplesPow2: 6 },
+      { occupancy: { lowBitWidth: 2 } },
+      { counts: { smallThreshold: 0 } },
+    ];
+    for (const options of invalid) {
+      const builder = new MultiBitVecBuilder(10);
+      builder.one(1, 2);
+      expect(() => builder.build(options)).toThrow('unknown key');
+    }
+    expect(() => MultiBitVecBuilder.fromPairs(10, [[1, 1]], /** @type {any} */ ({ occupancySelectSamplesPow2: 6 }))).toThrow(
+      'unknown key in options: occupancySelectSamplesPow2'
+    );
+  });
+
+  test('fromPairs', () => {
+    const counts = new Map([[7, 2], [0, 1], [40, 5], [3, 1e6]]);
+    const builder = new MultiBitVecBuilder(50);
+    for (const index of [0, 3, 7, 40]) {
+      builder.one(index, counts.get(index));
+    }
+    const expected = builder.build();
+    const bv = MultiBitVecBuilder.fromPairs(50, counts);
+    for (let i = 0; i <= 50; i++) {
+      expect(bv.rank1(i)).toBe(expected.rank1(i));
+      expect(bv.rank0(i)).toBe(expected.rank0(i));
+    }
+    expect(bv.numOnes).toBe(expected.numOnes);
+
+    // repeated indices accumulate
+    const repeated = MultiBitVecBuilder.fromPairs(10, [[2, 1], [5, 3], [2, 4]]);
+    expect(repeated.rank1(3)).toBe(5);
+    expect(repeated.numOnes).toBe(8);
+    expect(() => MultiBitVecBuilder.fromPairs(10, [[10, 1]])).toThrow('cannot exceed universeSize');
+  });
+});
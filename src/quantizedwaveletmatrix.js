@@ -0,0 +1,180 @@
+import { assert } from './assert.js';
+import { WaveletMatrix } from './waveletmatrix.js';
+
+/**
+ * Maps real values to integer bins and back. Bin `b` covers the half-open value interval
+ * `[binStart(b), binStart(b + 1))`, and values are assigned to bins using exactly these
+ * boundaries, so that a value lying on a bin edge always belongs to the bin that starts there.
+ */
+export class Quantizer {
+  /**
+   * @param {number} min - the start of the first bin
+   * @param {number} step - the width of each bin
+   * @param {number} numBins
+   */
+  constructor(min, step, numBins) {
+    assert(Number.isFinite(min), 'min must be finite');
+    assert(Number.isFinite(step) && step > 0, 'step must be positive and finite');
+    assert(Number.isInteger(numBins) && numBins > 0 && numBins <= 2 ** 32, 'numBins must be an integer in [1, 2^32]');
+    /** @readonly */
+    this.min = min;
+    /** @readonly */
+    this.step = step;
+    /** @readonly */
+    this.numBins = numBins;
+    /**
+     * The end of the last bin.
+     * @readonly
+     */
+    this.max = this.binStart(numBins);
+  }
+
+  /**
+   * Construct a quantizer with `numBins` bins of width `step`, starting at `min`.
+   * @param {number} min
+   * @param {number} step
+   * @param {number} numBins
+   */
+  static linear(min, step, numBins) {
+    return new Quantizer(min, step, numBins);
+  }
+
+  /**
+   * Returns the value at which bin `bin` starts. `bin` may equal `numBins`,
+   * in which case this returns the end of the last bin.
+   * @param {number} bin
+   */
+  binStart(bin) {
+    return this.min + bin * this.step;
+  }
+
+  /**
+   * Returns the value at the center of bin `bin`, which is used to represent the bin's values.
+   * @param {number} bin
+   */
+  binCenter(bin) {
+    return this.min + (bin + 0.5) * this.step;
+  }
+
+  /**
+   * Returns the bin containing `value`, clamping values outside of `[min, max)`
+   * to the first or last bin. This is used to quantize data values.
+   * @param {number} value
+   */
+  bin(value) {
+    return Math.max(0, Math.min(this.unclampedBin(value), this.numBins - 1));
+  }
+
+  /**
+   * Returns the bin containing `value`, or -1 for values below `min` and `numBins`
+   * for values at or above `max`. This is used to convert query thresholds to bins.
+   * @param {number} value
+   */
+  unclampedBin(value) {
+    assert(!Number.isNaN(value), 'cannot quantize NaN');
+    if (value < this.min) return -1;
+    if (value >= this.max) return this.numBins;
+    // The division may be off by one due to floating-point rounding,
+    // so adjust the estimate to agree with `binStart`.
+    const bin = Math.min(Math.floor((value - this.min) / this.step), this.numBins - 1);
+    if (this.binStart(bin) > value) return bin - 1;
+    if (this.binStart(bin + 1) <= value) return bin + 1;
+    return bin;
+  }
+}
+
+/**
+ * Wavelet matrix over quantized real values, whose queries accept and return values
+ * rather than bin indices. The symbols of the underlying wavelet matrix are bin indices.
+ */
+export class QuantizedWaveletMatrix {
+  /**
+   * @param {WaveletMatrix} wm - wavelet matrix whose symbols are bin indices of `quantizer`
+   * @param {Quantizer} quantizer
+   */
+  constructor(wm, quantizer) {
+    assert(wm.maxSymbol < quantizer.numBins, 'the wavelet matrix contains symbols outside of the quantizer bins');
+    /** @readonly */
+    this.wm = wm;
+    /** @readonly */
+    this.quantizer = quantizer;
+    /** @readonly */
+    this.length = wm.length;
+  }
+
+  /**
+   * Quantize `values` and construct a wavelet matrix over their bins.
+   * Values outside of the quantizer's range are clamped to the first or last bin.
+   * @param {ArrayLike<number>} values
+   * @param {Quantizer} quantizer
+   */
+  static fromValues(values, quantizer) {
+    const bins = Array.from(values, v => quantizer.bin(v));
+    return new QuantizedWaveletMatrix(new WaveletMatrix(bins, quantizer.numBins - 1), quantizer);
+  }
+
+  /**
+   * Returns the representative value (bin center) of the element at `index`.
+   * @param {number} index
+   */
+  get(index) {
+    return this.quantizer.binCenter(this.wm.get(index));
+  }
+
+  /**
+   * Number of elements in the index range whose bins lie entirely below `value`.
+   * Elements in the bin containing `value` are not counted, even if they were below it
+   * before quantization. Values below the first bin count nothing, and values at or above
+   * the end of the last bin count every element.
+   * @param {number} value
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  countLessThan(value, { range = { start: 0, end: this.length } } = {}) {
+    // If `value` is on a bin edge, the bin containing it starts at `value`, so it is excluded.
+    const bin = this.quantizer.unclampedBin(value);
+    return this.wm.countLessThanOrEqual(bin - 1, { range });
+  }
+
+  /**
+   * Returns one `{ value, bin, count }` object per bin with elements in the index range
+   * whose bin overlaps the half-open value range `valueRange`, where `value` is the bin center.
+   * The value range is clamped to the range of the quantizer.
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   * @param {{ start: number; end: number; }} [options.valueRange]
+   */
+  counts({ range = { start: 0, end: this.length }, valueRange = { start: this.quantizer.min, end: this.quantizer.max } } = {}) {
+    const q = this.quantizer;
+    if (!(valueRange.start < valueRange.end)) {
+      assert(!Number.isNaN(valueRange.start) && !Number.isNaN(valueRange.end), 'cannot quantize NaN');
+      return [];
+    }
+    const startBin = q.unclampedBin(valueRange.start);
+    const endBin = q.unclampedBin(valueRange.end);
+    // The value range end is exclusive, so the bin containing it overlaps the
+    // value range unless it starts exactly at the end.
+    const start = Math.max(0, startBin);
+    const end = q.binStart(endBin) === valueRange.end ? endBin : endBin + 1;
+    // The end may precede the clamped start if the value range lies below the first bin.
+    const symbolRange = { start, end: Math.max(start, end) };
+    return this.wm.counts({ range, symbolRange }).map(x => ({
+      value: q.binCenter(x.symbol),
+      bin: x.symbol,
+      count: x.end - x.start
+    }));
+  }
+
+  /**
+   * Returns the `k`-th smallest element in the index range as `{ value, bin, count }`,
+   * where `value` is the representative value of its bin and `count` is the number of
+   * elements in the index range that share its bin.
+   * @param {number} k
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  quantile(k, { range = { start: 0, end: this.length } } = {}) {
+    const { symbol, count } = this.wm.quantile(k, { range });
+    return { value: this.quantizer.binCenter(symbol), bin: symbol, count };
+  }
+}
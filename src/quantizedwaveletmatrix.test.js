@@ -0,0 +1,108 @@
+import { describe, expect, it, test } from 'vitest';
+import './debug.js';
+import { QuantizedWaveletMatrix, Quantizer } from './quantizedwaveletmatrix.js';
+
+describe('QuantizedWaveletMatrix', () => {
+  // A step of 0.1 makes floating-point division round the wrong way
+  // for some values on bin edges, eg. (0.3 - 0.1) / 0.1 < 2.
+  const quantizer = Quantizer.linear(0.1, 0.1, 10);
+
+  /**
+   * Brute-force reference for `unclampedBin`: the last bin that starts at or below `value`.
+   * @param {number} value
+   */
+  function referenceBin(value) {
+    if (value < quantizer.min) return -1;
+    if (value >= quantizer.max) return quantizer.numBins;
+    let bin = 0;
+    while (quantizer.binStart(bin + 1) <= value) bin++;
+    return bin;
+  }
+
+  // bin edges (as computed by the quantizer and as written in decimal),
+  // values just inside each bin, and values far outside of the quantizer range
+  const edges = Array.from({ length: quantizer.numBins + 3 }, (_, i) => quantizer.binStart(i - 1));
+  const testValues = [
+    ...edges,
+    ...edges.map(x => x + 1e-9),
+    ...edges.map(x => x - 1e-9),
+    0, 0.1, 0.2, 0.3, 0.6, 0.7, 1.0, 1.1, 1.2,
+    -1e9, 1e9, -Infinity, Infinity
+  ];
+
+  it('assigns values on bin edges to the bin that starts there', () => {
+    for (const value of testValues) {
+      expect(quantizer.unclampedBin(value)).toBe(referenceBin(value));
+      expect(quantizer.bin(value)).toBe(Math.max(0, Math.min(referenceBin(value), quantizer.numBins - 1)));
+    }
+    for (let bin = 0; bin < quantizer.numBins; bin++) {
+      expect(quantizer.bin(quantizer.binStart(bin))).toBe(bin);
+      expect(quantizer.bin(quantizer.binCenter(bin))).toBe(bin);
+    }
+  });
+
+  it('rejects NaN and invalid parameters', () => {
+    expect(() => quantizer.bin(NaN)).toThrow('NaN');
+    expect(() => Quantizer.linear(0, 0, 10)).toThrow();
+    expect(() => Quantizer.linear(0, -1, 10)).toThrow();
+    expect(() => Quantizer.linear(NaN, 1, 10)).toThrow();
+    expect(() => Quantizer.linear(0, 1, 0)).toThrow();
+    expect(() => Quantizer.linear(0, 1, 2.5)).toThrow();
+  });
+
+  // data values, including ones on bin edges and outside of the quantizer range
+  const values = [0.35, 0.3, 0.1, 1.05, 0.2, -5, 0.7, 0.3, 2.5, 0.65, 0.6, 0.15];
+  const bins = values.map(v => referenceBin(v)).map(b => Math.max(0, Math.min(b, quantizer.numBins - 1)));
+  const qwm = QuantizedWaveletMatrix.fromValues(values, quantizer);
+
+  it('get returns bin centers', () => {
+    bins.forEach((bin, i) => {
+      expect(qwm.get(i)).toBe(quantizer.binCenter(bin));
+    });
+  });
+
+  it('countLessThan', () => {
+    const range = { start: 2, end: 9 };
+    for (const value of [...testValues, ...values]) {
+      // count the elements whose bins lie entirely below the value
+      const expected = (/** @type {number[]} */ xs) => xs.filter(b => quantizer.binStart(b + 1) <= value).length;
+      expect(qwm.countLessThan(value)).toBe(expected(bins));
+      expect(qwm.countLessThan(value, { range })).toBe(expected(bins.slice(range.start, range.end)));
+    }
+    expect(() => qwm.countLessThan(NaN)).toThrow('NaN');
+  });
+
+  it('counts', () => {
+    for (const start of testValues) {
+      for (const end of testValues) {
+        // count the elements whose bins overlap the half-open value range [start, end)
+        const overlaps = (/** @type {number} */ b) =>
+          start < end && quantizer.binStart(b) < end && start < quantizer.binStart(b + 1);
+        /** @type {Map<number, number>} */
+        const expected = new Map();
+        for (const b of bins.filter(overlaps)) {
+          expected.set(b, (expected.get(b) ?? 0) + 1);
+        }
+        const actual = qwm.counts({ valueRange: { start, end } });
+        expect(new Map(actual.map(x => [x.bin, x.count]))).toEqual(expected);
+        for (const x of actual) {
+          expect(x.value).toBe(quantizer.binCenter(x.bin));
+        }
+      }
+    }
+    expect(qwm.counts().reduce((acc, x) => acc + x.count, 0)).toBe(values.length);
+    expect(() => qwm.counts({ valueRange: { start: NaN, end: 1 } })).toThrow('NaN');
+    expect(() => qwm.counts({ valueRange: { start: 0, end: NaN } })).toThrow('NaN');
+  });
+
+  it('quantile', () => {
+    const sorted = bins.slice().sort((a, b) => a - b);
+    sorted.forEach((bin, k) => {
+      expect(qwm.quantile(k)).toEqual({
+        value: quantizer.binCenter(bin),
+        bin,
+        count: sorted.filter(b => b === bin).length
+      });
+    });
+  });
+});
@@ -0,0 +1,370 @@
+import { assert } from './assert.js';
+import { DenseBitVecBuilder } from './densebitvec.js';
+import { Range, rangeIsEmpty } from './range.js';
+import { SortedArrayBitVecBuilder } from './sortedarraybitvec.js';
+import { ascending } from './sort.js';
+import { coalesceRanges, WaveletMatrix } from './waveletmatrix.js';
+
+// A small expression language for row filters over a set of co-indexed wavelet matrix columns,
+// such as `(category IN [3, 7..9]) AND (x, y IN rect) AND NOT (id IN selection)`:
+//
+//   And(
+//     SymbolRange('category', [Range(3, 4), Range(7, 10)]),
+//     Rect('x', Range(0, 100), 'y', Range(50, 60)),
+//     Not(PositionMask(selection)),
+//   )
+//
+// Expressions are evaluated by a `QueryContext`, which plans the evaluation order using exact
+// counts of the leaves: the operands of an `And` are ordered from most to least selective, the
+// first is materialized into a set of positions, and the rest are tested only at those positions.
+// Intermediate position sets are materialized as bit vectors, either dense or as sorted index
+// lists depending on their estimated density.
+
+/**
+ * @typedef {{ start: number; end: number; }} SymbolRangeBounds
+ * @typedef {{ type: 'symbolRange', column: string, ranges: SymbolRangeBounds[] }} SymbolRangeExpr
+ * @typedef {{ type: 'rect', xColumn: string, xRange: SymbolRangeBounds, yColumn: string, yRange: SymbolRangeBounds }} RectExpr
+ * @typedef {{ type: 'positionMask', bitVec: BitVec }} PositionMaskExpr
+ * @typedef {{ type: 'and', children: Expr[] }} AndExpr
+ * @typedef {{ type: 'or', children: Expr[] }} OrExpr
+ * @typedef {{ type: 'not', child: Expr }} NotExpr
+ * @typedef {SymbolRangeExpr | RectExpr | PositionMaskExpr | AndExpr | OrExpr | NotExpr} Expr
+ */
+
+/**
+ * Events reported to the `onTrace` option of a `QueryContext` as a query is evaluated.
+ * `estimate` is reported for each operand of an `And` before they are ordered, `materialize`
+ * when an expression's positions are computed, and `filter` when an expression is tested
+ * at the positions that passed the preceding operands of an `And`.
+ * @typedef {(
+ *   { type: 'estimate', expr: Expr, estimate: number } |
+ *   { type: 'materialize', expr: Expr, estimate: number, representation: 'dense' | 'sorted' } |
+ *   { type: 'filter', expr: Expr, candidates: number }
+ * )} TraceEvent
+ */
+
+/**
+ * Position sets with at least this fraction of 1-bits are materialized as dense bit vectors,
+ * and sparser ones as sorted index lists. A sorted index list takes 32 bits per position and a
+ * dense bit vector takes one bit per element, so this is roughly the density at which the dense
+ * representation becomes smaller.
+ */
+export const DenseThreshold = 1 / 32;
+
+/**
+ * Rows whose symbol in `column` lies in any of the given half-open symbol ranges.
+ * @param {string} column
+ * @param {SymbolRangeBounds | SymbolRangeBounds[]} ranges
+ * @returns {SymbolRangeExpr}
+ */
+export function SymbolRange(column, ranges) {
+  return { type: 'symbolRange', column, ranges: coalesceRanges(Array.isArray(ranges) ? ranges : [ranges]) };
+}
+
+/**
+ * Rows whose point `(x, y)` lies in the rectangle `xRange` × `yRange`, with the x and y coordinates
+ * taken from separate columns. This is planned like an `And` of the two symbol ranges, so that the
+ * more selective of the two is evaluated first.
+ * @param {string} xColumn
+ * @param {SymbolRangeBounds} xRange
+ * @param {string} yColumn
+ * @param {SymbolRangeBounds} yRange
+ * @returns {RectExpr}
+ */
+export function Rect(xColumn, xRange, yColumn, yRange) {
+  return { type: 'rect', xColumn, xRange, yColumn, yRange };
+}
+
+/**
+ * Rows whose position is a 1-bit in the bit vector, whose universe size must equal the number of rows.
+ * @param {BitVec} bitVec
+ * @returns {PositionMaskExpr}
+ */
+export function PositionMask(bitVec) {
+  assert(!bitVec.hasMultiplicity, 'position masks cannot have multiplicity');
+  return { type: 'positionMask', bitVec };
+}
+
+/**
+ * @param {...Expr} children
+ * @returns {AndExpr}
+ */
+export function And(...children) {
+  return { type: 'and', children };
+}
+
+/**
+ * @param {...Expr} children
+ * @returns {OrExpr}
+ */
+export function Or(...children) {
+  return { type: 'or', children };
+}
+
+/**
+ * @param {Expr} child
+ * @returns {NotExpr}
+ */
+export function Not(child) {
+  return { type: 'not', child };
+}
+
+export class QueryContext {
+  /**
+   * @param {Record<string, WaveletMatrix>} columns - co-indexed columns, all of the same length
+   * @param {Object} [options]
+   * @param {number} [options.length] - the number of rows, which is only required if there are no columns
+   * @param {((event: TraceEvent) => void) | null} [options.onTrace] - called as the query is evaluated; see `TraceEvent`
+   */
+  constructor(columns, { length = undefined, onTrace = null } = {}) {
+    const lengths = Object.values(columns).map(wm => wm.length);
+    length ??= lengths[0];
+    assert(length !== undefined, 'the number of rows must be specified if there are no columns');
+    assert(lengths.every(l => l === length), () => `expected all columns to have length ${length}, got ${lengths}`);
+    /** @readonly */
+    this.columns = columns;
+    /** @readonly */
+    this.length = length;
+    /** @readonly */
+    this.onTrace = onTrace;
+  }
+
+  /**
+   * Number of rows matching the expression.
+   * @param {Expr} expr
+   */
+  count(expr) {
+    // Leaves can be counted directly without materializing their positions
+    if (expr.type === 'symbolRange' || expr.type === 'positionMask') {
+      return this.estimate(expr);
+    }
+    return this.positions(expr).numOnes;
+  }
+
+  /**
+   * Positions of the rows matching the expression, as a bit vector over the rows.
+   * @param {Expr} expr
+   * @returns {BitVec}
+   */
+  positions(expr) {
+    switch (expr.type) {
+      case 'symbolRange': {
+        const estimate = this.estimate(expr);
+        const wm = this.column(expr.column);
+        const builder = this.builder(expr, estimate);
+        pushPositions(wm, expr.ranges, Range(0, this.length), i => builder.one(i));
+        return builder.build();
+      }
+      case 'rect':
+        return this.positions(rectAsAnd(expr));
+      case 'positionMask':
+        this.estimate(expr); // validates the universe size
+        return expr.bitVec;
+      case 'and': {
+        if (expr.children.length === 0) {
+          return this.positions(Not(Or()));
+        }
+        const estimates = expr.children.map(child => {
+          const estimate = this.estimate(child);
+          this.onTrace?.({ type: 'estimate', expr: child, estimate });
+          return { child, estimate };
+        });
+        // Sort stably so that operands with equal estimates are evaluated in the given order
+        estimates.sort((a, b) => a.estimate - b.estimate);
+        const first = this.positions(estimates[0].child);
+        if (estimates.length === 1) {
+          return first;
+        }
+        let candidates = Array.from(ones(first));
+        for (let i = 1; i < estimates.length; i++) {
+          const { child } = estimates[i];
+          this.onTrace?.({ type: 'filter', expr: child, candidates: candidates.length });
+          candidates = candidates.filter(index => this.matches(child, index));
+        }
+        return this.fromSorted(expr, candidates);
+      }
+      case 'or': {
+        /** @type {Set<number>} */
+        const union = new Set();
+        for (const child of expr.children) {
+          for (const index of ones(this.positions(child))) union.add(index);
+        }
+        return this.fromSorted(expr, Array.from(union).sort(ascending));
+      }
+      case 'not': {
+        const positions = this.positions(expr.child);
+        const builder = this.builder(expr, this.length - positions.numOnes);
+        let next = 0;
+        for (const index of ones(positions)) {
+          for (; next < index; next++) builder.one(next);
+          next = index + 1;
+        }
+        for (; next < this.length; next++) builder.one(next);
+        return builder.build();
+      }
+    }
+  }
+
+  /**
+   * Number of matching rows for each symbol of `groupBy` that occurs in a matching row,
+   * as an array of `[symbol, count]` pairs in ascending symbol order.
+   * @param {Expr} expr
+   * @param {string} groupBy - the column whose symbols to count
+   * @returns {[number, number][]}
+   */
+  groupCounts(expr, groupBy) {
+    const wm = this.column(groupBy);
+    // Matching rows often occur in runs, which `countsUnion` can count together
+    /** @type {{ start: number; end: number; }[]} */
+    const runs = [];
+    for (const index of ones(this.positions(expr))) {
+      const last = runs[runs.length - 1];
+      if (last !== undefined && last.end === index) {
+        last.end++;
+      } else {
+        runs.push(Range(index, index + 1));
+      }
+    }
+    return wm.countsUnion(runs)
+      .map(x => /** @type {[number, number]} */ ([x.symbol, x.count]))
+      .sort((a, b) => a[0] - b[0]);
+  }
+
+  /**
+   * Estimated number of rows matching the expression. This is exact for leaves other than
+   * rectangles and an upper bound for `And`, `Or`, and rectangles.
+   * @param {Expr} expr
+   * @returns {number}
+   */
+  estimate(expr) {
+    switch (expr.type) {
+      case 'symbolRange': {
+        const wm = this.column(expr.column);
+        return countInSymbolRanges(wm, expr.ranges, Range(0, this.length));
+      }
+      case 'rect':
+        return this.estimate(rectAsAnd(expr));
+      case 'positionMask': {
+        const { bitVec } = expr;
+        assert(bitVec.universeSize === this.length, () => `expected the position mask to have universe size ${this.length}, got ${bitVec.universeSize}`);
+        return bitVec.numOnes;
+      }
+      case 'and':
+        return Math.min(this.length, ...expr.children.map(child => this.estimate(child)));
+      case 'or':
+        return Math.min(this.length, expr.children.reduce((sum, child) => sum + this.estimate(child), 0));
+      case 'not':
+        return this.length - this.estimate(expr.child);
+    }
+  }
+
+  /**
+   * Test whether the row at `index` matches the expression.
+   * @param {Expr} expr
+   * @param {number} index
+   * @returns {boolean}
+   */
+  matches(expr, index) {
+    switch (expr.type) {
+      case 'symbolRange': {
+        const symbol = this.column(expr.column).get(index);
+        return expr.ranges.some(r => r.start <= symbol && symbol < r.end);
+      }
+      case 'rect':
+        return this.matches(rectAsAnd(expr), index);
+      case 'positionMask':
+        return expr.bitVec.get(index) === 1;
+      case 'and':
+        return expr.children.every(child => this.matches(child, index));
+      case 'or':
+        return expr.children.some(child => this.matches(child, index));
+      case 'not':
+        return !this.matches(expr.child, index);
+    }
+  }
+
+  /**
+   * @param {string} name
+   */
+  column(name) {
+    const wm = this.columns[name];
+    assert(wm !== undefined, () => `unknown column: ${name}`);
+    return wm;
+  }
+
+  /**
+   * Returns a builder for the positions of `expr` whose representation is chosen based on
+   * the estimated number of positions.
+   * @param {Expr} expr
+   * @param {number} estimate
+   */
+  builder(expr, estimate) {
+    const dense = estimate >= DenseThreshold * this.length;
+    this.onTrace?.({ type: 'materialize', expr, estimate, representation: dense ? 'dense' : 'sorted' });
+    return dense ? new DenseBitVecBuilder(this.length) : new SortedArrayBitVecBuilder(this.length);
+  }
+
+  /**
+   * @param {Expr} expr
+   * @param {number[]} indices - sorted, distinct row indices
+   */
+  fromSorted(expr, indices) {
+    const builder = this.builder(expr, indices.length);
+    for (const index of indices) builder.one(index);
+    return builder.build();
+  }
+}
+
+/**
+ * @param {RectExpr} expr
+ */
+function rectAsAnd(expr) {
+  return And(SymbolRange(expr.xColumn, expr.xRange), SymbolRange(expr.yColumn, expr.yRange));
+}
+
+/**
+ * Yields the indices of the 1-bits of a bit vector without multiplicity, in ascending order.
+ * @param {BitVec} bv
+ */
+function* ones(bv) {
+  for (let k = 0; k < bv.numOnes; k++) {
+    yield bv.select1(k);
+  }
+}
+
+/**
+ * Number of elements in the index range whose symbol lies in one of the disjoint `symbolRanges`.
+ * @param {WaveletMatrix} wm
+ * @param {SymbolRangeBounds[]} symbolRanges
+ * @param {{ start: number; end: number; }} range
+ */
+function countInSymbolRanges(wm, symbolRanges, range) {
+  let count = 0;
+  for (const r of symbolRanges) {
+    count += wm.countLessThanOrEqual(r.end - 1, { range }) - wm.countLessThanOrEqual(r.start - 1, { range });
+  }
+  return count;
+}
+
+/**
+ * Calls `f` with the index of each element in the index range whose symbol lies in one of the
+ * disjoint `symbolRanges`, in ascending order. Ranges are split in half until they contain
+ * only matches or none, so the number of counts is proportional to the number of matches
+ * times the logarithm of the range length, and runs of matches are found quickly.
+ * @param {WaveletMatrix} wm
+ * @param {SymbolRangeBounds[]} symbolRanges
+ * @param {{ start: number; end: number; }} range
+ * @param {(index: number) => void} f
+ */
+function pushPositions(wm, symbolRanges, range, f) {
+  if (rangeIsEmpty(range)) return;
+  const count = countInSymbolRanges(wm, symbolRanges, range);
+  if (count === 0) return;
+  if (count === range.end - range.start) {
+    for (let i = range.start; i < range.end; i++) f(i);
+    return;
+  }
+  const mid = range.start + Math.floor((range.end - range.start) / 2);
+  pushPositions(wm, symbolRanges, Range(range.start, mid), f);
+  pushPositions(wm, symbolRanges, Range(mid, range.end), f);
+}
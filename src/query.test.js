@@ -0,0 +1,137 @@
+import fc from 'fast-check';
+import { describe, expect, it } from 'vitest';
+import './debug.js';
+import { DenseBitVecBuilder } from './densebitvec.js';
+import { And, Not, Or, PositionMask, QueryContext, Rect, SymbolRange } from './query.js';
+import { Range } from './range.js';
+import { WaveletMatrix } from './waveletmatrix.js';
+
+/**
+ * Evaluate the expression row by row against the raw column data.
+ * @param {import('./query.js').Expr} expr
+ * @param {Record<string, number[]>} data
+ * @param {number} i
+ * @returns {boolean}
+ */
+function bruteForceMatches(expr, data, i) {
+  /** @type {(r: { start: number; end: number; }, x: number) => boolean} */
+  const inRange = (r, x) => r.start <= x && x < r.end;
+  switch (expr.type) {
+    case 'symbolRange': return expr.ranges.some(r => inRange(r, data[expr.column][i]));
+    case 'rect': return inRange(expr.xRange, data[expr.xColumn][i]) && inRange(expr.yRange, data[expr.yColumn][i]);
+    case 'positionMask': return expr.bitVec.get(i) === 1;
+    case 'and': return expr.children.every(c => bruteForceMatches(c, data, i));
+    case 'or': return expr.children.some(c => bruteForceMatches(c, data, i));
+    case 'not': return !bruteForceMatches(expr.child, data, i);
+  }
+}
+
+/**
+ * Build a random expression of bounded depth from a stream of random integers.
+ * @param {() => number} next - returns a random non-negative integer
+ * @param {number} depth
+ * @param {number} maxSymbol
+ * @param {BitVec} mask
+ * @returns {import('./query.js').Expr}
+ */
+function randomExpr(next, depth, maxSymbol, mask) {
+  const kind = depth === 0 ? next() % 3 : next() % 6;
+  /** @type {() => { start: number; end: number; }} */
+  const symbolRange = () => {
+    const start = next() % (maxSymbol + 2);
+    return Range(start, start + next() % (maxSymbol + 2));
+  };
+  const column = () => ['a', 'b', 'c'][next() % 3];
+  switch (kind) {
+    case 0: return SymbolRange(column(), Array.from({ length: 1 + next() % 2 }, symbolRange));
+    case 1: return Rect(column(), symbolRange(), column(), symbolRange());
+    case 2: return PositionMask(mask);
+    case 3: return And(...Array.from({ length: next() % 4 }, () => randomExpr(next, depth - 1, maxSymbol, mask)));
+    case 4: return Or(...Array.from({ length: next() % 4 }, () => randomExpr(next, depth - 1, maxSymbol, mask)));
+    default: return Not(randomExpr(next, depth - 1, maxSymbol, mask));
+  }
+}
+
+describe('QueryContext', () => {
+  it('matches brute force filtering', () => {
+    fc.assert(fc.property(
+      fc.record({
+        rows: fc.array(fc.tuple(fc.nat(7), fc.nat(7), fc.nat(300)), { maxLength: 200 }),
+        maskBits: fc.array(fc.boolean(), { maxLength: 200 }),
+        seeds: fc.array(fc.nat(2 ** 31 - 1), { minLength: 1, maxLength: 500 }),
+      }),
+      ({ rows, maskBits, seeds }) => {
+        const data = { a: rows.map(r => r[0]), b: rows.map(r => r[1]), c: rows.map(r => r[2]) };
+        const ctx = new QueryContext({
+          a: new WaveletMatrix(data.a, 7),
+          b: new WaveletMatrix(data.b, 7),
+          c: new WaveletMatrix(data.c, 300),
+        }, { length: rows.length });
+        const maskBuilder = new DenseBitVecBuilder(rows.length);
+        for (let i = 0; i < rows.length; i++) if (maskBits[i]) maskBuilder.one(i);
+        const mask = maskBuilder.build();
+
+        let seedIndex = 0;
+        const next = () => seeds[seedIndex++ % seeds.length] + seedIndex;
+        for (let q = 0; q < 5; q++) {
+          const expr = randomExpr(next, 3, 8, mask);
+          const expected = rows.map((_, i) => i).filter(i => bruteForceMatches(expr, data, i));
+          const positions = ctx.positions(expr);
+          expect(positions.universeSize).toBe(rows.length);
+          expect(Array.from({ length: positions.numOnes }, (_, k) => positions.select1(k))).toEqual(expected);
+          expect(ctx.count(expr)).toBe(expected.length);
+
+          /** @type {Map<number, number>} */
+          const groups = new Map();
+          for (const i of expected) groups.set(data.b[i], (groups.get(data.b[i]) ?? 0) + 1);
+          expect(ctx.groupCounts(expr, 'b')).toEqual(Array.from(groups).sort((x, y) => x[0] - y[0]));
+        }
+      }
+    ), { numRuns: 100 });
+  });
+
+  it('evaluates the most selective operand of an And first', () => {
+    const length = 1000;
+    // `a` is rarely 1 and `b` is usually 1; swapping the columns swaps their selectivity
+    const rare = Array.from({ length }, (_, i) => (i % 100 === 0 ? 1 : 0));
+    const common = Array.from({ length }, (_, i) => (i % 10 === 0 ? 0 : 1));
+    const expr = And(SymbolRange('a', Range(1, 2)), SymbolRange('b', Range(1, 2)));
+
+    /** @param {number[]} a @param {number[]} b */
+    const trace = (a, b) => {
+      /** @type {import('./query.js').TraceEvent[]} */
+      const events = [];
+      const ctx = new QueryContext({ a: new WaveletMatrix(a), b: new WaveletMatrix(b) }, { onTrace: e => events.push(e) });
+      const count = ctx.count(expr);
+      return { count, events };
+    };
+
+    const skewedA = trace(rare, common);
+    const skewedB = trace(common, rare);
+    expect(skewedA.count).toBe(rare.filter((x, i) => x === 1 && common[i] === 1).length);
+    expect(skewedB.count).toBe(skewedA.count);
+
+    /** @param {import('./query.js').TraceEvent[]} events */
+    const order = events => events
+      .filter(e => e.type === 'materialize' || e.type === 'filter')
+      .map(e => `${e.type} ${e.expr.type === 'symbolRange' ? e.expr.column : e.expr.type}`);
+    expect(order(skewedA.events)).toEqual(['materialize a', 'filter b', 'materialize and']);
+    expect(order(skewedB.events)).toEqual(['materialize b', 'filter a', 'materialize and']);
+
+    // the rare leaf is sparse enough to be materialized as a sorted index list
+    const first = skewedA.events.find(e => e.type === 'materialize');
+    expect(first?.type === 'materialize' && first.representation).toBe('sorted');
+    /** @type {import('./query.js').TraceEvent[]} */
+    const negated = [];
+    new QueryContext({ a: new WaveletMatrix(rare) }, { onTrace: e => negated.push(e) }).positions(Not(SymbolRange('a', Range(1, 2))));
+    expect(negated.map(e => e.type === 'materialize' && e.representation)).toEqual(['sorted', 'dense']);
+  });
+
+  it('validates its inputs', () => {
+    expect(() => new QueryContext({ a: new WaveletMatrix([1, 2]), b: new WaveletMatrix([1]) })).toThrow('expected all columns to have length');
+    expect(() => new QueryContext({})).toThrow('the number of rows must be specified');
+    const ctx = new QueryContext({ a: new WaveletMatrix([1, 2]) });
+    expect(() => ctx.count(SymbolRange('z', Range(0, 1)))).toThrow('unknown column: z');
+    expect(() => ctx.count(PositionMask(new DenseBitVecBuilder(3).build()))).toThrow('expected the position mask to have universe size 2');
+  });
+});
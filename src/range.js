@@ -0,0 +1,59 @@
+import { u32 } from './bits.js';
+
+// Helpers for half-open ranges `{ start, end }` of indices or symbols.
+// A range is empty if `start >= end`. Since these are plain numbers rather than 32-bit integers,
+// range ends of 2^32 (one past the largest 32-bit symbol) are representable without overflow,
+// and there is no need for inclusive ranges.
+
+/**
+ * @param {number} start
+ * @param {number} end
+ */
+export function Range(start, end) {
+  return { start, end };
+}
+
+/**
+ * @param {{ start: number; end: number; }} range
+ */
+export function rangeIsEmpty(range) {
+  return !(range.start < range.end);
+}
+
+/**
+ * Return true if ranges `a` and `b` have at least one element in common.
+ * An empty range does not overlap any range, including itself.
+ * @param {{ start: number; end: number; }} a
+ * @param {{ start: number; end: number; }} b
+ */
+export function rangesOverlap(a, b) {
+  return !rangeIsEmpty(a) && !rangeIsEmpty(b) && a.start < b.end && b.start < a.end;
+}
+
+/**
+ * Return true if every element of range `b` is also in range `a`.
+ * An empty `b` is contained in every range, including an empty one.
+ * @param {{ start: number; end: number; }} a
+ * @param {{ start: number; end: number; }} b
+ */
+export function rangeFullyContains(a, b) {
+  return rangeIsEmpty(b) || (a.start <= b.start && a.end >= b.end);
+}
+
+/**
+ * Return the range of values obtained by masking each value in `[start, end)` with `mask`,
+ * assuming that the values are 32-bit unsigned integers (so `end` may be at most 2^32).
+ * Masking is monotonic, so the masked range runs from the masked start to one past the masked
+ * last element. An empty input range results in an empty range at the masked start.
+ * @param {number} start
+ * @param {number} end
+ * @param {number} mask
+ */
+export function MaskedRange(start, end, mask) {
+  const maskedStart = u32(start & mask);
+  if (!(start < end)) {
+    return Range(maskedStart, maskedStart);
+  }
+  // The end is exclusive so we need to make sure to mask the true endpoint.
+  return Range(maskedStart, u32((end - 1) & mask) + 1);
+}
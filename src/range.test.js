@@ -0,0 +1,76 @@
+import { describe, expect, test } from 'vitest';
+import './debug.js';
+import { MaskedRange, Range, rangeFullyContains, rangeIsEmpty, rangesOverlap } from './range.js';
+
+describe('ranges', () => {
+  const max = 2 ** 32;
+  // empty and non-empty ranges, including ones at 0 and at the 32-bit boundary
+  const ranges = [
+    Range(0, 0), Range(5, 5), Range(max, max), Range(7, 3),
+    Range(0, 1), Range(0, 10), Range(3, 7), Range(5, 6), Range(9, 12),
+    Range(max - 1, max), Range(2 ** 31, max), Range(0, max)
+  ];
+  /** @param {{ start: number, end: number }} r */
+  const elements = r => {
+    // represent large ranges by their endpoints and a few interior points
+    const xs = [r.start, r.start + 1, 2 ** 31 - 1, 2 ** 31, r.end - 2, r.end - 1];
+    return xs.filter(x => r.start <= x && x < r.end);
+  };
+  /** 
+   * @param {{ start: number, end: number }} r 
+   * @param {number} x
+   */
+  const has = (r, x) => r.start <= x && x < r.end;
+
+  test('rangeIsEmpty', () => {
+    for (const r of ranges) {
+      expect(rangeIsEmpty(r)).toBe(elements(r).length === 0);
+    }
+  });
+
+  test('rangesOverlap', () => {
+    for (const a of ranges) {
+      for (const b of ranges) {
+        const expected = elements(a).some(x => has(b, x)) || elements(b).some(x => has(a, x));
+        expect(rangesOverlap(a, b)).toBe(expected);
+        expect(rangesOverlap(b, a)).toBe(expected);
+      }
+    }
+    // an empty range does not overlap even a range that surrounds it
+    expect(rangesOverlap(Range(5, 5), Range(0, 10))).toBe(false);
+  });
+
+  test('rangeFullyContains', () => {
+    for (const a of ranges) {
+      for (const b of ranges) {
+        const expected = rangeIsEmpty(b) || (has(a, b.start) && has(a, b.end - 1));
+        expect(rangeFullyContains(a, b)).toBe(expected);
+      }
+    }
+    // an empty range is contained in every range, including a disjoint or empty one
+    expect(rangeFullyContains(Range(0, 3), Range(5, 5))).toBe(true);
+    expect(rangeFullyContains(Range(0, 0), Range(7, 3))).toBe(true);
+    expect(rangeFullyContains(Range(5, 5), Range(5, 6))).toBe(false);
+  });
+
+  test('MaskedRange', () => {
+    const masks = [0xffffffff, 0x0000ffff, 0xffff0000, 0x55555555, 0];
+    for (const mask of masks) {
+      for (const { start, end } of ranges) {
+        const masked = MaskedRange(start, end, mask);
+        if (rangeIsEmpty(Range(start, end))) {
+          expect(rangeIsEmpty(masked)).toBe(true);
+        } else {
+          // masking is monotonic, so the masked range spans the masked endpoints
+          expect(masked.start).toBe((start & mask) >>> 0);
+          expect(masked.end).toBe((((end - 1) & mask) >>> 0) + 1);
+          expect(masked.start).toBeGreaterThanOrEqual(0);
+          expect(masked.start).toBeLessThan(masked.end);
+        }
+      }
+    }
+    // starts at or above 2^31 stay non-negative
+    expect(MaskedRange(2 ** 31, max, 0xffffffff)).toEqual(Range(2 ** 31, max));
+    expect(MaskedRange(0, max, 0xffffffff)).toEqual(Range(0, max));
+  });
+});
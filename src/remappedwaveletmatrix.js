@@ -0,0 +1,116 @@
+import { assert } from './assert.js';
+import * as bits from './bits.js';
+import { WaveletMatrix } from './waveletmatrix.js';
+
+/**
+ * Wavelet matrix over a sparse alphabet, such as timestamps or database keys, whose queries
+ * accept and return the original symbols. The underlying wavelet matrix stores the rank of each
+ * symbol among the distinct symbols, so its number of levels depends on the number of distinct
+ * symbols rather than on the largest one. The table of distinct symbols takes 4 bytes per entry.
+ */
+export class RemappedWaveletMatrix {
+  /**
+   * @param {WaveletMatrix} wm - wavelet matrix whose symbols are indices into `symbols`
+   * @param {Uint32Array} symbols - the distinct symbols in ascending order
+   */
+  constructor(wm, symbols) {
+    assert(wm.length === 0 || wm.maxSymbol < symbols.length, 'the wavelet matrix contains symbols outside of the symbol table');
+    DEBUG && assert(symbols.every((s, i) => i === 0 || symbols[i - 1] < s), 'expected the symbol table to be strictly increasing');
+    /** @readonly */
+    this.wm = wm;
+    /** @readonly */
+    this.symbols = symbols;
+    /** @readonly */
+    this.length = wm.length;
+  }
+
+  /**
+   * Build the table of distinct symbols and construct a wavelet matrix over their ranks.
+   * @param {ArrayLike<number>} data - symbols in [0, 2^32)
+   */
+  static fromSymbols(data) {
+    // Reject invalid symbols, which would otherwise be silently wrapped around or truncated
+    // when they are stored in the symbol table.
+    for (let i = 0; i < data.length; i++) {
+      const symbol = data[i];
+      assert(Number.isInteger(symbol) && symbol >= 0 && symbol < 2 ** 32, () => `expected symbols in [0, 2^32), got ${symbol} at index ${i}`);
+    }
+    const symbols = Uint32Array.from(new Set(Array.from(data))).sort();
+    const remapped = new Uint32Array(data.length);
+    for (let i = 0; i < data.length; i++) {
+      remapped[i] = bits.partitionPoint(symbols.length, j => symbols[j] < data[i]);
+    }
+    return new RemappedWaveletMatrix(new WaveletMatrix(remapped, Math.max(0, symbols.length - 1)), symbols);
+  }
+
+  /**
+   * Number of symbols in the table that are less than `symbol`, which is the rank of `symbol`
+   * in the underlying wavelet matrix if it occurs in the data.
+   * @param {number} symbol
+   */
+  rankOf(symbol) {
+    return bits.partitionPoint(this.symbols.length, i => this.symbols[i] < symbol);
+  }
+
+  /**
+   * @param {number} index
+   */
+  get(index) {
+    return this.symbols[this.wm.get(index)];
+  }
+
+  /**
+   * Number of times the symbol appears in the index range, which is zero for symbols
+   * that do not appear in the data.
+   * @param {number} symbol
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  count(symbol, { range = { start: 0, end: this.length } } = {}) {
+    const rank = this.rankOf(symbol);
+    if (this.symbols[rank] !== symbol) {
+      return 0;
+    }
+    return this.wm.count(rank, { range });
+  }
+
+  /**
+   * Number of symbols less than or equal to `symbol` in the index range.
+   * @param {number} symbol
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  countLessThanOrEqual(symbol, { range = { start: 0, end: this.length } } = {}) {
+    // The ranks of the symbols less than or equal to `symbol` are those below the rank of `symbol + 1`
+    return this.wm.countLessThanOrEqual(this.rankOf(symbol + 1) - 1, { range });
+  }
+
+  /**
+   * Returns the `k`-th smallest symbol in the index range, along with its count in the range.
+   * @param {number} k
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  quantile(k, { range = { start: 0, end: this.length } } = {}) {
+    const { symbol, count } = this.wm.quantile(k, { range });
+    return { symbol: this.symbols[symbol], count };
+  }
+
+  /**
+   * Returns a `{ symbol, start, end }` object for each symbol in `symbolRange` that occurs in the
+   * index range, as with `WaveletMatrix.counts`, with the symbols mapped back to the original ones.
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   * @param {{ start: number; end: number; }} [options.symbolRange]
+   * @param {boolean} [options.sortBySymbol]
+   */
+  counts({ range = { start: 0, end: this.length }, symbolRange = { start: 0, end: 2 ** 32 }, sortBySymbol = false } = {}) {
+    const start = this.rankOf(symbolRange.start);
+    const end = Math.max(start, this.rankOf(symbolRange.end));
+    return this.wm.counts({ range, symbolRange: { start, end }, sortBySymbol }).map(x => ({
+      symbol: this.symbols[x.symbol],
+      start: x.start,
+      end: x.end
+    }));
+  }
+}
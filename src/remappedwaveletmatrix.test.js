@@ -0,0 +1,63 @@
+import { describe, expect, it } from 'vitest';
+import './debug.js';
+import { RemappedWaveletMatrix } from './remappedwaveletmatrix.js';
+
+describe('RemappedWaveletMatrix', () => {
+  // a few distinct timestamp-like symbols spread across the 32-bit range
+  const distinct = [17, 1_000_000, 1_700_000_000, 1_700_000_123, 4_000_000_000];
+  const data = Array.from({ length: 100 }, (_, i) => distinct[(i * 7 + (i >> 3)) % distinct.length]);
+  const rwm = RemappedWaveletMatrix.fromSymbols(data);
+  const sorted = data.slice().sort((a, b) => a - b);
+
+  it('stores compact ranks', () => {
+    expect(Array.from(rwm.symbols)).toEqual(distinct);
+    expect(rwm.wm.numLevels).toBe(Math.ceil(Math.log2(distinct.length)));
+  });
+
+  it('get and quantile return the original symbols', () => {
+    for (let i = 0; i < data.length; i++) {
+      expect(rwm.get(i)).toBe(data[i]);
+      const { symbol, count } = rwm.quantile(i);
+      expect(symbol).toBe(sorted[i]);
+      expect(count).toBe(data.filter(d => d === symbol).length);
+    }
+  });
+
+  it('count and countLessThanOrEqual', () => {
+    const range = { start: 10, end: 70 };
+    const slice = data.slice(range.start, range.end);
+    for (const symbol of [0, 17, 18, 1_700_000_000, 1_700_000_001, 4_000_000_000, 2 ** 32 - 1]) {
+      expect(rwm.count(symbol, { range })).toBe(slice.filter(d => d === symbol).length);
+      expect(rwm.countLessThanOrEqual(symbol, { range })).toBe(slice.filter(d => d <= symbol).length);
+    }
+  });
+
+  it('counts', () => {
+    const range = { start: 5, end: 55 };
+    const slice = data.slice(range.start, range.end);
+    for (const symbolRange of [undefined, { start: 18, end: 1_700_000_124 }, { start: 1_700_000_000, end: 1_700_000_000 }, { start: 5, end: 17 }]) {
+      const counts = rwm.counts({ range, symbolRange, sortBySymbol: true });
+      const { start, end } = symbolRange ?? { start: 0, end: 2 ** 32 };
+      const expected = distinct.filter(s => start <= s && s < end && slice.includes(s));
+      expect(counts.map(x => x.symbol)).toEqual(expected);
+      for (const x of counts) {
+        expect(x.end - x.start).toBe(slice.filter(d => d === x.symbol).length);
+      }
+    }
+  });
+
+  it('handles empty data', () => {
+    const empty = RemappedWaveletMatrix.fromSymbols([]);
+    expect(empty.length).toBe(0);
+    expect(empty.count(5)).toBe(0);
+    expect(empty.counts()).toEqual([]);
+  });
+
+  it('rejects invalid symbols', () => {
+    expect(() => RemappedWaveletMatrix.fromSymbols([3, 2 ** 32, 5])).toThrow('expected symbols in [0, 2^32), got 4294967296 at index 1');
+    expect(() => RemappedWaveletMatrix.fromSymbols([3, 5, -1])).toThrow('got -1 at index 2');
+    expect(() => RemappedWaveletMatrix.fromSymbols([1.5])).toThrow('got 1.5 at index 0');
+    expect(() => RemappedWaveletMatrix.fromSymbols([NaN])).toThrow('got NaN at index 0');
+    expect(RemappedWaveletMatrix.fromSymbols([0, 2 ** 32 - 1]).get(1)).toBe(2 ** 32 - 1);
+  });
+});
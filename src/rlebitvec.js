@@ -1,7 +1,8 @@
-import { assert, assertNonNegative, assertSafeInteger } from './assert.js';
+import { assert, assertBitIndex, assertNonNegative, assertSafeInteger, assertStrictlyIncreasing, assertUniverseSize } from './assert.js';
 import * as defaults from './defaults';
 import { trySelect0 } from './defaults.js';
-import { bits } from './index.js';
+import * as bits from './bits.js';
+import { sampleSortedDistinct } from './sample.js';
 import { ascending } from './sort.js';
 import { SparseBitVec } from './sparsebitvec.js';
 
@@ -14,22 +15,27 @@ export class RLEBitVecBuilder {
    * @param {number} universeSize
    */
   constructor(universeSize) {
+    assertUniverseSize(universeSize);
     this.universeSize = universeSize;
     /** @type {number[]} */
     this.ones = [];
+    this.isBuilt = false;
   }
 
   /**
    * @param {number} index
    */
   one(index, count = 1) {
-    assert(index < this.universeSize, () => `index (${index}) cannot exceed universeSize (${this.universeSize})`);
+    assert(!this.isBuilt, 'cannot modify a builder after it has been built');
+    assertBitIndex(index, this.universeSize);
     for (let i = 0; i < count; i++) {
       this.ones.push(index);
     }
   }
   
   build(options = {}) {
+    assert(!this.isBuilt, 'a builder can only be built once');
+    this.isBuilt = true;
     this.ones.sort(ascending);
     const builder = new RLERunBuilder();
 
@@ -44,11 +50,45 @@ export class RLEBitVecBuilder {
     }
 
     // pad out with zeros if needed
-    const numZeros = this.universeSize - Math.max(0, prev);
+    const numZeros = this.universeSize - prev - 1;
     builder.run(numZeros, 0);
 
     return builder.build(options);
   }
+
+  /**
+   * Builds runs directly from the input, skipping the sort performed by `build`
+   * and coalescing each group of consecutive 1-bits into a single run.
+   * @param {number} universeSize
+   * @param {ArrayLike<number>} sortedOnes
+   */
+  static fromSortedOnes(universeSize, sortedOnes, options = {}) {
+    DEBUG && assertStrictlyIncreasing(sortedOnes);
+    const builder = new RLERunBuilder();
+
+    let prev = -1; // index of the last 1-bit of the previous run
+    let i = 0;
+    while (i < sortedOnes.length) {
+      const start = sortedOnes[i];
+      assertNonNegative(start);
+      assertSafeInteger(start);
+      assert(start < universeSize, () => `index (${start}) cannot exceed universeSize (${universeSize})`);
+      // extend the run for as long as the 1-bits are consecutive
+      let end = start + 1;
+      i++;
+      while (i < sortedOnes.length && sortedOnes[i] === end) {
+        end++;
+        i++;
+      }
+      builder.run(start - prev - 1, end - start);
+      prev = end - 1;
+    }
+
+    // pad out with zeros if needed
+    builder.run(universeSize - prev - 1, 0);
+
+    return builder.build(options);
+  }
 }
 
 /**
@@ -64,6 +104,7 @@ export class RLERunBuilder {
 
     this.numZeros = 0;
     this.numOnes = 0;
+    this.isBuilt = false;
   }
 
   /**
@@ -71,6 +112,7 @@ export class RLERunBuilder {
    * @param {number} numOnes
    */
   run(numZeros, numOnes) {
+    assert(!this.isBuilt, 'cannot modify a builder after it has been built');
     if (numZeros === 0 && numOnes === 0) { 
       return;
     }
@@ -96,6 +138,8 @@ export class RLERunBuilder {
   }
 
   build(options = {}) {
+    assert(!this.isBuilt, 'a builder can only be built once');
+    this.isBuilt = true;
     // We expect no options.
     DEBUG && assert(Object.keys(options).length === 0);
     
@@ -251,6 +295,53 @@ export class RLEBitVec {
     return defaults.rank0(this, index);
   }
 
+  /**
+   * Fast rank0 for an index that is aligned to a run boundary, ie. the start of
+   * a 01-run (a run of zeros followed by a run of ones), or the universe size.
+   * The result is not meaningful if `index` is not aligned; see `tryAlignedRank0`.
+   * @param {number} index
+   */
+  alignedRank0(index) {
+    // Number of complete 01-runs up to and including `index`
+    const j = this.zo.rank1(index + 1);
+    return this.z.trySelect1(j - 1) ?? 0;
+  }
+
+  /**
+   * Fast rank1 for an index that is aligned to a run boundary.
+   * The result is not meaningful if `index` is not aligned; see `tryAlignedRank1`.
+   * @param {number} index
+   */
+  alignedRank1(index) {
+    return index - this.alignedRank0(index);
+  }
+
+  /**
+   * Returns true if `index` is aligned to a run boundary, which is the case for the
+   * start index of each 01-run, as well as for the universe size.
+   * @param {number} index
+   */
+  isAligned(index) {
+    if (!Number.isInteger(index) || index < 0 || index > this.universeSize) return false;
+    return index === 0 || this.zo.get(index) > 0;
+  }
+
+  /**
+   * Checked version of `alignedRank0` that returns `null` if `index` is not aligned to a run boundary.
+   * @param {number} index
+   */
+  tryAlignedRank0(index) {
+    return this.isAligned(index) ? this.alignedRank0(index) : null;
+  }
+
+  /**
+   * Checked version of `alignedRank1` that returns `null` if `index` is not aligned to a run boundary.
+   * @param {number} index
+   */
+  tryAlignedRank1(index) {
+    return this.isAligned(index) ? this.alignedRank1(index) : null;
+  }
+
   /**
    * @param {number} n
    */
@@ -265,6 +356,40 @@ export class RLEBitVec {
     return defaults.select1(this, n);
   }
 
+  /**
+   * Returns the indices of the 1-bits with the given ordinals, which must be in ascending order
+   * but need not be distinct. Rather than binary searching the runs for each ordinal as `select1`
+   * does, this advances through the runs as the ordinals increase, and only falls back to a binary
+   * search over the remaining runs when an ordinal lies beyond the next run. Sequential ordinals
+   * therefore take constant time each.
+   * @param {ArrayLike<number>} ns
+   * @returns {number[]}
+   */
+  select1Batch(ns) {
+    const numRuns = this.z.numOnes;
+    // Number of ones up to and including the j-th 01-run
+    const onesThrough = (/** @type {number} */ j) => this.zo.select1(j) - this.z.select1(j);
+    /** @type {number[]} */
+    const result = [];
+    let j = 0;
+    for (let i = 0; i < ns.length; i++) {
+      const n = ns[i];
+      if (!(Number.isInteger(n) && n >= 0 && n < this.numOnes)) {
+        throw new Error(`n (${n}) is not a valid 1-bit index`);
+      }
+      assert(i === 0 || ns[i - 1] <= n, () => `expected ascending ordinals, got ${ns[i - 1]} followed by ${n}`);
+      if (onesThrough(j) <= n) {
+        j++;
+        if (onesThrough(j) <= n) {
+          j += bits.partitionPoint(numRuns - j, k => onesThrough(j + k) <= n);
+        }
+      }
+      // Number of zeros up to and including the j-th 01-run
+      result.push(this.z.select1(j) + n);
+    }
+    return result;
+  }
+
   /**
    * @param {number} index
    */
@@ -272,4 +397,72 @@ export class RLEBitVec {
     return defaults.get(this, index);
   }
 
+  /**
+   * @param {number} index
+   */
+  rank1Checked(index) {
+    return defaults.rank1Checked(this, index);
+  }
+
+  /**
+   * @param {number} first
+   * @param {number} last
+   */
+  countOnesInInclusive(first, last) {
+    return defaults.countOnesInInclusive(this, first, last);
+  }
+
+  /**
+   * Like the default `sampleOnes`, but selects the sorted ordinals with `select1Batch`.
+   * @param {number} k
+   * @param {number} seed
+   */
+  sampleOnes(k, seed) {
+    return this.select1Batch(sampleSortedDistinct(this.numOnes, k, seed));
+  }
+
+  /**
+   * Reads the 0-runs directly off the 01-runs.
+   * @param {{ start: number, end: number }} range
+   * @returns {Generator<{ start: number, end: number }>}
+   */
+  *zeroRuns(range) {
+    const end = Math.min(range.end, this.universeSize);
+    const start = Math.max(range.start, 0);
+    if (start >= end) {
+      return;
+    }
+    // Index of the 01-run containing `start`
+    let j = this.zo.rank1(start + 1);
+    // The pending run, which is yielded once we know it is not continued by the next 01-run
+    // (this happens when a 01-run has no ones)
+    let run = null;
+    for (; j < this.z.numOnes; j++) {
+      const blockStart = this.zo.trySelect1(j - 1) ?? 0;
+      if (blockStart >= end) {
+        break;
+      }
+      const numZeros = this.z.select1(j) - (this.z.trySelect1(j - 1) ?? 0);
+      const zerosStart = Math.max(blockStart, start);
+      const zerosEnd = Math.min(blockStart + numZeros, end);
+      if (zerosStart < zerosEnd) {
+        if (run !== null && run.end === zerosStart) {
+          run.end = zerosEnd;
+        } else {
+          if (run !== null) yield run;
+          run = { start: zerosStart, end: zerosEnd };
+        }
+      }
+    }
+    if (run !== null) yield run;
+  }
+
+  /**
+   * @param {{ start: number, end: number }} range
+   * @param {number} minLength
+   */
+  firstZeroRunAtLeast(range, minLength) {
+    return defaults.firstZeroRunAtLeast(this, range, minLength);
+  }
+
 }
\ No newline at end of file
@@ -5,6 +5,22 @@ import { testBitVecType } from './testutils.js';
 testBitVecType(RLEBitVecBuilder);
 
 describe('RLEBitVec', () => {
+  test('select1Batch', () => {
+    const builder = new RLERunBuilder();
+    for (const [numZeros, numOnes] of [[0, 3], [5, 1], [2, 0], [0, 4], [7, 2], [1, 10], [3, 0]]) {
+      builder.run(numZeros, numOnes);
+    }
+    const bv = builder.build();
+    const all = Array.from({ length: bv.numOnes }, (_, n) => n);
+    for (const ns of [[], all, [0, 0, 0], [2, 3, 3, 7, 15, 19], [bv.numOnes - 1], all.filter(n => n % 3 === 0)]) {
+      expect(bv.select1Batch(ns)).toEqual(ns.map(n => bv.select1(n)));
+    }
+    expect(() => bv.select1Batch([0, bv.numOnes])).toThrow(`n (${bv.numOnes}) is not a valid 1-bit index`);
+    expect(() => bv.select1Batch([-1])).toThrow('n (-1) is not a valid 1-bit index');
+    expect(() => bv.select1Batch([5, 4])).toThrow('expected ascending ordinals, got 5 followed by 4');
+    expect(new RLEBitVecBuilder(10).build().select1Batch([])).toEqual([]);
+  });
+
   test('can handle large runs', () => {
 
     // Test scenario:
@@ -31,4 +47,41 @@ describe('RLEBitVec', () => {
     expect(bv.select0(1e9)).toBe(2e9);
     expect(bv.select1(2e9)).toBe(4e9);
   });
+
+  test('aligned rank', () => {
+    // runs of (zeros, ones): 00111 0 1 000011 00
+    const runs = [[2, 3], [1, 1], [4, 2], [2, 0]];
+    const builder = new RLERunBuilder();
+    /** @type {Set<number>} */
+    const aligned = new Set([0]);
+    let index = 0;
+    for (const [numZeros, numOnes] of runs) {
+      builder.run(numZeros, numOnes);
+      index += numZeros + numOnes;
+      aligned.add(index);
+    }
+    const bv = builder.build();
+    expect(bv.universeSize).toBe(index);
+
+    for (let i = -1; i <= bv.universeSize + 1; i++) {
+      expect(bv.isAligned(i)).toBe(aligned.has(i));
+      if (aligned.has(i)) {
+        expect(bv.alignedRank0(i)).toBe(bv.rank0(i));
+        expect(bv.alignedRank1(i)).toBe(bv.rank1(i));
+        expect(bv.tryAlignedRank0(i)).toBe(bv.rank0(i));
+        expect(bv.tryAlignedRank1(i)).toBe(bv.rank1(i));
+      } else {
+        expect(bv.tryAlignedRank0(i)).toBeNull();
+        expect(bv.tryAlignedRank1(i)).toBeNull();
+      }
+    }
+  });
+
+  test('run builder cannot be reused after it is built', () => {
+    const builder = new RLERunBuilder();
+    builder.run(2, 3);
+    builder.build();
+    expect(() => builder.run(1, 1)).toThrow('after it has been built');
+    expect(() => builder.build()).toThrow('only be built once');
+  });
 });
\ No newline at end of file
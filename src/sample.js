@@ -0,0 +1,78 @@
+import { assert, assertSafeInteger } from './assert.js';
+
+// Random sampling without replacement, used to pick random 1-bits from bit vectors and
+// random matching elements from wavelet matrices. The generator is seeded so that samples
+// are reproducible, eg. across renders of the same selection.
+
+/**
+ * Returns an unsorted sample of k elements without replacement, sampled
+ * from the universe [0, `n`).
+ * This algorithm is best when the sample is sparse, ie. `k` is much smaller than `n`.
+ * If `k` and `n` are of comparable size, then you may as well just do
+ * an in-place Fischer-Yates shuffle of the full range as an array:
+ *   https://en.wikipedia.org/wiki/Fisher%E2%80%93Yates_shuffle
+ * 
+ * The idea comes from the paper
+ *
+ *   - Title: Simple, Optimal Algorithms for Random Sampling Without Replacement
+ *   - Link: https://arxiv.org/abs/2104.05091
+ *   - Author Daniel Ting: https://stat.uw.edu/about-us/people/daniel-ting
+ *
+ * Note: If we were in a typed language, instead of storing the `index` in `m`, we could
+ * store `n - index` (or `n - index - 1` or something) to make the numbers smaller, potentially
+ * being able to use a smaller integer type for the keys and values of `m` (in a typed language).
+ * 
+ * @param {number} k - number of elements to sample
+ * @param {number} n - universe size
+ * @param { () => number } rng - random number generator function
+ */
+export function sparseFisherYatesSample(k, n, rng) {
+  assertSafeInteger(k);
+  assertSafeInteger(n);
+  if (n < 0) throw new Error("n must be nonnegative");
+  if (k > n) throw new Error("k must not exceed n");
+  if (n === 0) return new Uint32Array();
+  const m = new Map();
+  const xs = new Uint32Array(k);
+  for (let i = 0; i < k; i++) {
+    // iterate through the conceptual array of n elements backwards
+    const index = n - i - 1;
+    // random number between 0 and index
+    const x = Math.floor((index + 1) * rng());
+    xs[i] = m.get(x) ?? x;
+    m.set(x, m.get(index) ?? index);
+    if (x === index) m.delete(index);
+  }
+  return xs;
+}
+
+/**
+ * Returns a pseudorandom number generator producing numbers in [0, 1) with 32 bits of
+ * randomness each, fully determined by the 32-bit integer `seed`. This is the "mulberry32"
+ * generator, which is small and fast and passes the usual statistical test batteries,
+ * though it is not suitable for cryptographic use.
+ * @param {number} seed
+ */
+export function seededRandom(seed) {
+  let state = seed >>> 0;
+  return () => {
+    state = (state + 0x6d2b79f5) >>> 0;
+    let t = state;
+    t = Math.imul(t ^ (t >>> 15), t | 1);
+    t ^= t + Math.imul(t ^ (t >>> 7), t | 61);
+    return ((t ^ (t >>> 14)) >>> 0) / 2 ** 32;
+  };
+}
+
+/**
+ * Returns `min(k, n)` distinct integers sampled uniformly without replacement from [0, `n`),
+ * in ascending order, determined by `seed`. See `seededRandom`.
+ * @param {number} n
+ * @param {number} k
+ * @param {number} seed
+ */
+export function sampleSortedDistinct(n, k, seed) {
+  assertSafeInteger(k);
+  assert(k >= 0, () => `expected a non-negative sample size, got ${k}`);
+  return sparseFisherYatesSample(Math.min(k, n), n, seededRandom(seed)).sort();
+}
@@ -1,4 +1,4 @@
-import { assert, assertDefined, assertNonNegative, assertSafeInteger, log } from "./assert.js";
+import { assert, assertBitIndex, assertDefined, assertNonNegative, assertSafeInteger, assertStrictlyIncreasing, assertUniverseSize, log } from "./assert.js";
 import { partitionPoint } from './bits';
 import * as defaults from './defaults';
 import { ascending } from './sort.js';
@@ -12,25 +12,40 @@ export class SortedArrayBitVecBuilder {
    * @param {number} universeSize
    */
   constructor(universeSize) {
+    assertUniverseSize(universeSize);
     this.universeSize = universeSize;
     /** @type {number[]} */
     this.ones = [];
+    this.isBuilt = false;
   }
 
   /**
    * @param {number} index
    */
   one(index, count = 1) {
-    assert(index < this.universeSize, () => `index (${index}) cannot exceed universeSize (${this.universeSize})`);
+    assert(!this.isBuilt, 'cannot modify a builder after it has been built');
+    assertBitIndex(index, this.universeSize);
     for (let i = 0; i < count; i++) {
       this.ones.push(index);
     }
   }
   
   build(options = {}) {
+    assert(!this.isBuilt, 'a builder can only be built once');
+    this.isBuilt = true;
     this.ones.sort(ascending);
     return new SortedArrayBitVec(this.ones, this.universeSize);
   }
+
+  /**
+   * Skips the sort performed by `build`, since the input is already sorted.
+   * @param {number} universeSize
+   * @param {ArrayLike<number>} sortedOnes
+   */
+  static fromSortedOnes(universeSize, sortedOnes, options = {}) {
+    DEBUG && assertStrictlyIncreasing(sortedOnes);
+    return new SortedArrayBitVec(Array.from(sortedOnes), universeSize);
+  }
 }
 
 // select: select the k-th occurrence of a 0/1 bit.
@@ -62,6 +77,7 @@ export class SortedArrayBitVec {
    * @param {number} universeSize
    */
   constructor(ones, universeSize) {
+    assertUniverseSize(universeSize);
     let numUniqueOnes = 0;
     let hasMultiplicity = false;
     let prev = -1;
@@ -153,4 +169,42 @@ export class SortedArrayBitVec {
   get(index) {
     return defaults.get(this, index); 
   }
+
+  /**
+   * @param {number} index
+   */
+  rank1Checked(index) {
+    return defaults.rank1Checked(this, index);
+  }
+
+  /**
+   * @param {number} first
+   * @param {number} last
+   */
+  countOnesInInclusive(first, last) {
+    return defaults.countOnesInInclusive(this, first, last);
+  }
+
+  /**
+   * @param {number} k
+   * @param {number} seed
+   */
+  sampleOnes(k, seed) {
+    return defaults.sampleOnes(this, k, seed);
+  }
+
+  /**
+   * @param {{ start: number, end: number }} range
+   */
+  zeroRuns(range) {
+    return defaults.zeroRuns(this, range);
+  }
+
+  /**
+   * @param {{ start: number, end: number }} range
+   * @param {number} minLength
+   */
+  firstZeroRunAtLeast(range, minLength) {
+    return defaults.firstZeroRunAtLeast(this, range, minLength);
+  }
 }
\ No newline at end of file
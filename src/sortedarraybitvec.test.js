@@ -1,5 +1,13 @@
+import { describe, expect, test } from 'vitest';
 import { SortedArrayBitVec, SortedArrayBitVecBuilder } from './sortedarraybitvec.js';
 import { testBitVecType, testMultiBitVecType } from './testutils.js';
 
 testBitVecType(SortedArrayBitVecBuilder);
 testMultiBitVecType(SortedArrayBitVecBuilder);
+
+describe('SortedArrayBitVec', () => {
+  test('rejects swapped constructor arguments', () => {
+    // @ts-ignore because the arguments are deliberately of the wrong type
+    expect(() => new SortedArrayBitVec(10, [1, 2])).toThrow('expected universeSize to be an integer');
+  });
+});
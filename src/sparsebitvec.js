@@ -1,8 +1,8 @@
-import { assert, assertNonNegative, assertSafeInteger } from "./assert.js";
+import { assert, assertBitIndex, assertStrictlyIncreasing, assertUniverseSize } from "./assert.js";
 import { BitBuf } from './bitbuf.js';
 import * as bits from './bits.js';
 import * as defaults from './defaults';
-import { DenseBitVec, DenseBitVecBuilder } from './densebitvec.js';
+import { DenseBitVec } from './densebitvec.js';
 import { IntBuf } from './intbuf.js';
 import { ascending } from './sort.js';
 
@@ -25,39 +25,74 @@ export class SparseBitVecBuilder {
    * @param {number} universeSize
    */
   constructor(universeSize) {
+    assertUniverseSize(universeSize);
     this.universeSize = universeSize;
     /** @type {number[]} */
     this.ones = [];
+    this.isBuilt = false;
   }
 
   /**
    * @param {number} index
    */
   one(index, count = 1) {
-    assert(index < this.universeSize, () => `index (${index}) cannot exceed universeSize (${this.universeSize})`);
+    assert(!this.isBuilt, 'cannot modify a builder after it has been built');
+    assertBitIndex(index, this.universeSize);
     for (let i = 0; i < count; i++) {
       this.ones.push(index);
     }
   }
   
+  /**
+   * @param {SparseBuildOptions} [options]
+   */
   build(options = {}) {
+    assert(!this.isBuilt, 'a builder can only be built once');
+    this.isBuilt = true;
     this.ones.sort(ascending);
-    return new SparseBitVec(this.ones, this.universeSize);
+    return new SparseBitVec(this.ones, this.universeSize, options);
+  }
+
+  /**
+   * Skips the sort performed by `build`, since the input is already sorted.
+   * @param {number} universeSize
+   * @param {number[] | Uint32Array | Float64Array} sortedOnes
+   * @param {SparseBuildOptions} [options]
+   */
+  static fromSortedOnes(universeSize, sortedOnes, options = {}) {
+    DEBUG && assertStrictlyIncreasing(sortedOnes);
+    return new SparseBitVec(sortedOnes, universeSize, options);
   }
 }
 
+/**
+ * Options for the Elias-Fano encoding of a `SparseBitVec`: `lowBitWidth` overrides the
+ * number of low bits stored per 1-bit, which is otherwise chosen to minimize the size,
+ * and the sample rates apply to the dense bit vector that stores the high bits.
+ * @typedef {{
+ *   lowBitWidth?: number,
+ *   rank1SamplesPow2?: number,
+ *   selectSamplesPow2?: number,
+ * }} SparseBuildOptions
+ */
+
 /**
  * Sparse bitvector using Elias-Fano encoding. Supports multiplicity.
  * @implements {BitVec}
  */
 export class SparseBitVec {
   /**
+   * Construct a sparse bit vector from 1-bit positions in nondecreasing order.
+   * The whole input is validated before anything is encoded, and invalid input
+   * results in an error that identifies the first offending element.
+   * Use `SparseBitVec.fromUnsorted` if the input is not known to be sorted.
    * @param {number[] | Uint32Array | Float64Array} ones
    * @param {number} universeSize
+   * @param {SparseBuildOptions} [options]
    */
-  constructor(ones, universeSize) {
+  constructor(ones, universeSize, { lowBitWidth: lowBitWidthOverride, rank1SamplesPow2 = 10, selectSamplesPow2 = 10 } = {}) {
     // disallow humungous universes because JS only supports efficient bit ops for 32-bit integers
-    assert(universeSize < 2 ** 32, () => `universeSize (${universeSize}) cannot exceed 2^32 - 1`);
+    assertUniverseSize(universeSize);
 
     // The paper "On Elias-Fano for Rank Queries in FM-Indexes" recommends a formula to compute
     // the number of low bits that is mostly equivalent to the version used below, except that
@@ -67,7 +102,11 @@ export class SparseBitVec {
     // low bits, or the next power of two of the universe size separators in the high bits. Hopefully this will
     // be explained clearly in the accompanying design & background documentation.
     const numOnes = ones.length;
-    const lowBitWidth = numOnes === 0 ? 0 : Math.floor(Math.log2(Math.max(1, universeSize / numOnes))); 
+    const lowBitWidth = lowBitWidthOverride ?? (numOnes === 0 ? 0 : Math.floor(Math.log2(Math.max(1, universeSize / numOnes))));
+    assert(
+      Number.isInteger(lowBitWidth) && lowBitWidth >= 0 && lowBitWidth < 32,
+      () => `expected lowBitWidth to be an integer in [0, 32), got ${lowBitWidth}`
+    );
 
     // unary coding; 1 denotes values and 0 denotes separators, since that way
     // encoding becomes more efficient and we have a chance of saving space due to runs of
@@ -79,18 +118,22 @@ export class SparseBitVec {
     const low = new IntBuf(numOnes, lowBitWidth);
     const lowMask = bits.oneMask(lowBitWidth);
 
+    // Validate the input in a separate pass so that we never begin encoding invalid input.
     let numUniqueOnes = 0;
     let hasMultiplicity = false;
     let prev = -1;
     for (let i = 0; i < ones.length; i++) {
       const cur = ones[i];
+      assert(Number.isSafeInteger(cur) && cur >= 0, () => `expected a non-negative safe integer at index ${i}, got ${cur}`);
+      assert(cur < universeSize, () => `expected 1-bit at index ${i} (${cur}) to not exceed the universeSize (${universeSize})`);
+      assert(prev <= cur, () => `expected monotonically nondecreasing sequence, but the 1-bit at index ${i} (${cur}) is less than the one before it (${prev})`);
       hasMultiplicity ||= prev === cur;
       numUniqueOnes += Number(prev !== cur);
-      assertNonNegative(cur);
-      assertSafeInteger(cur);
-      assert(cur < universeSize, () => `expected 1 - bit(${cur}) to not exceed the universeSize(${universeSize})`);
-      assert(prev <= cur, 'expected monotonically nondecreasing sequence');
       prev = cur;
+    }
+
+    for (let i = 0; i < ones.length; i++) {
+      const cur = ones[i];
 
       // Encode element
       const quotient = cur >>> lowBitWidth;
@@ -99,12 +142,11 @@ export class SparseBitVec {
       low.push(remainder);
     }
 
-    // todo: allow tuning of the block parameters
-    // todo: explore passing a highBuilder here, so that you can eg. specify the options
-    // to the bitvec type. We would have to change the above loop to use the builder, and
-    // then say this.high = builder.build(buildOptions) with the options we were passed.
+    // todo: explore passing a highBuilder here, so that you can eg. choose the bitvec type.
+    // We would have to change the above loop to use the builder, and then say
+    // this.high = builder.build(buildOptions) with the options we were passed.
     /** @readonly */
-    this.high = new DenseBitVec(high.maybePadded(), 10, 10);
+    this.high = new DenseBitVec(high.maybePadded(), rank1SamplesPow2, selectSamplesPow2);
 
     /** @readonly */
     this.low = low;
@@ -134,6 +176,16 @@ export class SparseBitVec {
     this.numUniqueZeros = this.numZeros;
   }
 
+  /**
+   * Construct a sparse bit vector from 1-bit positions in any order.
+   * The input is not modified; a sorted copy is made instead.
+   * @param {ArrayLike<number>} ones
+   * @param {number} universeSize
+   */
+  static fromUnsorted(ones, universeSize) {
+    return new SparseBitVec(Array.from(ones).sort(ascending), universeSize);
+  }
+
   /**
    * @param {number} value
    */
@@ -251,4 +303,50 @@ export class SparseBitVec {
   get(index) {
     return defaults.get(this, index);
   }
+
+  /**
+   * @param {number} index
+   */
+  rank1Checked(index) {
+    return defaults.rank1Checked(this, index);
+  }
+
+  /**
+   * @param {number} first
+   * @param {number} last
+   */
+  countOnesInInclusive(first, last) {
+    return defaults.countOnesInInclusive(this, first, last);
+  }
+
+  /**
+   * @param {number} k
+   * @param {number} seed
+   */
+  sampleOnes(k, seed) {
+    return defaults.sampleOnes(this, k, seed);
+  }
+
+  /**
+   * @param {{ start: number, end: number }} range
+   */
+  zeroRuns(range) {
+    return defaults.zeroRuns(this, range);
+  }
+
+  /**
+   * @param {{ start: number, end: number }} range
+   * @param {number} minLength
+   */
+  firstZeroRunAtLeast(range, minLength) {
+    return defaults.firstZeroRunAtLeast(this, range, minLength);
+  }
+
+  /**
+   * Return the occupancy of this bit vector (which bits are set, ignoring multiplicity)
+   * as packed basic blocks, eg. for export to a plain bitmap format.
+   */
+  toOccupancyBlocks() {
+    return defaults.occupancyBlocks(this);
+  }
 };
\ No newline at end of file
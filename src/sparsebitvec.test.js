@@ -1,4 +1,5 @@
 import { describe, expect, it, test } from 'vitest';
+import * as bits from './bits.js';
 import { SparseBitVec, SparseBitVecBuilder } from './sparsebitvec.js';
 import { testBitVecType, testMultiBitVecType } from './testutils.js';
 
@@ -32,4 +33,54 @@ describe('SparseBitVec', () => {
     expect(bv.select0(3e9)).toBe(3e9 + 3);
     expect(bv.select0(4e9)).toBe(4e9 + 3);
   });
-});
\ No newline at end of file
+
+  test('countOnesInInclusive up to the largest 32-bit index', () => {
+    const universeSize = 2 ** 32 - 1;
+    const bv = new SparseBitVec([0, universeSize - 1], universeSize);
+    expect(bv.countOnesInInclusive(0, 2 ** 32 - 1)).toBe(2);
+    expect(bv.countOnesInInclusive(1, 2 ** 32 - 1)).toBe(1);
+    expect(bv.countOnesInInclusive(universeSize - 1, universeSize - 1)).toBe(1);
+    expect(bv.countOnesInInclusive(2 ** 32 - 1, 2 ** 32 - 1)).toBe(0);
+  });
+
+  test('can be constructed from unsorted input', () => {
+    const ones = [7, 3, 9, 3, 0];
+    const bv = SparseBitVec.fromUnsorted(ones, 10);
+    const expected = new SparseBitVec([0, 3, 3, 7, 9], 10);
+    expect(bv.numOnes).toBe(expected.numOnes);
+    expect(bv.hasMultiplicity).toBe(true);
+    for (let n = 0; n < bv.numOnes; n++) {
+      expect(bv.select1(n)).toBe(expected.select1(n));
+    }
+    // the input is left unmodified
+    expect(ones).toEqual([7, 3, 9, 3, 0]);
+  });
+
+  test('identifies the first invalid element of unsorted input', () => {
+    expect(() => new SparseBitVec([1, 5, 4, 2], 10)).toThrow('index 2 (4)');
+    expect(() => new SparseBitVec([1, 5, 10], 10)).toThrow('index 2 (10)');
+    expect(() => new SparseBitVec([1, -5], 10)).toThrow('index 1, got -5');
+    // the violation is detected even if it occurs at the very end of the input,
+    // since the input is validated in full before it is encoded.
+    const ones = Array.from({ length: 1000 }, (_, i) => i);
+    ones[999] = 0;
+    expect(() => new SparseBitVec(ones, 1000)).toThrow('index 999 (0)');
+  });
+
+  test('rejects invalid universe sizes', () => {
+    expect(() => new SparseBitVec([], 2 ** 32)).toThrow('expected universeSize to be an integer in [0, 2^32)');
+    // @ts-ignore because the arguments are deliberately of the wrong type
+    expect(() => new SparseBitVec(10, [1, 2])).toThrow('expected universeSize to be an integer');
+  });
+
+  test('toOccupancyBlocks', () => {
+    const bv = new SparseBitVec([0, 3, 3, 31, 32, 32, 32, 70], 71);
+    const blocks = bv.toOccupancyBlocks();
+    expect(Array.from(blocks)).toEqual([
+      bits.u32((1 << 0) | (1 << 3) | (1 << 31)),
+      1 << 0,
+      1 << (70 - 64),
+    ]);
+    expect(Array.from(new SparseBitVec([], 0).toOccupancyBlocks())).toEqual([]);
+  });
+});
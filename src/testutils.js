@@ -1,10 +1,11 @@
 import * as d3 from 'd3';
 import fc from 'fast-check';
 import { describe, expect, it, test } from 'vitest';
-import { assert, assertSafeInteger } from './assert.js';
+import { assert } from './assert.js';
 import { BitBuf } from './bitbuf';
 import { DenseBitVec, DenseBitVecBuilder } from './densebitvec';
-import { bits } from './index.js';
+import * as bits from './bits.js';
+import { sparseFisherYatesSample } from './sample.js';
 import { SortedArrayBitVec, SortedArrayBitVecBuilder } from './sortedarraybitvec.js';
 
 // todo:
@@ -26,8 +27,17 @@ export function testBitVec(bv) {
   expect(bv.rank1(0)).toBe(0);
   expect(bv.rank1(bv.numZeros + bv.numOnes + 1)).toBe(bv.numOnes);
 
-  expect(() => bv.get(-1)).toThrow();
-  expect(() => bv.get(bv.numZeros + bv.numOnes + 1)).toThrow();
+  expect(bv.rank1Checked(-1)).toEqual({ count: 0, clamped: 'start' });
+  expect(bv.rank1Checked(bv.universeSize)).toEqual({ count: bv.numOnes, clamped: 'end' });
+  expect(bv.rank1Checked(bv.universeSize + 1)).toEqual({ count: bv.numOnes, clamped: 'end' });
+  if (bv.universeSize > 0) {
+    const index = bv.universeSize - 1;
+    expect(bv.rank1Checked(index)).toEqual({ count: bv.rank1(index), clamped: null });
+  }
+
+  expect(() => bv.get(-1)).toThrow('out of bounds');
+  expect(() => bv.get(bv.universeSize)).toThrow('out of bounds');
+  expect(() => bv.get(bv.numZeros + bv.numOnes + 1)).toThrow('out of bounds');
 
   // Run an adjusted set of tests in the case of multiplicity.
   // In particular, all of the bit vectors that allow multiplicity
@@ -119,48 +129,211 @@ export function testBitVec(bv) {
     expect(bv.get(i)).toEqual(count);
   }
 
+  testRankSelectIdentities(bv);
+  testZeroRuns(bv);
+  testCountOnesInInclusive(bv);
+  testSampleOnes(bv);
 }
 
 /**
- * Returns an unsorted sample of k elements without replacement, sampled
- * from the universe [0, `n`).
- * This algorithm is best when the sample is sparse, ie. `k` is much smaller than `n`.
- * If `k` and `n` are of comparable size, then you may as well just do
- * an in-place Fischer-Yates shuffle of the full range as an array:
- *   https://en.wikipedia.org/wiki/Fisher%E2%80%93Yates_shuffle
+ * Check `countOnesInInclusive` against a bit-by-bit sum over several ranges, including
+ * empty ranges and ranges that extend beyond the universe.
+ * @param {BitVec} bv
+ */
+export function testCountOnesInInclusive(bv) {
+  const n = bv.universeSize;
+  const ranges = [[0, n - 1], [-5, n + 5], [1, n - 2], [Math.floor(n / 2), Math.floor(n / 2)], [n - 1, 0], [0, 2 ** 32 - 1]];
+  for (const [first, last] of ranges) {
+    let expected = 0;
+    for (let i = Math.max(first, 0); i <= Math.min(last, n - 1); i++) {
+      expected += bv.get(i);
+    }
+    expect(bv.countOnesInInclusive(first, last)).toBe(expected);
+  }
+}
+
+/**
+ * Check that `sampleOnes` returns the requested number of 1-bit positions in ascending order,
+ * without sampling any 1-bit twice, and that the sample is determined by the seed.
+ * @param {BitVec} bv
+ */
+export function testSampleOnes(bv) {
+  for (const k of [0, 1, Math.floor(bv.numOnes / 2), bv.numOnes, bv.numOnes + 5]) {
+    for (const seed of [0, 12345]) {
+      const sample = bv.sampleOnes(k, seed);
+      expect(sample.length).toBe(Math.min(k, bv.numOnes));
+      expect(bv.sampleOnes(k, seed)).toEqual(sample);
+      const counts = d3.rollup(sample, g => g.length, d => d);
+      for (const [i, count] of counts) {
+        expect(count).toBeLessThanOrEqual(bv.get(i));
+      }
+      for (let n = 1; n < sample.length; n++) {
+        expect(sample[n - 1]).toBeLessThanOrEqual(sample[n]);
+      }
+    }
+  }
+  if (!bv.hasMultiplicity) {
+    expect(bv.sampleOnes(bv.numOnes, 7)).toEqual(Array.from({ length: bv.numOnes }, (_, n) => bv.select1(n)));
+  }
+}
+
+/**
+ * Check that every method of a bit vector with a universe size of 0 is well-defined:
+ * ranks are 0, selects find nothing, and `get` throws since there are no valid indices.
+ * @param {BitVec} bv
+ */
+export function testEmptyUniverse(bv) {
+  expect(bv.universeSize).toBe(0);
+  expect(bv.numOnes).toBe(0);
+  expect(bv.numZeros).toBe(0);
+  expect(bv.numUniqueOnes).toBe(0);
+  expect(bv.numUniqueZeros).toBe(0);
+  for (const x of [0, 1, 2 ** 32 - 1]) {
+    expect(bv.rank1(x)).toBe(0);
+    if (!bv.hasMultiplicity) {
+      expect(bv.rank0(x)).toBe(0);
+    }
+    expect(bv.rank1Checked(x)).toEqual({ count: 0, clamped: 'end' });
+    expect(bv.countOnesInInclusive(0, x)).toBe(0);
+    expect(bv.trySelect1(x)).toBe(null);
+    expect(() => bv.select1(x)).toThrow();
+    if (!bv.hasMultiplicity) {
+      expect(bv.trySelect0(x)).toBe(null);
+      expect(() => bv.select0(x)).toThrow();
+    }
+    expect(() => bv.get(x)).toThrow();
+    expect(Array.from(bv.zeroRuns({ start: 0, end: x }))).toEqual([]);
+    expect(bv.firstZeroRunAtLeast({ start: 0, end: x }, 1)).toBe(null);
+    expect(bv.sampleOnes(x, 0)).toEqual([]);
+  }
+}
+
+/**
+ * Check `zeroRuns` and `firstZeroRunAtLeast` against a bit-by-bit scan over several ranges,
+ * including ranges that bisect runs and ranges that extend beyond the universe.
+ * @param {BitVec} bv
+ */
+export function testZeroRuns(bv) {
+  const n = bv.universeSize;
+  const isZero = Array.from({ length: n }, (_, i) => bv.get(i) === 0);
+  const ranges = [
+    { start: 0, end: n },
+    { start: -5, end: n + 5 },
+    { start: 1, end: n - 1 },
+    { start: Math.floor(n / 3), end: Math.floor(2 * n / 3) },
+    { start: Math.floor(n / 2), end: Math.floor(n / 2) },
+  ];
+  for (const range of ranges) {
+    /** @type {{ start: number, end: number }[]} */
+    const expected = [];
+    for (let i = Math.max(range.start, 0); i < Math.min(range.end, n); i++) {
+      if (!isZero[i]) continue;
+      const last = expected[expected.length - 1];
+      if (last !== undefined && last.end === i) {
+        last.end++;
+      } else {
+        expected.push({ start: i, end: i + 1 });
+      }
+    }
+    expect(Array.from(bv.zeroRuns(range))).toEqual(expected);
+    for (const minLength of [1, 2, 5]) {
+      expect(bv.firstZeroRunAtLeast(range, minLength)).toEqual(expected.find(r => r.end - r.start >= minLength) ?? null);
+    }
+  }
+}
+
+/**
+ * Check the algebraic identities relating rank and select directly on `bv`,
+ * rather than by comparison against a baseline implementation (which could share a bug).
+ * 
+ * In the absence of multiplicity, for all valid `n` and all indices `i`:
+ * - `rank1(select1(n)) === n`
+ * - `select1(rank1(select1(n))) === select1(n)`
+ * - `rank1(i) + rank0(i) === min(max(i, 0), universeSize)`
+ * - `rank1` is monotonically nondecreasing
  * 
- * The idea comes from the paper
- *
- *   - Title: Simple, Optimal Algorithms for Random Sampling Without Replacement
- *   - Link: https://arxiv.org/abs/2104.05091
- *   - Author Daniel Ting: https://stat.uw.edu/about-us/people/daniel-ting
- *
- * Note: If we were in a typed language, instead of storing the `index` in `m`, we could
- * store `n - index` (or `n - index - 1` or something) to make the numbers smaller, potentially
- * being able to use a smaller integer type for the keys and values of `m` (in a typed language).
+ * In the presence of multiplicity, rank1 is weighted by multiplicity, so the first
+ * identity weakens to `rank1(select1(n)) <= n < rank1(select1(n) + 1)`, with equality
+ * only when `n` is the first 1-bit in its group of repeated 1-bits. The second identity
+ * holds as stated, since `rank1(select1(n))` is always the first 1-bit of that group.
+ * If rank0 is supported, the third identity holds with the count of unique 1-bits in
+ * place of `rank1(i)`, ie. `numUniqueOnes` when `i >= universeSize`.
  * 
- * @param {number} k - number of elements to sample
- * @param {number} n - universe size
- * @param { () => number } rng - random number generator function
+ * @param {BitVec} bv
  */
-function sparseFisherYatesSample(k, n, rng) {
-  assertSafeInteger(k);
-  assertSafeInteger(n);
-  if (n < 0) throw new Error("n must be nonnegative");
-  if (k > n) throw new Error("k must not exceed n");
-  if (n === 0) return new Uint32Array();
-  const m = new Map();
-  const xs = new Uint32Array(k);
-  for (let i = 0; i < k; i++) {
-    // iterate through the conceptual array of n elements backwards
-    const index = n - i - 1;
-    // random number between 0 and index
-    const x = Math.floor((index + 1) * rng());
-    xs[i] = m.get(x) ?? x;
-    m.set(x, m.get(index) ?? index);
-    if (x === index) m.delete(index);
+export function testRankSelectIdentities(bv) {
+  // rank1 is monotonically nondecreasing, including outside of the universe
+  let prevRank = 0;
+  for (let i = -1; i <= bv.universeSize + 1; i++) {
+    const rank = bv.rank1(i);
+    expect(rank).toBeGreaterThanOrEqual(prevRank);
+    prevRank = rank;
   }
-  return xs;
+
+  for (let n = 0; n < bv.numOnes; n++) {
+    const select1 = bv.select1(n);
+    const rank1 = bv.rank1(select1);
+    if (bv.hasMultiplicity) {
+      expect(rank1).toBeLessThanOrEqual(n);
+      expect(bv.rank1(select1 + 1)).toBeGreaterThan(n);
+    } else {
+      expect(rank1).toBe(n);
+    }
+    expect(bv.select1(rank1)).toBe(select1);
+  }
+
+  let supportsRank0 = true;
+  try {
+    bv.rank0(0);
+  } catch {
+    supportsRank0 = false;
+  }
+  // Only multiplicity-capable bit vectors may decline to support rank0.
+  expect(supportsRank0 || bv.hasMultiplicity).toBe(true);
+
+  if (supportsRank0) {
+    // Count the unique 1-bits below each index by walking the 1-bits in order.
+    let n = 0;
+    let numUniqueOnesBelow = 0;
+    for (let i = -1; i <= bv.universeSize + 1; i++) {
+      while (n < bv.numOnes && bv.select1(n) < i) {
+        const select1 = bv.select1(n);
+        numUniqueOnesBelow++;
+        // skip any repeated 1-bits at the same index
+        while (n < bv.numOnes && bv.select1(n) === select1) n++;
+      }
+      expect(numUniqueOnesBelow + bv.rank0(i)).toBe(Math.min(Math.max(i, 0), bv.universeSize));
+    }
+    expect(numUniqueOnesBelow).toBe(bv.numUniqueOnes);
+  }
+}
+
+/**
+ * Returns a collection of hand-picked bit patterns that tend to exercise edge cases
+ * in bit vector implementations, as `{ name, universeSize, ones }` objects.
+ * @param {number} universeSize
+ */
+export function adversarialInputs(universeSize) {
+  const all = Array.from({ length: universeSize }, (_, i) => i);
+  return [
+    { name: 'all zeros', universeSize, ones: [] },
+    { name: 'all ones', universeSize, ones: all },
+    { name: 'single one at the start', universeSize, ones: [0] },
+    { name: 'single one at the end', universeSize, ones: [universeSize - 1] },
+    { name: 'alternating bits (starting with 1)', universeSize, ones: all.filter(i => i % 2 === 0) },
+    { name: 'alternating bits (starting with 0)', universeSize, ones: all.filter(i => i % 2 === 1) },
+    {
+      // each basic block contains a single run of 1-bits whose position and length vary by block
+      name: 'one run per block',
+      universeSize,
+      ones: all.filter(i => {
+        const block = bits.basicBlockIndex(i);
+        const offset = bits.basicBlockBitOffset(i);
+        const runStart = block % bits.BasicBlockSize;
+        return offset >= runStart && offset < runStart + block + 1;
+      })
+    },
+  ];
 }
 
 /**
@@ -224,13 +397,97 @@ export function testMultiBitVecType(BitVecBuilder, buildOptions = {}) {
 export function testBitVecType(BitVecBuilder, buildOptions = {}) {  
   test('builder', () => {
     const builder = new BitVecBuilder(0);
-    DEBUG && expect(() => builder.one(0)).toThrow();
+    expect(() => builder.one(0)).toThrow('cannot exceed universeSize');
+  });
+
+  test('empty universe', () => {
+    for (const bv of [new BitVecBuilder(0).build(buildOptions), BitVecBuilder.fromSortedOnes(0, [], buildOptions)]) {
+      testEmptyUniverse(bv);
+    }
+  });
+
+  test('builder rejects out-of-universe indices', () => {
+    const universeSize = 10;
+    for (const index of [universeSize, universeSize + 1, 2 ** 32 - 1]) {
+      const builder = new BitVecBuilder(universeSize);
+      expect(() => builder.one(index)).toThrow(`index (${index}) cannot exceed universeSize (${universeSize})`);
+    }
+    for (const index of [-1, 1.5, NaN]) {
+      const builder = new BitVecBuilder(universeSize);
+      expect(() => builder.one(index)).toThrow(`index (${index}) must be a non-negative integer`);
+    }
+  });
+
+  test('builder rejects invalid universe sizes', () => {
+    const message = 'expected universeSize to be an integer in [0, 2^32)';
+    expect(() => new BitVecBuilder(-1)).toThrow(message);
+    expect(() => new BitVecBuilder(1.5)).toThrow(message);
+    expect(() => new BitVecBuilder(2 ** 32)).toThrow(message);
+    expect(() => new BitVecBuilder(NaN)).toThrow(message);
+    // catch swapped arguments
+    // @ts-ignore because the arguments are deliberately of the wrong type
+    expect(() => BitVecBuilder.fromSortedOnes([1, 2], 10)).toThrow(message);
+  });
+
+  test('builder cannot be reused after it is built', () => {
+    const builder = new BitVecBuilder(10);
+    builder.one(3);
+    const bv = builder.build(buildOptions);
+    expect(() => builder.one(5)).toThrow('after it has been built');
+    expect(() => builder.build(buildOptions)).toThrow('only be built once');
+    // the built bit vector is unaffected by the attempted modification
+    expect(bv.numOnes).toBe(1);
+    expect(bv.select1(0)).toBe(3);
   });
 
   test('property tests', () => {
     testBitVecProperties(BitVecBuilder, buildOptions);
   });
 
+  test('adversarial inputs', () => {
+    // test universe sizes that do and do not align with the basic block size
+    for (const universeSize of [1, bits.BasicBlockSize, bits.BasicBlockSize * 10 + 3]) {
+      for (const { ones } of adversarialInputs(universeSize)) {
+        const builder = new BitVecBuilder(universeSize);
+        for (const one of ones) {
+          builder.one(one);
+        }
+        const bv = builder.build(buildOptions);
+        expect(bv.numOnes).toBe(ones.length);
+        expect(bv.universeSize).toBe(universeSize);
+        testBitVec(bv);
+      }
+    }
+  });
+
+  test('fromSortedOnes', () => {
+    const universeSize = bits.BasicBlockSize * 10 + 3;
+    for (const { ones } of adversarialInputs(universeSize)) {
+      // compare against the regular path, setting the 1-bits in reverse order
+      const builder = new BitVecBuilder(universeSize);
+      for (let i = ones.length; i-- > 0;) {
+        builder.one(ones[i]);
+      }
+      const expected = builder.build(buildOptions);
+      const bv = BitVecBuilder.fromSortedOnes(universeSize, ones, buildOptions);
+      expect(bv.universeSize).toBe(expected.universeSize);
+      expect(bv.numOnes).toBe(expected.numOnes);
+      for (let n = 0; n < bv.numOnes; n++) {
+        expect(bv.select1(n)).toBe(expected.select1(n));
+      }
+      for (let i = 0; i <= universeSize; i++) {
+        expect(bv.rank1(i)).toBe(expected.rank1(i));
+      }
+    }
+
+    if (DEBUG) {
+      // unsorted input
+      expect(() => BitVecBuilder.fromSortedOnes(10, [3, 1], buildOptions)).toThrow();
+      // repeated input
+      expect(() => BitVecBuilder.fromSortedOnes(10, [1, 1], buildOptions)).toThrow();
+    }
+  });
+
   // large enough to span many blocks
   const universeSize = bits.BasicBlockSize * 10;
   // save time by only testing with every `step`-th bit set
@@ -0,0 +1,118 @@
+/**
+ * Level-by-level traversal of wavelet matrix nodes, which drives the batched queries of
+ * `WaveletMatrix` and can be used to implement custom ones outside of this library. Each node
+ * is represented by a caller-defined value, such as its leftmost symbol and index range along
+ * with a key identifying the query it belongs to.
+ *
+ * Each call to `traverse` visits the current nodes in order and lets the visitor emit any
+ * number of left and right children. The children then become the current nodes, with all
+ * left children ordered before all right children, which matches the order in which nodes are
+ * laid out on the next level of a wavelet matrix.
+ *
+ * If a `merge` function is given, each emitted child is first offered to it along with the
+ * previous child emitted in the same direction. If it returns true, the new child is assumed
+ * to have been merged into the previous one and is discarded.
+ *
+ * Node index ranges on each level can be computed with `WaveletMatrix.childRanges`.
+ * For example, this computes the nodes returned by `counts` for the index range `range`:
+ *
+ *   const traversal = new Traversal([{ symbol: 0, ...range }]);
+ *   traversal.traverseLevels(wm.levels, (x, go, { level, index }) => {
+ *     const { left, right } = wm.childRanges(index, x);
+ *     if (left.start < left.end) go.left({ symbol: x.symbol, ...left });
+ *     if (right.start < right.end) go.right({ symbol: x.symbol + level.bit, ...right });
+ *   });
+ *   const nodes = Array.from(traversal.results());
+ *
+ * @template T
+ */
+export class Traversal {
+  /**
+   * @param {Iterable<T>} values - the initial nodes
+   * @param {Object} [options]
+   * @param {((prev: T, next: T) => boolean) | null} [options.merge]
+   */
+  constructor(values, { merge = null } = {}) {
+    /** @type {T[]} */
+    this.values = Array.from(values);
+    /** @type {T[]} */
+    this.nextLeft = [];
+    /** @type {T[]} */
+    this.nextRight = [];
+    this.merge = merge;
+  }
+
+  /**
+   * The number of current nodes.
+   */
+  get size() {
+    return this.values.length;
+  }
+
+  /**
+   * Visit the current nodes in order, replacing them with the children emitted by `f`.
+   * The optional `context` is passed through to `f` as its third argument.
+   * @template C
+   * @param {(value: T, go: { left: (child: T) => void, right: (child: T) => void }, context: C) => void} f
+   * @param {C} [context]
+   */
+  traverse(f, context) {
+    const { nextLeft, nextRight, merge } = this;
+    const go = {
+      left: (/** @type {T} */ child) => { pushOrMerge(nextLeft, child, merge); },
+      right: (/** @type {T} */ child) => { pushOrMerge(nextRight, child, merge); },
+    };
+    for (const value of this.values) {
+      f(value, go, /** @type {C} */ (context));
+    }
+    // reuse the current array for the next round of left children
+    const values = this.values;
+    values.length = 0;
+    for (const child of nextRight) {
+      nextLeft.push(child);
+    }
+    nextRight.length = 0;
+    this.values = nextLeft;
+    this.nextLeft = values;
+  }
+
+  /**
+   * Traverse once per level, passing each level to `f` along with its index
+   * and the number of levels remaining after it.
+   * @template L
+   * @param {L[]} levels
+   * @param {(value: T, go: { left: (child: T) => void, right: (child: T) => void }, context: LevelContext<L>) => void} f
+   */
+  traverseLevels(levels, f) {
+    for (let index = 0; index < levels.length; index++) {
+      const context = { level: levels[index], index, remaining: levels.length - index - 1 };
+      this.traverse(f, context);
+    }
+  }
+
+  /**
+   * Iterate over the current nodes, eg. after traversing to the desired level.
+   * @returns {IterableIterator<T>}
+   */
+  results() {
+    return this.values.values();
+  }
+}
+
+/**
+ * Append `child` to `nodes` unless `merge` merges it into the last node.
+ * @template T
+ * @param {T[]} nodes
+ * @param {T} child
+ * @param {((prev: T, next: T) => boolean) | null} merge
+ */
+function pushOrMerge(nodes, child, merge) {
+  if (merge === null || nodes.length === 0 || !merge(nodes[nodes.length - 1], child)) {
+    nodes.push(child);
+  }
+}
+
+/**
+ * @template L
+ * @typedef {{ level: L, index: number, remaining: number }} LevelContext
+ */
@@ -0,0 +1,64 @@
+import { describe, expect, it } from 'vitest';
+import './debug.js';
+import { Range } from './range.js';
+import { Traversal } from './traversal.js';
+import { WaveletMatrix } from './waveletmatrix.js';
+
+describe('Traversal', () => {
+  it('passes level context to the visitor', () => {
+    const traversal = new Traversal([0]);
+    /** @type {{ level: string, index: number, remaining: number }[]} */
+    const contexts = [];
+    traversal.traverseLevels(['a', 'b', 'c'], (x, go, context) => {
+      if (x === 0) contexts.push(context);
+      go.left(x);
+      go.right(x + 2 ** context.remaining);
+    });
+    expect(contexts).toEqual([
+      { level: 'a', index: 0, remaining: 2 },
+      { level: 'b', index: 1, remaining: 1 },
+      { level: 'c', index: 2, remaining: 0 },
+    ]);
+    // a custom traversal that emits the leftmost symbol of every node in a three-level tree
+    expect(Array.from(traversal.results())).toEqual([0, 4, 2, 6, 1, 5, 3, 7]);
+  });
+
+  it('merges children emitted in the same direction', () => {
+    // merge consecutive children with equal values, counting the duplicates
+    const traversal = new Traversal([[1, 1], [1, 1], [2, 1], [2, 1]], {
+      merge: (prev, next) => {
+        if (prev[0] !== next[0]) return false;
+        prev[1] += next[1];
+        return true;
+      }
+    });
+    traversal.traverse((x, go) => {
+      go.left(x);
+      go.right([x[0], 1]);
+    });
+    // children are not merged across the boundary between left and right children
+    expect(Array.from(traversal.results())).toEqual([[1, 2], [2, 2], [1, 2], [2, 2]]);
+  });
+
+  it('can implement counts', () => {
+    const data = Array.from({ length: 500 }, (_, i) => (i * 37 + (i >> 4)) % 23);
+    const wm = new WaveletMatrix(data);
+    const ranges = [Range(0, 500), Range(17, 18), Range(100, 321), Range(250, 250)];
+    const traversal = new Traversal(ranges.map((range, key) => ({ key, symbol: 0, ...range })));
+    traversal.traverseLevels(wm.levels, (x, go, { level, index }) => {
+      const { left, right } = wm.childRanges(index, x);
+      if (left.start < left.end) go.left({ key: x.key, symbol: x.symbol, ...left });
+      if (right.start < right.end) go.right({ key: x.key, symbol: x.symbol + level.bit, ...right });
+    });
+    const nodes = Array.from(traversal.results());
+    for (const [key, range] of ranges.entries()) {
+      const rangeNodes = nodes.filter(x => x.key === key);
+      expect(rangeNodes.map(({ symbol, start, end }) => ({ symbol, start, end }))).toEqual(wm.counts({ range }));
+    }
+  });
+
+  it('childRanges rejects invalid levels', () => {
+    const wm = new WaveletMatrix([1, 2, 3]);
+    expect(() => wm.childRanges(wm.numLevels, Range(0, 3))).toThrow('must be an integer in [0, 2)');
+  });
+});
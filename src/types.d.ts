@@ -1,7 +1,12 @@
 interface BitVecBuilderConstructable {
   new(universeSize: number): BitVecBuilder;
+  // Build a bit vector from an ascending array of unique 1-bit positions.
+  // Builders may override this to skip work (eg. sorting) that is redundant for such input.
+  fromSortedOnes(universeSize: number, sortedOnes: ArrayLike<number>, options?: object): BitVec;
 }
 
+// Builders are single-use: since a built bit vector may share storage with its builder,
+// builders throw if they are modified or built again after `build` has been called.
 interface BitVecBuilder {
   // The second argument is optional and customarily filled in with a default value of 1.
   one(index: number, count?: number): void;
@@ -16,6 +21,23 @@ interface BitVec {
   rank1(index: number): number;
   rank0(index: number): number;
 
+  // Like rank1, but reports whether the index was clamped to the start or end of the universe.
+  // Indices at the universe size count as clamped to the end.
+  rank1Checked(index: number): RankResult;
+
+  // Count of 1-bits in the inclusive index range [first, last], for callers working with
+  // inclusive extents. The range is clamped to the universe, so `last` may be 2^32 - 1.
+  countOnesInInclusive(first: number, last: number): number;
+
+  // Positions of min(k, numOnes) 1-bits sampled uniformly without replacement, in ascending
+  // order. The sample is determined by the 32-bit integer `seed`.
+  sampleOnes(k: number, seed: number): number[];
+
+  // Iterate over the maximal runs of 0-bits within the index range, clipped to the range.
+  zeroRuns(range: { start: number, end: number }): Iterable<{ start: number, end: number }>;
+  // The first of those runs whose length is at least `minLength`, or null if there is none.
+  firstZeroRunAtLeast(range: { start: number, end: number }, minLength: number): { start: number, end: number } | null;
+
   select1(n: number): number;
   select0(n: number): number;
 
@@ -61,3 +83,7 @@ interface BitVec {
 
 }
 
+interface RankResult {
+  count: number;
+  clamped: 'start' | 'end' | null;
+}
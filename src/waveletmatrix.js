@@ -1,9 +1,15 @@
-import { symbol } from 'd3';
 import { assert } from './assert.js';
 import { BitBuf } from './bitbuf.js';
+import { Columns } from './columns.js';
 import { oneMask, reverseLowBits, u32 } from './bits.js';
 import { DenseBitVec } from './densebitvec.js';
-import { bits } from './index.js';
+import './debug.js';
+import * as bits from './bits.js';
+import { Traversal } from './traversal.js';
+import { ascending } from './sort.js';
+import * as morton from '../morton.js';
+import { MaskedRange, Range, rangeFullyContains, rangeIsEmpty, rangesOverlap } from './range.js';
+import { sampleSortedDistinct } from './sample.js';
 
 // Implements a wavelet matrix, which is an efficient data structure for
 // wavelet tree operations on top of a levelwise bitvector representation
@@ -19,6 +25,14 @@ import { bits } from './index.js';
 // Paper: New algorithms on wavelet trees and applications to information retrieval:
 //   https://www.sciencedirect.com/science/article/pii/S0304397511009625/pdf?md5=32fe86d035e8a0859fd3a4b045e8b36b&pid=1-s2.0-S0304397511009625-main.pdf
 
+// The `ignoreBits` option accepted by several methods below causes the bottom `ignoreBits` levels
+// of the matrix to be ignored, so that symbols are only distinguished by their high
+// `numLevels - ignoreBits` bits. Queries then operate on the "virtual bottom level" at depth
+// `numLevels - ignoreBits`, in which each node groups the `2^ignoreBits` symbols sharing those
+// high bits, and elements within a node keep their original sequence order. With `ignoreBits`
+// equal to `numLevels`, all symbols are treated as equal. The valid values are integers in
+// `[0, numLevels]`; other values throw an error.
+//
 // todo:
 // - debug asserts (eg. validate ranges)
 // - explain and test behavior of selectUpwards. I tried writing tests but couldn't actually figure out what it's supposed to do.
@@ -26,47 +40,152 @@ import { bits } from './index.js';
 const rank1SamplesPow2 = 10;
 const selectSamplesPow2 = 10;
 
+/**
+ * Signal that can be passed to long-running traversals to cancel them, which will cause them
+ * to throw `reason` (or a generic error). An `AbortSignal` can be used directly. Since queries
+ * run synchronously, the signal can only change during a query if its `aborted` property is
+ * computed on access, eg. from a `SharedArrayBuffer` flag set by another thread or from a deadline.
+ * @typedef {{ readonly aborted: boolean, readonly reason?: any }} CancelSignal
+ */
+
+/**
+ * Progress of a wavelet matrix construction, reported to the `onProgress` construction option.
+ * Construction makes one pass over the data per level, and `elementsDone` counts the elements
+ * processed across all passes, so it increases monotonically up to `elementsTotal`, which is
+ * `length * levelsTotal`. The phase is the construction algorithm; see `constructionStrategy`.
+ * @typedef {{
+ *   phase: 'histogram' | 'sort',
+ *   levelsDone: number,
+ *   levelsTotal: number,
+ *   elementsDone: number,
+ *   elementsTotal: number,
+ * }} Progress
+ */
+
 export class WaveletMatrix {
 
   /**
-   * @param {number[]} data
+   * The data is never modified, so a matrix can be built directly from a view into a larger
+   * buffer, eg. from `buffer.subarray(start, end)` when building several matrices from slices of
+   * the same buffer. The histogram-based construction reads the data in place, while the sort-based
+   * construction works on a copy; see `constructionStrategy` for which one is used.
+   * @param {number[] | Uint32Array} data
    * @param {number} [maxSymbol]
+   * @param {Object} [options]
+   * @param {boolean} [options.retainData] - if true, keep a copy of `data` alongside the levels
+   * so that `get` and `toArray` can read from it directly rather than walking the levels,
+   * which takes O(numLevels) rank operations per element. The copy uses 4 bytes per element,
+   * which is typically several times more than the levels themselves.
+   * @param {((progress: Progress) => void) | null} [options.onProgress] - called at the start of
+   * construction, at every level boundary, every `ProgressInterval` elements within a level,
+   * and upon completion; see `Progress`
    */
-  constructor(data, maxSymbol) {
+  constructor(data, maxSymbol, { retainData = false, onProgress = null } = {}) {
     if (maxSymbol === undefined) {
       maxSymbol = data.reduce((a, b) => Math.max(a, b), 0);
     }
     assert(maxSymbol < 2 ** 32);
+    // The levels can represent symbols up to the next power of two, so a symbol above `maxSymbol`
+    // would otherwise be stored and later returned by queries such as `counts`.
+    const invalidIndex = data.findIndex(x => !(x >= 0 && x <= maxSymbol));
+    assert(invalidIndex === -1, () => `expected symbols in [0, ${maxSymbol}], got ${data[invalidIndex]} at index ${invalidIndex}`);
     const alphabetSize = maxSymbol + 1;
     const numLevels = Math.max(1, Math.ceil(Math.log2(alphabetSize)));
-    // We implement two different wavelet matrix construction algorithms. One of them is more
-    // efficient, but that algorithm does not scale well to large alphabets and also cannot
-    // cannot handle element multiplicity because it constructs the bitvectors out-of-order.
-    // It also requires O(2^num_levels) space. So, we check whether the number of data points
-    // is less than 2^num_levels, and if so use the scalable algorithm, and otherise use the
-    // the efficient algorithm.
+    // We implement two different wavelet matrix construction algorithms, which differ
+    // in the amount of scratch space they need; see `constructionStrategy`.
     let /** @type {BitVec[]} */ bitVecs;
     if (data.length === 0) {
       // Create an empty bitvec since numLevels is 1
       bitVecs = [new DenseBitVec(new BitBuf(0), rank1SamplesPow2, selectSamplesPow2)];
-    } else if (numLevels <= Math.floor(Math.log2(data.length))) {
-      bitVecs = buildBitVecsSmallAlphabet(data, numLevels);
+      progressReporter(onProgress, 'histogram', 0, 1)?.(1, 0);
+    } else if (constructionStrategy(data.length, numLevels) === 'histogram') {
+      bitVecs = buildBitVecsSmallAlphabet(data, numLevels, progressReporter(onProgress, 'histogram', data.length, numLevels));
     } else {
-      bitVecs = buildBitVecsLargeAlphabet(data, numLevels);
+      bitVecs = buildBitVecsLargeAlphabet(data, numLevels, progressReporter(onProgress, 'sort', data.length, numLevels));
     };
 
-    assert(bitVecs.length > 0);
-    this.maxSymbol = maxSymbol;
-    this.numLevels = bitVecs.length;
-    this.maxLevel = this.numLevels - 1;
-    this.alphabetSize = 2 ** this.numLevels;
-    this.length = bitVecs[0].universeSize;
-    this.levels = bitVecs.map((bv, index) => ({
-      nz: bv.numZeros,
-      bit: u32(1 << (this.maxLevel - index)),
-      bv
-    }));
-    this.defaultLevelMasks = bitVecs.map(() => bits.oneMask(32));
+    initialize(this, bitVecs, maxSymbol, retainData ? Uint32Array.from(data) : null);
+  }
+
+  /**
+   * Build a wavelet matrix over the 2d Morton codes of the points `(xs[i], ys[i])`, whose
+   * coordinates must be less than 2^16. Use `mortonLevelMasks(wm.numLevels, 2)` as the
+   * `ignoreBits` option of the counting functions to query a box, given as the symbol range
+   * from the code of its lowest corner to one past the code of its highest corner.
+   * @param {ArrayLike<number>} xs
+   * @param {ArrayLike<number>} ys
+   * @param {Object} [options] - construction options; see the constructor
+   * @param {boolean} [options.retainData]
+   */
+  static fromPoints2d(xs, ys, options = {}) {
+    checkCoordinates([xs, ys], 16);
+    const codes = morton.encode2Array(xs, ys);
+    return new WaveletMatrix(codes, codes.reduce((a, b) => Math.max(a, b), 0), options);
+  }
+
+  /**
+   * The 3d version of `fromPoints2d`, for coordinates less than 2^10.
+   * @param {ArrayLike<number>} xs
+   * @param {ArrayLike<number>} ys
+   * @param {ArrayLike<number>} zs
+   * @param {Object} [options] - construction options; see the constructor
+   * @param {boolean} [options.retainData]
+   */
+  static fromPoints3d(xs, ys, zs, options = {}) {
+    checkCoordinates([xs, ys, zs], 10);
+    const codes = morton.encode3Array(xs, ys, zs);
+    return new WaveletMatrix(codes, codes.reduce((a, b) => Math.max(a, b), 0), options);
+  }
+
+  /**
+   * Returns a new wavelet matrix over the symbols `f(symbol)`, with maximum symbol `newMaxSymbol`.
+   * The transformation should be monotone non-decreasing (eg. a quantization onto a coarser grid),
+   * which is spot-checked in debug mode. The sequence is reconstructed and the new matrix is built
+   * from scratch; to drop the low bits of every symbol, `mapSymbolsShift` is much cheaper.
+   * @param {(symbol: number) => number} f
+   * @param {number} newMaxSymbol
+   * @param {Object} [options] - construction options for the new matrix; see the constructor
+   * @param {boolean} [options.retainData]
+   */
+  mapSymbols(f, newMaxSymbol, options = {}) {
+    const data = this.toArray();
+    DEBUG && assertMonotoneOnSample(f, data);
+    for (let i = 0; i < data.length; i++) {
+      data[i] = f(data[i]);
+      DEBUG && assert(data[i] <= newMaxSymbol, () => `mapped symbol (${data[i]}) cannot exceed newMaxSymbol (${newMaxSymbol})`);
+    }
+    const wm = new WaveletMatrix(data, newMaxSymbol, options);
+    // The elements keep their positions, so a decimated matrix keeps its sampling step
+    wm.sampleShift = this.sampleShift;
+    return wm;
+  }
+
+  /**
+   * Returns a new wavelet matrix over the symbols `symbol >>> k`, equivalent to calling
+   * `mapSymbols(s => s >>> k, this.maxSymbol >>> k)`. Since the order of elements on each level
+   * only depends on the bits of the levels above it, this drops the bottom `k` levels and shares
+   * the bit vectors of the remaining ones with this matrix, without touching the data.
+   * Queries on the result behave like queries on this matrix with `ignoreBits` equal to `k`,
+   * except that the symbols are shifted down rather than having their low bits cleared.
+   * @param {number} k
+   */
+  mapSymbolsShift(k) {
+    assert(Number.isInteger(k) && k >= 0 && k < 32, 'k must be an integer in [0, 32)');
+    const data = this.data === null ? null : this.data.map(d => d >>> k);
+    const maxSymbol = this.maxSymbol >>> k;
+    if (k >= this.numLevels) {
+      // Every symbol becomes zero, which is represented by a single all-zero level
+      const zeros = new Uint32Array(this.length);
+      const wm = new WaveletMatrix(zeros, maxSymbol, { retainData: data !== null });
+      wm.sampleShift = this.sampleShift;
+      return wm;
+    }
+    const bitVecs = this.levels.slice(0, this.numLevels - k).map(level => level.bv);
+    const wm = Object.create(WaveletMatrix.prototype);
+    initialize(wm, bitVecs, maxSymbol, data);
+    // The elements keep their positions, so a decimated matrix keeps its sampling step
+    wm.sampleShift = this.sampleShift;
+    return wm;
   }
 
   /**
@@ -76,6 +195,7 @@ export class WaveletMatrix {
    * @param {number} [options.ignoreBits]
    */
   locate(symbol, { range = Range(0, this.length), ignoreBits = 0 } = {}) {
+    assertValidIgnoreBits(this, ignoreBits);
     let precedingCount = 0;
     const numLevels = this.numLevels - ignoreBits;
     for (let i = 0; i < numLevels; i++) {
@@ -122,30 +242,141 @@ export class WaveletMatrix {
   }
 
   /**
-   * @param {number} k
+   * Number of times the symbol appears in the query range at positions that are 1-bits in
+   * `alive`, a bit vector with a universe size equal to the length of this matrix that marks
+   * logically deleted elements with 0-bits. This supports deletion without rebuilding the matrix.
+   * The located occurrences are intersected with `alive` either by mapping each occurrence up to
+   * its index with `selectUpwards`, or by checking the symbol at each dead index in the range with
+   * `get`, whichever involves fewer elements, so deleting a few elements keeps this cheap.
+   * @param {number} symbol
+   * @param {BitVec} alive
    * @param {Object} [options]
    * @param {{ start: number; end: number; }} [options.range]
    */
-  quantile(k, { range = Range(0, this.length) } = {}) {
-    assert(0 <= k && k < this.length);
-    let symbol = 0;
-    for (const level of this.levels) {
-      let start = ranks(level, range.start);
-      let end = ranks(level, range.end);
-      let leftCount = end.zeros - start.zeros;
-      if (k < leftCount) {
-        // Go left
+  countAlive(symbol, alive, { range = Range(0, this.length) } = {}) {
+    assert(
+      alive.universeSize === this.length,
+      () => `expected the alive bit vector to have universe size ${this.length}, got ${alive.universeSize}`
+    );
+    assert(!alive.hasMultiplicity, 'the alive bit vector cannot have multiplicity');
+    if (symbol > this.maxSymbol) {
+      return 0;
+    }
+    const loc = this.locate(symbol, { range });
+    const count = loc.range.end - loc.range.start;
+    if (count === 0) {
+      return 0;
+    }
+    const firstDead = alive.rank0(range.start);
+    const numDead = alive.rank0(range.end) - firstDead;
+    if (numDead < count) {
+      let numDeadMatches = 0;
+      for (let n = firstDead; n < firstDead + numDead; n++) {
+        if (this.get(alive.select0(n)) === symbol) {
+          numDeadMatches++;
+        }
+      }
+      return count - numDeadMatches;
+    }
+    let numAlive = 0;
+    for (let i = loc.range.start; i < loc.range.end; i++) {
+      numAlive += alive.get(this.selectUpwards(i));
+    }
+    return numAlive;
+  }
+
+  /**
+   * Returns both `precedingCount(symbol, { range })`, the number of symbols less than `symbol`,
+   * and `count(symbol, { range })`, the number of occurrences of `symbol`, from a single
+   * traversal rather than the two that calling both would take. Together they give the
+   * positions `[precedingCount, precedingCount + count)` that the occurrences of the symbol
+   * would occupy if the range were sorted.
+   * @param {number} symbol
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  rankAndCount(symbol, { range = Range(0, this.length) } = {}) {
+    const loc = this.locate(symbol, { range });
+    return { precedingCount: loc.precedingCount, count: loc.range.end - loc.range.start };
+  }
+
+  /**
+   * Number of symbols less than or equal to `symbol` in the query range.
+   * Equivalent to `precedingCount(symbol) + count(symbol)`, but computed in a single
+   * descent that stops early once every symbol in the current node is known to be
+   * less than or equal to `symbol`.
+   * @param {number} symbol
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  countLessThanOrEqual(symbol, { range = Range(0, this.length) } = {}) {
+    if (symbol < 0 || rangeIsEmpty(range)) return 0;
+    // Symbols beyond the alphabet would have their high bits ignored by the level bits below.
+    symbol = Math.min(symbol, this.alphabetSize - 1);
+    let count = 0;
+    for (let i = 0; i < this.numLevels; i++) {
+      const level = this.levels[i];
+      // If all of the symbol's bits from this level downwards are set, then every symbol
+      // in the current node is less than or equal to it.
+      const lowMask = level.bit * 2 - 1;
+      if (u32(symbol & lowMask) === lowMask) {
+        return count + (range.end - range.start);
+      }
+      const start = ranks(level, range.start);
+      const end = ranks(level, range.end);
+      if ((symbol & level.bit) === 0) {
         range = Range(start.zeros, end.zeros);
       } else {
-        k -= leftCount;
-        symbol += level.bit;
+        // All symbols in the left child are less than `symbol`
+        count += end.zeros - start.zeros;
         range = Range(level.nz + start.ones, level.nz + end.ones);
       }
     }
-    let count = range.end - range.start;
+    return count + (range.end - range.start);
+  }
+
+  /**
+   * Returns the `k`-th smallest symbol in the index range along with its number of occurrences
+   * in the range. Consecutive values of `k` that fall within the occurrences of the same symbol
+   * return the same result; see `quantileWithOffset` to distinguish them.
+   * @param {number} k
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  quantile(k, { range = Range(0, this.length) } = {}) {
+    const { symbol, count } = this.quantileWithOffset(k, { range });
     return { symbol, count };
   }
 
+  /**
+   * Like `quantile`, but also returns the `offset` of the `k`-th smallest element among the
+   * occurrences of its symbol in the index range, in sequence order. Ties are broken by position,
+   * so the elements with `k` in `[k - offset, k - offset + count)` are the occurrences of `symbol`
+   * from left to right, and `offset` is `k - precedingCount(symbol, { range })`.
+   * @param {number} k
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  quantileWithOffset(k, { range = Range(0, this.length) } = {}) {
+    const { symbol, range: node, offset } = descendToQuantile(this, k, range);
+    return { symbol, count: node.end - node.start, offset };
+  }
+
+  /**
+   * Returns the index of the `k`-th smallest element in the index range, with ties broken by
+   * position, ie. the index at position `k` after stably sorting the range's elements by symbol.
+   * The descent ends at the node of the element's symbol on the bottom level, whose elements are
+   * in sequence order, so the element is at `offset` within that node and can be mapped back up
+   * with `selectUpwards` without locating the symbol again.
+   * @param {number} k
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  quantileSelect(k, { range = Range(0, this.length) } = {}) {
+    const { range: node, offset } = descendToQuantile(this, k, range);
+    return this.selectUpwards(node.start + offset);
+  }
+
   /**
    * This function abstracts the common second half of the select algorithm, once you've
    * identified an index on the "bottom" level and want to bubble it back up to translate
@@ -155,13 +386,23 @@ export class WaveletMatrix {
    * Note that this function returns absolute indices. So all functions that rely on it also
    * return absolute indices, even when the user passes in a range.
    * 
+   * The caller is responsible for ensuring that `index` is a valid position on the level
+   * that sits `ignoreBits` levels above the virtual bottom level (ie. in `[0, this.length)`),
+   * and that `ignoreBits` is in `[0, this.numLevels]`. See `trySelectUpwards` for a checked version.
+   * 
    * @param {number} index
    * @param {Object} [options]
    * @param {number} [options.ignoreBits]
    */
   selectUpwards(index, { ignoreBits = 0 } = {}) {
+    DEBUG && assertValidIgnoreBits(this, ignoreBits);
     for (let i = this.numLevels - ignoreBits; i-- > 0;) {
       const level = this.levels[i];
+      // On a level of all 0-bits, the `index`-th 0-bit is at `index`, and similarly
+      // for a level of all 1-bits, where `level.nz` is zero.
+      if (level.allZeros || level.allOnes) {
+        continue;
+      }
       // `index` represents an index on the level below this one, which may be
       // the bottom-most 'virtual' layer that contains all symbols in sorted order.
       //
@@ -189,6 +430,26 @@ export class WaveletMatrix {
     return index;
   }
 
+  /**
+   * Checked version of `selectUpwards` that returns `null` rather than throwing or
+   * returning a meaningless result when `index` is not a valid position on the starting
+   * level, or when `ignoreBits` is not in `[0, this.numLevels]`.
+   * 
+   * @param {number} index
+   * @param {Object} [options]
+   * @param {number} [options.ignoreBits]
+   */
+  trySelectUpwards(index, { ignoreBits = 0 } = {}) {
+    if (!Number.isInteger(ignoreBits) || ignoreBits < 0 || ignoreBits > this.numLevels) {
+      return null;
+    }
+    // Every level has the same length, so the valid positions are the same on all of them.
+    if (!Number.isInteger(index) || index < 0 || index >= this.length) {
+      return null;
+    }
+    return this.selectUpwards(index, { ignoreBits });
+  }
+
   /**
    * Return the index of the k-th occurrence of the symbol in this wavelet matrix.
    * Note that this returns an absolute index, even if a range is specified.
@@ -251,6 +512,41 @@ export class WaveletMatrix {
     return this.selectUpwards(index, { ignoreBits });
   }
 
+  /**
+   * Return the index of the k-th element (in sequence order) within the index range whose
+   * symbol lies in the half-open `symbolRange`, or `null` if there are at most `k` such elements.
+   * Like `select`, this returns an absolute index.
+   * 
+   * Since the matching elements are spread across many bottom-level nodes, their sequence
+   * order cannot be recovered with a single descent. Instead, we binary search for the
+   * shortest prefix of the range that contains `k + 1` matching elements, which takes
+   * O(log(range length)) band counts of O(numLevels) each.
+   * 
+   * @param {{ start: number; end: number; }} symbolRange
+   * @param {Object} [options]
+   * @param {number} [options.k]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  selectInSymbolRange(symbolRange, { k = 0, range = Range(0, this.length) } = {}) {
+    assert(Number.isInteger(k) && k >= 0, () => `k (${k}) must be a non-negative integer`);
+    if (rangeIsEmpty(range) || rangeIsEmpty(symbolRange)) {
+      return null;
+    }
+    // number of elements in the symbol range within the index range [range.start, end)
+    const countBefore = (/** @type {number} */ end) => {
+      const prefix = Range(range.start, end);
+      return this.countLessThanOrEqual(symbolRange.end - 1, { range: prefix })
+        - this.countLessThanOrEqual(symbolRange.start - 1, { range: prefix });
+    };
+    const length = range.end - range.start;
+    if (countBefore(range.end) <= k) {
+      return null;
+    }
+    // the offset of the first element whose inclusive prefix contains more than k matches
+    const offset = bits.partitionPoint(length, i => countBefore(range.start + i + 1) <= k);
+    return range.start + offset;
+  }
+
   /**
    * Returns the index of the first symbol less than `symbol` in the index range `range`.
    * ("First" here is based on sequence order; we will return the leftmost such index).
@@ -355,12 +651,41 @@ export class WaveletMatrix {
   //     frequency >25%", for power of two frequencies (or actually arbitrary ones, based on the quantiles...right?)
   // note: even more useful would be a k_majority_candidates function that returns all the samples, which can then be filtered down.
 
+  /**
+   * Index ranges of the left and right children of the node with index range `range` on level
+   * `level`, ie. the positions on the next level of the elements whose level bit is 0 and 1.
+   * This is the basic step of a top-down traversal; see `Traversal`.
+   * @param {number} level - level index in [0, numLevels)
+   * @param {{ start: number; end: number; }} range
+   */
+  childRanges(level, range) {
+    assert(Number.isInteger(level) && level >= 0 && level < this.numLevels, () => `level (${level}) must be an integer in [0, ${this.numLevels})`);
+    const { nz } = this.levels[level];
+    const start = ranks(this.levels[level], range.start);
+    const end = ranks(this.levels[level], range.end);
+    return {
+      left: Range(start.zeros, end.zeros),
+      right: Range(nz + start.ones, nz + end.ones),
+    };
+  }
+
   /**
    * @param {number} index
    */
   get(index) {
+    assert(index >= 0 && index < this.length, () => `index (${index}) is out of bounds for length (${this.length})`);
+    if (this.data !== null) {
+      return this.data[index];
+    }
     let symbol = 0;
     for (const level of this.levels) {
+      // Degenerate levels map `index` to itself on the level below
+      if (level.allZeros) {
+        continue;
+      } else if (level.allOnes) {
+        symbol += level.bit;
+        continue;
+      }
       if (level.bv.get(index) === 0) {
         // Go left
         index = level.bv.rank0(index);
@@ -372,148 +697,993 @@ export class WaveletMatrix {
     return symbol;
   }
 
+  /**
+   * Inverse of `select`: returns the symbol at `index` along with its occurrence rank `k` among
+   * the elements with that symbol, so that `select(symbol, { k })` returns `index`.
+   * This walks the levels like `get` does, additionally tracking the start of the node
+   * containing the element, at the cost of an extra rank operation per level.
+   * @param {number} index
+   */
+  inverseSelect(index) {
+    assert(index >= 0 && index < this.length, () => `index (${index}) is out of bounds for length (${this.length})`);
+    let symbol = 0;
+    let nodeStart = 0;
+    for (const level of this.levels) {
+      // Degenerate levels map `index` and `nodeStart` to themselves on the level below
+      if (level.allZeros) {
+        continue;
+      } else if (level.allOnes) {
+        symbol += level.bit;
+        continue;
+      }
+      if (level.bv.get(index) === 0) {
+        // Go left
+        index = level.bv.rank0(index);
+        nodeStart = level.bv.rank0(nodeStart);
+      } else {
+        symbol += level.bit;
+        index = level.nz + level.bv.rank1(index);
+        nodeStart = level.nz + level.bv.rank1(nodeStart);
+      }
+    }
+    return { symbol, k: index - nodeStart };
+  }
+
+  /**
+   * Return the symbols of this wavelet matrix in sequence order.
+   * This is a copy of the retained data if the matrix was constructed with `retainData`,
+   * and otherwise requires a `get` call per element.
+   */
+  toArray() {
+    if (this.data !== null) {
+      return this.data.slice();
+    }
+    const result = new Uint32Array(this.length);
+    for (let i = 0; i < this.length; i++) {
+      result[i] = this.get(i);
+    }
+    return result;
+  }
+
+  /**
+   * Check the structural invariants of the levels, throwing an error that names the level and
+   * the failed check if any do not hold. This is meant for testing new bit vector types, whose
+   * rank and select bugs would otherwise surface as wrong query answers. The checks are:
+   * - each level's bit vector has a universe size equal to the length of the matrix,
+   * - each level's `nz` and all-zeros and all-ones flags agree with its bit vector's counts,
+   *   and with a recomputed `rank0` at the end of the level,
+   * - the level bits are descending powers of two ending at 1,
+   * - for a sample of positions, walking the element down to the bottom level and mapping it back
+   *   up with `selectUpwards` returns the original position, and the symbol read off the levels
+   *   is at most `maxSymbol` and equal to `get` (which reads the retained data, if any).
+   * See `verifyAgainst` to check every element against the original data.
+   * @param {Object} [options]
+   * @param {number} [options.sampleSize] - number of positions to check, or Infinity for all of them
+   */
+  verify({ sampleSize = 64 } = {}) {
+    assert(this.levels.length === this.numLevels, () => `expected ${this.numLevels} levels, got ${this.levels.length}`);
+    assert(this.alphabetSize === 2 ** this.numLevels, () => `expected alphabetSize to be 2^${this.numLevels}, got ${this.alphabetSize}`);
+    for (const [i, level] of this.levels.entries()) {
+      /** @type {(check: boolean, message: () => string) => void} */
+      const check = (ok, message) => assert(ok, () => `level ${i}: ${message()}`);
+      const { bv } = level;
+      check(bv.universeSize === this.length, () => `expected universeSize ${this.length}, got ${bv.universeSize}`);
+      check(bv.numZeros + bv.numOnes === this.length, () => `expected numZeros + numOnes to be ${this.length}, got ${bv.numZeros} + ${bv.numOnes}`);
+      check(level.nz === bv.numZeros, () => `expected nz (${level.nz}) to equal numZeros (${bv.numZeros})`);
+      check(bv.rank0(this.length) === level.nz, () => `expected rank0(${this.length}) to equal nz (${level.nz}), got ${bv.rank0(this.length)}`);
+      check(level.allZeros === (bv.numOnes === 0), () => `allZeros (${level.allZeros}) disagrees with numOnes (${bv.numOnes})`);
+      check(level.allOnes === (bv.numZeros === 0), () => `allOnes (${level.allOnes}) disagrees with numZeros (${bv.numZeros})`);
+      const bit = 2 ** (this.numLevels - 1 - i);
+      check(level.bit === bit, () => `expected level bit ${bit}, got ${level.bit}`);
+    }
+    const step = Math.max(1, Math.floor(this.length / sampleSize));
+    for (let index = 0; index < this.length; index += step) {
+      verifyElement(this, index, null);
+    }
+    if (this.length > 0) {
+      verifyElement(this, this.length - 1, null);
+    }
+  }
+
+  /**
+   * Like `verify`, but also checks every element against `data`, the symbols the matrix was
+   * constructed from.
+   * @param {ArrayLike<number>} data
+   */
+  verifyAgainst(data) {
+    assert(data.length === this.length, () => `expected data of length ${this.length}, got ${data.length}`);
+    this.verify({ sampleSize: 0 });
+    for (let index = 0; index < this.length; index++) {
+      verifyElement(this, index, data[index]);
+    }
+  }
+
+  /**
+   * Number of boundaries between runs of equal symbols within the index range, ie. the number of
+   * positions `i` such that `i` and `i + 1` both lie in the range and `get(i) !== get(i + 1)`.
+   * Boundaries with elements outside of the range (between `start - 1` and `start`, or between
+   * `end - 1` and `end`) are excluded, so ranges of length 0 and 1 have no boundaries.
+   * @param {{ start: number; end: number; }} range
+   */
+  countBoundaries(range) {
+    let count = 0;
+    visitBoundaries(this, range.start, range.end, () => count++);
+    return count;
+  }
+
+  /**
+   * Returns the positions `i` in ascending order at which the symbol changes within
+   * the index range, ie. where `get(i) !== get(i + 1)`. See `countBoundaries`
+   * for the treatment of the range edges.
+   * @param {{ start: number; end: number; }} range
+   */
+  boundaries(range) {
+    /** @type {number[]} */
+    const result = [];
+    visitBoundaries(this, range.start, range.end, i => result.push(i));
+    return result;
+  }
+
+  /**
+   * Lazily yields `[index, symbol]` for each element of the index range whose symbol lies in
+   * `symbolRange`, in ascending index order. The index range is split in half until each piece
+   * contains only matches or none, using a pair of `countLessThanOrEqual` calls per piece, so the
+   * work done before each yielded element is O(numLevels * log(range length)) in the worst case,
+   * and a piece of consecutive matches is yielded at O(numLevels) per element. Since the pieces are
+   * visited depth-first, taking the first few matches of a broad filter is cheap.
+   * @param {{ start: number; end: number; }} range
+   * @param {{ start: number; end: number; }} symbolRange
+   * @returns {Generator<[number, number]>}
+   */
+  *matches(range, symbolRange) {
+    assert(
+      0 <= range.start && range.end <= this.length,
+      () => `range [${range.start}, ${range.end}) is out of bounds for length (${this.length})`
+    );
+    if (rangeIsEmpty(symbolRange)) {
+      return;
+    }
+    const count = (/** @type {{ start: number; end: number; }} */ r) =>
+      this.countLessThanOrEqual(symbolRange.end - 1, { range: r }) - this.countLessThanOrEqual(symbolRange.start - 1, { range: r });
+    // pieces still to be visited, with the leftmost on top
+    const stack = [range];
+    let piece;
+    while ((piece = stack.pop()) !== undefined) {
+      if (rangeIsEmpty(piece)) {
+        continue;
+      }
+      const n = count(piece);
+      if (n === piece.end - piece.start) {
+        for (let i = piece.start; i < piece.end; i++) {
+          yield [i, this.get(i)];
+        }
+      } else if (n > 0) {
+        const mid = piece.start + Math.floor((piece.end - piece.start) / 2);
+        stack.push(Range(mid, piece.end), Range(piece.start, mid));
+      }
+    }
+  }
+
+  /**
+   * Returns `[index, symbol]` for `min(k, number of matches)` elements sampled uniformly without
+   * replacement from the elements of the index range whose symbol lies in `symbolRange`, in
+   * ascending index order, eg. to draw a bounded number of random points from a selection.
+   * The sample is determined by the 32-bit integer `seed`. Each sampled element is located from
+   * its position within the nodes returned by `counts` with a `selectUpwards` call.
+   * @param {number} k
+   * @param {number} seed
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   * @param {{ start: number; end: number; }} [options.symbolRange]
+   * @returns {[number, number][]}
+   */
+  sampleMatches(k, seed, { range = Range(0, this.length), symbolRange = Range(0, this.alphabetSize) } = {}) {
+    const nodes = this.counts({ range, symbolRange });
+    const total = nodes.reduce((sum, node) => sum + node.end - node.start, 0);
+    const ordinals = sampleSortedDistinct(total, k, seed);
+    /** @type {[number, number][]} */
+    const result = [];
+    // the ordinals are sorted, so they can be matched to the nodes in a single pass
+    let n = 0;
+    let offset = 0;
+    for (const node of nodes) {
+      offset += node.end - node.start;
+      for (; n < ordinals.length && ordinals[n] < offset; n++) {
+        result.push([this.selectUpwards(node.end - (offset - ordinals[n])), node.symbol]);
+      }
+    }
+    return result.sort((a, b) => a[0] - b[0]);
+  }
+
   // todo: consider using extent for symbols, ie. [start, end], and MaskedExtents to avoid the extra sub/add instructions
   /**
-   * Returns the index of the first symbol less than `symbol` in the index range `range`.
-   * ("First" here is based on sequence order; we will return the leftmost such index).
+   * Returns the wavelet tree nodes on the bottom-most level (accounting for `ignoreBits`)
+   * that contain symbols in `symbolRange` within the index range `range`, as an array of
+   * `{ symbol, start, end }` objects, where `symbol` is the node's leftmost symbol and
+   * `end - start` is the number of such symbols within the query range.
    * 
-   * Implements the following logic:
-   * selectFirstLessThanOrEqual = (arr, symbol, lo, hi) => {
-   *   let i = arr.slice(lo, hi).findIndex((x) => x <= symbol);
-   *   return i === -1 ? null : lo + i;
-   * }
+   * Without ignored bits, each returned symbol occurs in the index range, lies in `symbolRange`,
+   * and is at most `maxSymbol`, even though the levels also represent the symbols between
+   * `maxSymbol` and the next power of two. With ignored bits, a node is returned if any of its
+   * symbols lies in `symbolRange`, so its leftmost symbol may lie below the range.
+   * 
+   * If `excludedSymbolRanges` is given, nodes whose symbols all lie within the excluded ranges
+   * are skipped. When bits are ignored, a bottom-level node spans multiple symbols, and it is
+   * returned (with its full count) unless all of its symbols are excluded.
+   * 
+   * The nodes are returned in the order in which they appear on the bottom level of the
+   * wavelet matrix, which is not ascending symbol order since the matrix orders symbols
+   * by their bit-reversed values. Pass `sortBySymbol: true` to sort them by symbol, at
+   * the cost of an additional O(n log n) sort of the n returned nodes.
    * 
    * @param {Object} [options]
    * @param {{ start: number; end: number; }} [options.range]
    * @param {{ start: number; end: number; }} [options.symbolRange]
    * @param {number | number[]} [options.ignoreBits]
+   * @param {{ start: number; end: number; }[]} [options.excludedSymbolRanges]
+   * @param {boolean} [options.sortBySymbol]
+   * @param {CancelSignal | null} [options.signal] - see `CancelSignal`
    */
-  counts({ range = Range(0, this.length), symbolRange = Range(0, this.maxSymbol + 1), ignoreBits = 0 } = {}) {
-    const masks = typeof ignoreBits === 'number'
-      ? ignoreBits === 0 ? this.defaultLevelMasks : this.defaultLevelMasks.slice(0, -ignoreBits)
-      : ignoreBits;
-
-    let xs = [{
-      symbol: 0, // the leftmost symbol in the current node
-      start: range.start, // index  range start
-      end: range.end // index range end
-    }];
-    let nextLeft = xs.slice(0, 0);  // create these empty arrays via slicing 
-    const nextRight = xs.slice(0, 0); // for type inference purposes
-
-    for (let i = 0; i < masks.length; i++) {
-      const mask = masks[i];
-      const level = this.levels[i];
-      const levelSymbolRange = MaskedRange(symbolRange.start, symbolRange.end, mask);
-
-      // cache `ranks` results when contiguous ranges share an endpoint
-      // (ie. next start === prev end)
-      let xEnd = 0; // cache key
-      let rankCache = ranks(level, xEnd); // cached value
-
-      for (const x of xs) {
-        // use the cache if the cache key matches
-        const start = x.start === xEnd ? rankCache : ranks(level, x.start);
-        const end = ranks(level, x.end);
-
-        // update the cache
-        xEnd = x.end;
-        rankCache = end; 
-
-        const symbol = x.symbol;
-        const { left, right } = childSymbolRanges(level, symbol, mask);
-
-        // if there are any left children, go left
-        if (start.zeros !== end.zeros && rangesOverlap(levelSymbolRange, left)) {
-          nextLeft.push({
-            symbol, 
-            start: start.zeros, 
-            end: end.zeros
-          }); 
-        }
+  counts({
+    range = Range(0, this.length),
+    symbolRange = Range(0, this.alphabetSize),
+    ignoreBits = 0,
+    excludedSymbolRanges = [],
+    sortBySymbol = false,
+    signal = null
+  } = {}) {
+    const excluded = coalesceRanges(excludedSymbolRanges);
+    const xs = traverseCounts(this, [range], symbolRange, ignoreBits, null, { excluded, signal }).xs;
+    return sortBySymbol ? xs.sort(bySymbol) : xs;
+  }
 
-        // if there are any right children, set the level bit and go right
-        if (start.ones !== end.ones && rangesOverlap(levelSymbolRange, right)) {
-          nextRight.push({
-            symbol: symbol + level.bit, 
-            start: level.nz + start.ones, 
-            end: level.nz + end.ones
-          });
+  /**
+   * Number of occurrences of each symbol in `symbolRange` within the index range, as an array
+   * of `[symbol, count]` pairs in ascending symbol order, omitting symbols that do not occur.
+   * This is `counts` in the shape most callers want, without the nodes' index ranges.
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   * @param {{ start: number; end: number; }} [options.symbolRange]
+   * @returns {[number, number][]}
+   */
+  symbolCounts({ range = Range(0, this.length), symbolRange = Range(0, this.maxSymbol + 1) } = {}) {
+    return this.counts({ range, symbolRange, sortBySymbol: true }).map(x => [x.symbol, x.end - x.start]);
+  }
+
+  /**
+   * Like `symbolCounts`, but with each count divided by the length of the index range, eg. for
+   * plotting the distribution of symbols. An empty index range has no symbols and so returns an
+   * empty array rather than dividing by zero.
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   * @param {{ start: number; end: number; }} [options.symbolRange]
+   * @returns {[number, number][]}
+   */
+  symbolFractions({ range = Range(0, this.length), symbolRange = Range(0, this.maxSymbol + 1) } = {}) {
+    if (rangeIsEmpty(range)) {
+      return [];
+    }
+    const rangeLength = range.end - range.start;
+    return this.symbolCounts({ range, symbolRange }).map(([symbol, count]) => [symbol, count / rangeLength]);
+  }
+
+  /**
+   * Indices of the occurrences of each symbol in `symbolRange` within the index range, as an
+   * array of `[symbol, indices]` pairs in ascending symbol order with each symbol's indices in
+   * ascending order, omitting symbols that do not occur. Each index takes a `selectUpwards` call
+   * over the symbol's bottom-level node, and the result holds one entry per element in the
+   * range with a matching symbol, so this can be large; see `sampleMatches` for a bounded
+   * alternative, or `symbolCounts` if only the counts are needed.
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   * @param {{ start: number; end: number; }} [options.symbolRange]
+   * @returns {[number, Uint32Array][]}
+   */
+  positionsBySymbol({ range = Range(0, this.length), symbolRange = Range(0, this.maxSymbol + 1) } = {}) {
+    return this.counts({ range, symbolRange, sortBySymbol: true }).map(x => {
+      const indices = new Uint32Array(x.end - x.start);
+      for (let i = 0; i < indices.length; i++) {
+        indices[i] = this.selectUpwards(x.start + i);
+      }
+      return [x.symbol, indices];
+    });
+  }
+
+  /**
+   * Number of elements in the index range whose symbol is not in any of the (possibly
+   * overlapping) symbol ranges in `excludedSymbolRanges`.
+   * @param {{ start: number; end: number; }[]} excludedSymbolRanges
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  countExcluding(excludedSymbolRanges, { range = Range(0, this.length) } = {}) {
+    let count = range.end - range.start;
+    for (const excluded of coalesceRanges(excludedSymbolRanges)) {
+      count -= this.countLessThanOrEqual(excluded.end - 1, { range });
+      count += this.countLessThanOrEqual(excluded.start - 1, { range });
+    }
+    return count;
+  }
+
+  /**
+   * Empirical cumulative distribution of the symbols in the index range. Returns one
+   * `{ symbol, cumulativeCount }` object per distinct symbol in `symbolRange` that occurs in
+   * the index range, in ascending symbol order, where `cumulativeCount` is the number of elements
+   * in the index range with symbols in `symbolRange` that are less than or equal to `symbol`.
+   * Use `countLessThanOrEqual` to evaluate the distribution at a single symbol.
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   * @param {{ start: number; end: number; }} [options.symbolRange]
+   */
+  cdf({ range = Range(0, this.length), symbolRange = Range(0, this.maxSymbol + 1) } = {}) {
+    let cumulativeCount = 0;
+    return this.counts({ range, symbolRange, sortBySymbol: true }).map(x => {
+      cumulativeCount += x.end - x.start;
+      return { symbol: x.symbol, cumulativeCount };
+    });
+  }
+
+  /**
+   * Histogram of symbol frequencies within the index range: returns an array whose `i`-th entry
+   * is the number of distinct symbols whose count in the range lies in the `i`-th bucket, where
+   * the buckets are `[1, bucketBounds[0])`, `[bucketBounds[0], bucketBounds[1])`, ..., and finally
+   * `[bucketBounds[bucketBounds.length - 1], Infinity)`. The bounds must be strictly increasing
+   * and greater than 1.
+   * 
+   * This avoids materializing a count for every distinct symbol where possible: a node containing
+   * a single element must hold a symbol that occurs once in the range, so it is counted without
+   * descending further. For data with many distinct symbols, most of the traversal ends early.
+   * @param {number[]} bucketBounds
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.range]
+   */
+  countFrequencyHistogram(bucketBounds, { range = Range(0, this.length) } = {}) {
+    for (let i = 0; i < bucketBounds.length; i++) {
+      const prev = i === 0 ? 1 : bucketBounds[i - 1];
+      assert(bucketBounds[i] > prev, () => `expected bucket bounds to be strictly increasing and greater than 1, got ${bucketBounds}`);
+    }
+    const histogram = new Array(bucketBounds.length + 1).fill(0);
+    const bucket = (/** @type {number} */ count) => bits.partitionPoint(bucketBounds.length, i => bucketBounds[i] <= count);
+    if (rangeIsEmpty(range)) {
+      return histogram;
+    }
+    let nodes = [Range(range.start, range.end)];
+    for (const level of this.levels) {
+      /** @type {{ start: number; end: number; }[]} */
+      const next = [];
+      for (const node of nodes) {
+        if (node.end - node.start === 1) {
+          histogram[bucket(1)]++;
+          continue;
+        }
+        const start = ranks(level, node.start);
+        const end = ranks(level, node.end);
+        if (start.zeros !== end.zeros) {
+          next.push(Range(start.zeros, end.zeros));
+        }
+        if (start.ones !== end.ones) {
+          next.push(Range(level.nz + start.ones, level.nz + end.ones));
         }
       }
+      nodes = next;
+    }
+    // each remaining node is a distinct symbol on the bottom level
+    for (const node of nodes) {
+      histogram[bucket(node.end - node.start)]++;
+    }
+    return histogram;
+  }
 
-      // swap xs and nextLeft
-      let tmp = xs;
-      xs = nextLeft;
-      nextLeft = tmp;
+  /**
+   * Like `counts`, but counts the symbols within the union of the index ranges `ranges`,
+   * which may overlap. Each element is counted once, even if it is contained in multiple ranges.
+   * Returns an array of `{ symbol, count }` objects in the same symbol order as `counts`.
+   * 
+   * @param {{ start: number; end: number; }[]} ranges
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.symbolRange]
+   * @param {number | number[]} [options.ignoreBits]
+   * @param {CancelSignal | null} [options.signal] - see `CancelSignal`
+   */
+  countsUnion(ranges, { symbolRange = Range(0, this.alphabetSize), ignoreBits = 0, signal = null } = {}) {
+    const { xs } = traverseCounts(this, coalesceRanges(ranges), symbolRange, ignoreBits, null, { signal });
+    // Since each level stably partitions the nodes, nodes with the same symbol
+    // that originate from different ranges are adjacent on the bottom level.
+    /** @type {{ symbol: number, count: number }[]} */
+    const result = [];
+    for (const x of xs) {
+      const last = result[result.length - 1];
+      if (last !== undefined && last.symbol === x.symbol) {
+        last.count += x.end - x.start;
+      } else {
+        result.push({ symbol: x.symbol, count: x.end - x.start });
+      }
+    }
+    return result;
+  }
 
-      // append the right to the left
-      for (let i = 0; i < nextRight.length; i++) {
-        xs.push(nextRight[i]);
+  /**
+   * Like `counts`, but for multiple index ranges at once, with the results attributed to
+   * the range they came from: returns an array whose `i`-th element contains the `counts`
+   * result for `ranges[i]`. All ranges are traversed together level by level, which allows
+   * rank results to be shared when one range starts where the previous one ends.
+   * As with `counts`, pass `sortBySymbol: true` to sort each range's nodes by symbol.
+   * 
+   * @param {{ start: number; end: number; }[]} ranges
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.symbolRange]
+   * @param {number | number[]} [options.ignoreBits]
+   * @param {boolean} [options.sortBySymbol]
+   * @param {CancelSignal | null} [options.signal] - see `CancelSignal`
+   */
+  countsByRange(ranges, { symbolRange = Range(0, this.alphabetSize), ignoreBits = 0, sortBySymbol = false, signal = null } = {}) {
+    const rangeKeys = ranges.map((_, i) => i);
+    const { xs, keys } = traverseCounts(this, ranges, symbolRange, ignoreBits, rangeKeys, { signal });
+    /** @type {{ symbol: number; start: number; end: number; }[][]} */
+    const result = ranges.map(() => []);
+    for (let i = 0; i < xs.length; i++) {
+      // @ts-ignore because `keys` is non-null when we pass in non-null keys
+      result[keys[i]].push(xs[i]);
+    }
+    if (sortBySymbol) {
+      for (const nodes of result) {
+        nodes.sort(bySymbol);
       }
+    }
+    return result;
+  }
 
-      // clear both for the next iteration
-      nextLeft.length = nextRight.length = 0;
+  /**
+   * Like `countsByRange`, but returns the results as `Columns` with the columns `key` (the index
+   * of the range in `ranges`), `symbol` and `count`, which are filled directly from the traversal.
+   * The rows are in traversal order unless `ordered` is true, in which case they are sorted by
+   * key and then by symbol.
+   * 
+   * @param {{ start: number; end: number; }[]} ranges
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.symbolRange]
+   * @param {number | number[]} [options.ignoreBits]
+   * @param {boolean} [options.ordered]
+   * @param {CancelSignal | null} [options.signal] - see `CancelSignal`
+   */
+  countsColumnar(ranges, { symbolRange = Range(0, this.alphabetSize), ignoreBits = 0, ordered = false, signal = null } = {}) {
+    const rangeKeys = ranges.map((_, i) => i);
+    const { xs, keys } = traverseCounts(this, ranges, symbolRange, ignoreBits, rangeKeys, { signal });
+    const columns = new Columns(['key', 'symbol', 'count'], xs.length);
+    for (let i = 0; i < xs.length; i++) {
+      const x = xs[i];
+      // @ts-ignore because `keys` is non-null when we pass in non-null keys
+      columns.push(keys[i], x.symbol, x.end - x.start);
+    }
+    if (ordered) {
+      columns.sortBy('key', 'symbol');
+    }
+    return columns;
+  }
+
+  /**
+   * Returns a smaller wavelet matrix containing every `2^shift`-th element of this one,
+   * starting with the first. It can be used with `approximateCounts` to answer
+   * interactive queries over large ranges at a fraction of the cost of exact counts.
+   * The returned matrix records its sampling step in `sampleShift`, so decimating an
+   * already-decimated matrix accumulates the shifts.
+   * @param {number} shift
+   */
+  decimate(shift) {
+    assert(shift >= 0 && shift < 32, 'shift must be in [0, 32)');
+    const step = 2 ** shift;
+    /** @type {number[]} */
+    const data = [];
+    for (let i = 0; i < this.length; i += step) {
+      data.push(this.get(i));
+    }
+    const decimated = new WaveletMatrix(data, this.maxSymbol);
+    decimated.sampleShift = this.sampleShift + shift;
+    return decimated;
+  }
+
+  /**
+   * Approximate `counts` for each of the index ranges `ranges`, which refer to positions in the
+   * original (undecimated) matrix. Only the sampled positions within each range are counted.
+   * Returns an array whose `i`-th element contains the `{ symbol, count }` estimates for `ranges[i]`,
+   * in which each count is obtained by scaling the sampled count by `2^sampleShift`.
+   * The result is only as accurate as the sample is representative of the range; counts of rare
+   * symbols and counts over ranges that are short relative to the sampling step may be far off.
+   * On a matrix that was not produced by `decimate`, the counts are exact.
+   * 
+   * @param {{ start: number; end: number; }[]} ranges - index ranges in the original matrix
+   * @param {Object} [options]
+   * @param {{ start: number; end: number; }} [options.symbolRange]
+   * @param {number | number[]} [options.ignoreBits]
+   * @param {CancelSignal | null} [options.signal] - see `CancelSignal`
+   */
+  approximateCounts(ranges, { symbolRange = Range(0, this.alphabetSize), ignoreBits = 0, signal = null } = {}) {
+    const step = 2 ** this.sampleShift;
+    // The sampled positions are the multiples of `step`, so each decimated range
+    // covers those multiples that lie within the original range.
+    const sampledRanges = ranges.map(range => Range(
+      Math.min(Math.ceil(range.start / step), this.length),
+      Math.min(Math.ceil(range.end / step), this.length)
+    ));
+    return this.countsByRange(sampledRanges, { symbolRange, ignoreBits, signal }).map(xs =>
+      xs.map(x => ({ symbol: x.symbol, count: (x.end - x.start) * step }))
+    );
+  }
+}
+
+/**
+ * Implements `counts` for a list of index ranges, which are traversed together.
+ * Nodes originating from different ranges are not merged, so overlapping ranges
+ * will count their shared elements once per range.
+ * 
+ * If `keys` is non-null, it should contain one key per range, and the returned `keys`
+ * array will contain the key of the range each returned node originated from.
+ * 
+ * Nodes whose symbols lie entirely within one of the `excluded` symbol ranges are pruned.
+ * The excluded ranges must be sorted and disjoint, eg. as returned by `coalesceRanges`.
+ * 
+ * If a `signal` is given, it is checked at the start of each level and periodically within each level.
+ * 
+ * When `keys` is null, nodes that share a symbol and have adjacent index ranges (which happens
+ * when multiple input ranges are separated only by elements that went to the other child) are
+ * merged, so that each merged node is only visited once on the levels below.
+ * @param {WaveletMatrix} wm
+ * @param {{ start: number; end: number; }[]} ranges
+ * @param {{ start: number; end: number; }} symbolRange
+ * @param {number | number[]} ignoreBits
+ * @param {number[] | null} keys
+ * @param {Object} [options]
+ * @param {{ start: number; end: number; }[]} [options.excluded]
+ * @param {CancelSignal | null} [options.signal]
+ */
+function traverseCounts(wm, ranges, symbolRange, ignoreBits, keys, { excluded = [], signal = null } = {}) {
+  throwIfCancelled(signal);
+  // An inverted symbol range is almost certainly a bug in the caller, so reject it rather
+  // than silently returning no results.
+  assert(
+    symbolRange.start <= symbolRange.end,
+    () => `symbolRange start (${symbolRange.start}) must not exceed its end (${symbolRange.end})`
+  );
+  // Validate ignoreBits before any early return, so that invalid arguments are rejected
+  // regardless of whether the query happens to be trivial.
+  if (typeof ignoreBits === 'number') {
+    assertValidIgnoreBits(wm, ignoreBits);
+  } else {
+    assert(ignoreBits.length <= wm.numLevels, () => `expected at most ${wm.numLevels} level masks, got ${ignoreBits.length}`);
+  }
+  // Clamp the symbol range to the symbols that can exist in this wavelet matrix. Besides
+  // tightening the search, this prevents symbol range endpoints at or beyond 2^32 from wrapping
+  // around when they are masked with 32-bit bitwise operations. With level masks, the range
+  // endpoints encode the corners of a box, and clamping the end to an arbitrary symbol would
+  // change its masked components, so in that case we only clamp to the alphabet size.
+  // Subtrees beyond `maxSymbol` are still pruned, since their nodes are empty.
+  const maxEnd = typeof ignoreBits === 'number' ? wm.maxSymbol + 1 : wm.alphabetSize;
+  const isSingleSymbol = symbolRange.end - symbolRange.start === 1;
+  symbolRange = Range(Math.max(0, symbolRange.start), Math.min(symbolRange.end, maxEnd));
+  if (rangeIsEmpty(symbolRange)) {
+    return { xs: [], keys: keys === null ? null : [] };
+  }
+
+  // If only a single symbol is requested, each range maps to at most one node on the
+  // bottom level, which we can find directly without the per-level overlap checks.
+  // We check the requested range rather than the clamped one, since a range that only
+  // becomes a single symbol by clamping, eg. [maxSymbol, 2^32), is usually cheaper to
+  // traverse: the traversal stops at empty nodes and shares ranks between input ranges.
+  if (ignoreBits === 0 && isSingleSymbol) {
+    const symbol = symbolRange.start;
+    const xs = [];
+    /** @type {number[] | null} */
+    const xKeys = keys === null ? null : [];
+    if (isExcluded(excluded, symbolRange)) {
+      return { xs, keys: xKeys };
     }
-    return xs;
+    for (let j = 0; j < ranges.length; j++) {
+      const { range } = wm.locate(symbol, { range: ranges[j] });
+      if (!rangeIsEmpty(range)) {
+        xs.push({ symbol, start: range.start, end: range.end });
+        if (xKeys !== null && keys !== null) xKeys.push(keys[j]);
+      }
+    }
+    return { xs, keys: xKeys };
   }
+
+  const masks = typeof ignoreBits === 'number'
+    ? ignoreBits === 0 ? wm.defaultLevelMasks : wm.defaultLevelMasks.slice(0, -ignoreBits)
+    : ignoreBits;
+  const levelSymbolRanges = masks.map(mask => MaskedRange(symbolRange.start, symbolRange.end, mask));
+
+  // Each node carries the key of the range it originated from, using -1 if we aren't tracking keys
+  // so that the node objects keep a single shape.
+  const traversal = new Traversal(ranges.map((range, i) => ({
+    key: keys === null ? -1 : keys[i],
+    symbol: 0, // the leftmost symbol in the current node
+    start: range.start, // index  range start
+    end: range.end // index range end
+  })), { merge: keys === null ? mergeAdjacent : null });
+
+  let currentLevel = -1; // index of the level being traversed
+  let j = 0; // index of the current node within its level
+
+  // cache `ranks` results when contiguous ranges share an endpoint
+  // (ie. next start === prev end) on the same level
+  let xEnd = -1; // cache key
+  let rankCache = { zeros: 0, ones: 0 }; // cached value
+
+  // With multiple input ranges, nodes can also share endpoints that are not adjacent on the
+  // level, eg. when many ranges end at the same index, so we memoize the ranks at every
+  // endpoint seen on the current level. A single range has no such repeats, so it skips the memo.
+  /** @type {Map<number, { zeros: number, ones: number }> | null} */
+  const memo = ranges.length > 1 ? new Map() : null;
+  const rankAt = (/** @type {{ nz: number; bit: number; bv: BitVec; allZeros: boolean; allOnes: boolean }} */ level, /** @type {number} */ index) => {
+    if (memo === null) {
+      return ranks(level, index);
+    }
+    let result = memo.get(index);
+    if (result === undefined) {
+      result = ranks(level, index);
+      memo.set(index, result);
+    }
+    return result;
+  };
+
+  traversal.traverseLevels(wm.levels.slice(0, masks.length), (x, go, { level, index }) => {
+    if (index !== currentLevel) {
+      // this is the first node on a new level, so check for cancellation and reset the caches
+      throwIfCancelled(signal);
+      currentLevel = index;
+      j = 0;
+      xEnd = -1;
+      if (memo !== null) memo.clear();
+    } else if ((++j & (CancelCheckInterval - 1)) === CancelCheckInterval - 1) {
+      // check for cancellation periodically on wide levels
+      throwIfCancelled(signal);
+    }
+
+    // use the cache if the cache key matches
+    const start = x.start === xEnd ? rankCache : rankAt(level, x.start);
+    const end = rankAt(level, x.end);
+
+    // update the cache
+    xEnd = x.end;
+    rankCache = end;
+
+    const symbol = x.symbol;
+    const { left, right } = childSymbolRanges(level, symbol, masks[index]);
+    const { mid, right: rightEnd } = split(level, symbol);
+
+    // if there are any left children, go left
+    if (
+      start.zeros !== end.zeros &&
+      rangesOverlap(levelSymbolRanges[index], left) &&
+      !isExcluded(excluded, Range(symbol, mid))
+    ) {
+      go.left({
+        key: x.key,
+        symbol,
+        start: start.zeros,
+        end: end.zeros
+      });
+    }
+
+    // if there are any right children, set the level bit and go right
+    if (
+      start.ones !== end.ones &&
+      rangesOverlap(levelSymbolRanges[index], right) &&
+      !isExcluded(excluded, Range(mid, rightEnd))
+    ) {
+      go.right({
+        key: x.key,
+        symbol: symbol + level.bit,
+        start: level.nz + start.ones,
+        end: level.nz + end.ones
+      });
+    }
+  });
+
+  const xs = [];
+  const xKeys = keys === null ? null : [];
+  for (const { key, symbol, start, end } of traversal.results()) {
+    xs.push({ symbol, start, end });
+    if (xKeys !== null) xKeys.push(key);
+  }
+  // On the bottom level, each node holds a single symbol, which lies in the symbol range since
+  // its node overlapped the range, and which occurs in the data since its node is nonempty.
+  DEBUG && ignoreBits === 0 && assert(
+    xs.every(x => x.symbol >= symbolRange.start && x.symbol < symbolRange.end && x.symbol <= wm.maxSymbol),
+    'expected every node on the bottom level to lie within the symbol range'
+  );
+  return { xs, keys: xKeys };
 }
 
 /**
+ * Descend from the top level towards the `k`-th smallest symbol in the index range. Returns
+ * the symbol, the index range of its occurrences on the bottom level, and the remaining
+ * value of `k`, which is the offset of the `k`-th smallest element within that range.
+ * @param {WaveletMatrix} wm
+ * @param {number} k
  * @param {{ start: number; end: number; }} range
  */
-function rangeIsEmpty(range) {
-  return !(range.start < range.end);
+function descendToQuantile(wm, k, range) {
+  const rangeLength = range.end - range.start;
+  assert(0 <= k && k < rangeLength, () => `k (${k}) must be in [0, ${rangeLength}), the length of the range`);
+  let symbol = 0;
+  for (const level of wm.levels) {
+    let start = ranks(level, range.start);
+    let end = ranks(level, range.end);
+    let leftCount = end.zeros - start.zeros;
+    if (k < leftCount) {
+      // Go left
+      range = Range(start.zeros, end.zeros);
+    } else {
+      k -= leftCount;
+      symbol += level.bit;
+      range = Range(level.nz + start.ones, level.nz + end.ones);
+    }
+  }
+  return { symbol, range, offset: k };
 }
 
-// todo: document
 /**
- * @param {{ nz: number; bit: number; bv: BitVec; }} level
+ * Extend the node `last` to include `next` if they have the same symbol and
+ * the index range of `last` ends where that of `next` starts.
+ * Returns true if the nodes were merged.
+ * @param {{ symbol: number; start: number; end: number; }} last
+ * @param {{ symbol: number; start: number; end: number; }} next
+ */
+function mergeAdjacent(last, next) {
+  if (last.symbol === next.symbol && last.end === next.start) {
+    last.end = next.end;
+    return true;
+  }
+  return false;
+}
+
+/**
+ * Set the fields of a wavelet matrix from its per-level bit vectors, ordered from the top level down.
+ * @param {WaveletMatrix} wm
+ * @param {BitVec[]} bitVecs
+ * @param {number} maxSymbol
+ * @param {Uint32Array | null} data - retained copy of the data, if any
+ */
+function initialize(wm, bitVecs, maxSymbol, data) {
+  assert(bitVecs.length > 0);
+  wm.maxSymbol = maxSymbol;
+  wm.numLevels = bitVecs.length;
+  wm.maxLevel = wm.numLevels - 1;
+  wm.alphabetSize = 2 ** wm.numLevels;
+  wm.length = bitVecs[0].universeSize;
+  wm.levels = bitVecs.map((bv, index) => ({
+    nz: bv.numZeros,
+    bit: u32(1 << (wm.maxLevel - index)),
+    bv,
+    // Levels consisting entirely of 0-bits (or 1-bits) map every index to the same
+    // index on the level below, so we can skip rank and select operations on them.
+    // This happens eg. when maxSymbol is much smaller than the next power of two.
+    allZeros: bv.numOnes === 0,
+    allOnes: bv.numZeros === 0,
+  }));
+  wm.defaultLevelMasks = bitVecs.map(() => bits.oneMask(32));
+  // The log2 of the sampling step of a matrix produced by `decimate`, or 0 otherwise
+  wm.sampleShift = 0;
+  /**
+   * The retained copy of the input data, if requested.
+   * @type {Uint32Array | null}
+   */
+  wm.data = data;
+}
+
+/**
+ * Check a single element for `verify`: walk it down the levels, reading its symbol along the way,
+ * and map its bottom-level position back up with `selectUpwards`.
+ * @param {WaveletMatrix} wm
  * @param {number} index
+ * @param {number | null} expected - the element's symbol, if known
  */
-function ranks(level, index) {
-  let numOnes = level.bv.rank1(index);
-  let numZeros = index - numOnes;
-  return { zeros: numZeros, ones: numOnes };
+function verifyElement(wm, index, expected) {
+  let position = index;
+  let symbol = 0;
+  for (const [i, level] of wm.levels.entries()) {
+    const bit = level.bv.get(position);
+    assert(bit === 0 || bit === 1, () => `level ${i}: expected get(${position}) to be 0 or 1, got ${bit}`);
+    const rank1 = level.bv.rank1(position);
+    if (bit === 0) {
+      position = position - rank1;
+    } else {
+      symbol += level.bit;
+      position = level.nz + rank1;
+    }
+    assert(position < wm.length, () => `level ${i}: element ${index} maps to position ${position} on the level below, beyond the length ${wm.length}`);
+  }
+  assert(symbol <= wm.maxSymbol, () => `element ${index} has symbol ${symbol}, which exceeds maxSymbol (${wm.maxSymbol})`);
+  assert(wm.get(index) === symbol, () => `element ${index} has symbol ${symbol} on the levels, but get returns ${wm.get(index)}`);
+  assert(expected === null || symbol === expected, () => `element ${index} has symbol ${symbol}, expected ${expected}`);
+  const up = wm.selectUpwards(position);
+  assert(up === index, () => `element ${index} is at bottom-level position ${position}, which selectUpwards maps to ${up}`);
 }
 
-// todo: document
 /**
- * @param {number} start
- * @param {number} end
+ * Debug check that `f` is monotone non-decreasing on a sample of the symbols in `data`.
+ * @param {(symbol: number) => number} f
+ * @param {ArrayLike<number>} data
  */
-function Range(start, end) {
-  return { start, end };
+function assertMonotoneOnSample(f, data) {
+  const step = Math.max(1, Math.floor(data.length / 64));
+  const sample = [];
+  for (let i = 0; i < data.length; i += step) {
+    sample.push(data[i]);
+  }
+  sample.sort(ascending);
+  for (let i = 1; i < sample.length; i++) {
+    const a = sample[i - 1];
+    const b = sample[i];
+    assert(f(a) <= f(b), () => `expected a monotone non-decreasing symbol mapping, but f(${a}) > f(${b})`);
+  }
 }
 
-// todo: document
 /**
- * @param {{ start: number; end: number; }} a
- * @param {{ end: number; start: number; }} b
+ * Throws an error unless `ignoreBits` is an integer in `[0, wm.numLevels]`.
+ * @param {WaveletMatrix} wm
+ * @param {number} ignoreBits
  */
-function rangesOverlap(a, b) {
-  return a.start < b.end && b.start < a.end;
+function assertValidIgnoreBits(wm, ignoreBits) {
+  assert(
+    Number.isInteger(ignoreBits) && ignoreBits >= 0 && ignoreBits <= wm.numLevels,
+    () => `ignoreBits (${ignoreBits}) must be an integer in [0, ${wm.numLevels}]`
+  );
 }
 
 /**
- * Return true if range `a` fully contains range `b`
- * @param {{ start: number; end: number; }} a
- * @param {{ start: number; end: number; }} b
+ * Calls `visit(i)` in ascending order for each position `i` in `[start, end - 1)` at which
+ * the symbol differs from the one at `i + 1`. Ranges are bisected until they are either
+ * constant, which prunes them, or consist of a single adjacent pair. Each constancy check
+ * descends the levels only until the range's bits first disagree, so runs of equal symbols
+ * are skipped in logarithmic time while high-entropy ranges are rejected after a level or two.
+ * @param {WaveletMatrix} wm
+ * @param {number} start
+ * @param {number} end
+ * @param {(index: number) => void} visit
  */
-function rangeFullyContains(a, b) {
-  // if a starts before b, and a ends after b.
-  return a.start <= b.start && a.end >= b.end;
+function visitBoundaries(wm, start, end, visit) {
+  if (end - start < 2 || isConstant(wm, start, end)) return;
+  if (end - start === 2) {
+    visit(start);
+    return;
+  }
+  // The halves share the element at `mid`, so that the pair (mid - 1, mid) is
+  // considered by the left half and the pair (mid, mid + 1) by the right.
+  const mid = start + ((end - start) >>> 1);
+  visitBoundaries(wm, start, mid + 1, visit);
+  visitBoundaries(wm, mid, end, visit);
 }
 
-// todo: document
 /**
+ * Returns true if every element in the non-empty index range `[start, end)` has the same symbol.
+ * @param {WaveletMatrix} wm
  * @param {number} start
  * @param {number} end
- * @param {number} mask
  */
-function MaskedRange(start, end, mask) {
-  // The end is exclusive so we need to make sure to mask the true endpoint.
-  return { start: start & mask, end: u32((end - 1) & mask) + 1 };
+function isConstant(wm, start, end) {
+  for (const level of wm.levels) {
+    const s = ranks(level, start);
+    const e = ranks(level, end);
+    if (s.ones === e.ones) {
+      // all 0-bits; go left
+      start = s.zeros;
+      end = e.zeros;
+    } else if (s.zeros === e.zeros) {
+      // all 1-bits; go right
+      start = level.nz + s.ones;
+      end = level.nz + e.ones;
+    } else {
+      return false;
+    }
+  }
+  return true;
+}
+
+/**
+ * Number of nodes to process between cancellation checks within a single level.
+ * Must be a power of two.
+ */
+const CancelCheckInterval = 1024;
+
+/**
+ * Throws if the signal indicates that the query should be cancelled.
+ * @param {CancelSignal | null} signal
+ */
+function throwIfCancelled(signal) {
+  if (signal !== null && signal.aborted) {
+    throw signal.reason ?? new Error('the query was cancelled');
+  }
+}
+
+/**
+ * Comparator that orders nodes by ascending symbol.
+ * @param {{ symbol: number }} a
+ * @param {{ symbol: number }} b
+ */
+function bySymbol(a, b) {
+  return a.symbol - b.symbol;
+}
+
+/**
+ * Returns true if the symbol range `range` is fully contained in one of the `excluded` ranges,
+ * which must be sorted and disjoint.
+ * @param {{ start: number; end: number; }[]} excluded
+ * @param {{ start: number; end: number; }} range
+ */
+function isExcluded(excluded, range) {
+  if (excluded.length === 0) return false;
+  // Find the last excluded range that starts at or before `range`
+  const i = bits.partitionPoint(excluded.length, i => excluded[i].start <= range.start) - 1;
+  return i >= 0 && rangeFullyContains(excluded[i], range);
+}
+
+/**
+ * Join two co-indexed wavelet matrices, ie. ones whose `i`-th elements describe the same item:
+ * for each symbol in `symbols`, finds its index range in `primary`, and counts the symbols of
+ * `secondary` over the union of those ranges. Returns a map from secondary symbol to count,
+ * in ascending symbol order. Each element is counted once even if `symbols` contains repeats.
+ * 
+ * The primary matrix must be sorted by symbol, so that each of its symbols occupies a
+ * contiguous index range. For example, with items sorted by id, `primary` can store the ids
+ * and `secondary` an attribute of each item, and this counts the attribute values for a set of ids.
+ * @param {WaveletMatrix} primary - matrix whose symbols are in non-decreasing order
+ * @param {WaveletMatrix} secondary - matrix of the same length as `primary`
+ * @param {number[]} symbols - primary symbols to look up
+ */
+export function joinCounts(primary, secondary, symbols) {
+  assert(primary.length === secondary.length, () => `expected co-indexed matrices, but their lengths differ (${primary.length} and ${secondary.length})`);
+  const ranges = [];
+  for (const symbol of symbols) {
+    if (symbol > primary.maxSymbol) continue;
+    // Since the primary matrix is sorted, the symbols less than `symbol` precede it
+    const { precedingCount, range } = primary.locate(symbol);
+    ranges.push(Range(precedingCount, precedingCount + range.end - range.start));
+  }
+  const counts = secondary.countsUnion(ranges).sort(bySymbol);
+  return new Map(counts.map(({ symbol, count }) => [symbol, count]));
+}
+
+/**
+ * Sort the given ranges and merge any that overlap or touch, dropping empty ranges.
+ * The returned ranges are disjoint and in ascending order.
+ * This is useful to normalize symbol ranges as well as index ranges.
+ * @param {{ start: number; end: number; }[]} ranges
+ */
+export function coalesceRanges(ranges) {
+  const sorted = ranges.filter(r => !rangeIsEmpty(r)).sort((a, b) => a.start - b.start);
+  /** @type {{ start: number; end: number; }[]} */
+  const result = [];
+  for (const r of sorted) {
+    const last = result[result.length - 1];
+    if (last !== undefined && r.start <= last.end) {
+      last.end = Math.max(last.end, r.end);
+    } else {
+      result.push(Range(r.start, r.end));
+    }
+  }
+  return result;
+}
+
+/**
+ * Returns the number of 0-bits and 1-bits preceding `index` on the given level,
+ * avoiding the rank operation on degenerate levels of all 0-bits or all 1-bits.
+ * @param {{ nz: number; bit: number; bv: BitVec; allZeros: boolean; allOnes: boolean }} level
+ * @param {number} index
+ */
+function ranks(level, index) {
+  if (level.allZeros) {
+    return { zeros: index, ones: 0 };
+  } else if (level.allOnes) {
+    return { zeros: 0, ones: index };
+  } else if (index === level.bv.universeSize) {
+    // The ranks at the end of the level are its total counts
+    return { zeros: level.nz, ones: index - level.nz };
+  }
+  let numOnes = level.bv.rank1(index);
+  let numZeros = index - numOnes;
+  return { zeros: numZeros, ones: numOnes };
 }
 
 // todo: document
@@ -552,17 +1722,73 @@ function childSymbolRanges(level, leftSymbol, mask) {
   };
 }
 
+/**
+ * Returns the level masks for a wavelet matrix over `numDims`-dimensional Morton codes, for use
+ * as the `ignoreBits` option of the counting functions. The mask of each level selects the bits
+ * of the dimension that its level bit belongs to. Since Morton codes interleave the dimensions
+ * starting from the least significant bit, which belongs to the first dimension, the masks are
+ * assigned from the bottom level upward: the bottom level has the first dimension's mask, the
+ * level above has the second's, and so on. This way, each level is paired with the right
+ * dimension regardless of the number of levels, which depends on the largest code in the data.
+ * @param {number} numLevels
+ * @param {number} numDims
+ */
+export function mortonLevelMasks(numLevels, numDims) {
+  assert(Number.isInteger(numDims) && numDims >= 1 && numDims <= 32, () => `expected numDims in [1, 32], got ${numDims}`);
+  const dimMasks = Array.from({ length: numDims }, (_, dim) => {
+    let mask = 0;
+    for (let bit = dim; bit < 32; bit += numDims) mask |= 1 << bit;
+    return u32(mask);
+  });
+  // Level `i` from the top has the level bit `2^(numLevels - 1 - i)`
+  return Array.from({ length: numLevels }, (_, i) => dimMasks[(numLevels - 1 - i) % numDims]);
+}
+
+/**
+ * @param {ArrayLike<number>[]} coordinates - one array per dimension
+ * @param {number} bitsPerDim
+ */
+function checkCoordinates(coordinates, bitsPerDim) {
+  const length = coordinates[0].length;
+  for (const [dim, xs] of coordinates.entries()) {
+    assert(xs.length === length, () => `expected coordinate arrays of length ${length}, got ${xs.length} in dimension ${dim}`);
+    for (let i = 0; i < xs.length; i++) {
+      const x = xs[i];
+      assert(Number.isInteger(x) && x >= 0 && x < 2 ** bitsPerDim, () => `expected coordinates in [0, 2^${bitsPerDim}), got ${x} at index ${i} in dimension ${dim}`);
+    }
+  }
+}
+
+/**
+ * Returns the name of the construction algorithm used for a wavelet matrix over `length` elements
+ * with `numLevels` levels, which is the one that needs the least scratch space (beyond the levels
+ * themselves, which are the same size either way). Ties go to the histogram algorithm since it
+ * does less work per level.
+ * - 'histogram' (`buildBitVecsSmallAlphabet`) needs one 32-bit counter per symbol, ie. 4 * 2^numLevels bytes.
+ * - 'sort' (`buildBitVecsLargeAlphabet`) needs two 32-bit copies of the data, ie. 8 * length bytes.
+ * @param {number} length
+ * @param {number} numLevels
+ * @returns {'histogram' | 'sort'}
+ */
+export function constructionStrategy(length, numLevels) {
+  const histogramBytes = 4 * 2 ** numLevels;
+  const sortBytes = 8 * length;
+  return histogramBytes <= sortBytes ? 'histogram' : 'sort';
+}
+
 /**
  * Wavelet matrix construction algorithm that takes space proportional to the alphabet size (which is 2^numLevels).
  * Algorithm 1 (seq.pc) the paper "Practical Wavelet Tree Construction" (see link in comment at the top of this file)
- * @param {number[]} data
+ * @param {number[] | Uint32Array} data
  * @param {number} numLevels
+ * @param {ProgressReporter | null} report
  */
-function buildBitVecsSmallAlphabet(data, numLevels) {
+function buildBitVecsSmallAlphabet(data, numLevels, report) {
   assert(numLevels > 0);
   const levels = Array.from({ length: numLevels }, () => new BitBuf(data.length));
-  const hist = new Uint32Array(u32(1 << numLevels));
-  const borders = new Uint32Array(u32(1 << numLevels));
+  // The histogram starts with one entry per symbol and is halved in place for each level,
+  // so after halving the upper half of the array is free and we use it for the borders.
+  const hist = new Uint32Array(2 ** numLevels);
   const maxLevel = numLevels - 1;
 
   {
@@ -571,9 +1797,10 @@ function buildBitVecsSmallAlphabet(data, numLevels) {
     const level = levels[0];
     const levelBit = u32(1 << maxLevel);
     for (let i = 0; i < data.length; i++) {
+      if (report !== null && (i & ProgressIntervalMask) === 0) report(0, i);
       const d = data[i];
       hist[d] += 1;
-      if ((d & levelBit) > 0) {
+      if ((d & levelBit) !== 0) {
         level.setOne(i);
       }
     }
@@ -589,6 +1816,7 @@ function buildBitVecsSmallAlphabet(data, numLevels) {
       // Update the histogram in-place
       hist[i] = hist[2 * i] + hist[2 * i + 1];
     }
+    const borders = hist.subarray(numNodes, 2 * numNodes);
 
     // Get starting positions of intervals from the new histogram
     borders[0] = 0;
@@ -609,20 +1837,25 @@ function buildBitVecsSmallAlphabet(data, numLevels) {
     // This is a bit subtle since the negation operates only on the 32-bit value,
     // but this works so long as we never build elements with value >= 2^32
     const bitPrefixMask = ~oneMask(levelBitIndex);
-    for (const d of data) {
+    // Level 0 was filled first, so the levels done so far are 0 and l + 1, ..., maxLevel
+    const levelsDone = numLevels - l;
+    for (let i = 0; i < data.length; i++) {
+      if (report !== null && (i & ProgressIntervalMask) === 0) report(levelsDone, i);
+      const d = data[i];
       // Get and update position for bit by computing its bit prefix from the
       // MSB downwards which encodes the path from the root to the node at
       // this level that contains this bit
       let node_index = (d & bitPrefixMask) >>> (levelBitIndex + 1);
       const p = borders[node_index];
       // Set the bit in the bitvector
-      if ((d & levelBit) > 0) {
+      if ((d & levelBit) !== 0) {
         level.setOne(p);
       }
       borders[node_index]++;
     }
   }
 
+  report?.(numLevels, 0);
   // todo: configurable dense bitvec parameters
   return levels.map(d => new DenseBitVec(d.maybePadded(), rank1SamplesPow2, selectSamplesPow2));
 }
@@ -632,18 +1865,22 @@ function buildBitVecsSmallAlphabet(data, numLevels) {
  * than the alphabet size, allowing for sparse alphabets up to 2^32, eg. a symbol space of [0, 2^32).
  * The basic idea is to do a 2-bucket bucket sort, and mark the values that went right with a 1-bit.
  * From the paper "Practical Wavelet Tree Construction" (see link in comment at the top of this file)
- * @param {number[]} data
+ * @param {number[] | Uint32Array} input
  * @param {number} numLevels
+ * @param {ProgressReporter | null} report
  */
-function buildBitVecsLargeAlphabet(data, numLevels) {
+function buildBitVecsLargeAlphabet(input, numLevels, report) {
   assert(numLevels > 0);
   const levels = [];
   const maxLevel = numLevels - 1;
 
+  // Sort a copy so that the input is left unmodified.
+  const data = Uint32Array.from(input);
+
   // For each level, stably sort the datapoints by their bit value at that level.
   // Elements with a zero bit get sorted left, and elements with a one bits
   // get sorted right, which is effectvely a bucket sort with two buckets.
-  const right = [];
+  const right = new Uint32Array(data.length);
 
   for (let l = 0; l < maxLevel; l++) {
     const levelBit = u32(1 << (maxLevel - l));
@@ -653,21 +1890,19 @@ function buildBitVecsLargeAlphabet(data, numLevels) {
     // We retain the elements that went left, then append those that went right.
     let n = 0;
     for (let i = 0; i < data.length; i++) {
+      if (report !== null && (i & ProgressIntervalMask) === 0) report(l, i);
       const value = data[i];
       if ((value & levelBit) === 0) {
         // this value goes to the left
         data[n++] = value;
       } else {
         bits.setOne(i);
-        right.push(value);
+        right[i - n] = value;
       }
     }
 
-    // append `right` to `data`, then clear `right`
-    for (let i = 0; i < right.length; i++) {
-      data[n++] = right[i];
-    }
-    right.length = 0;
+    // append the elements that went right to `data`
+    data.set(right.subarray(0, data.length - n), n);
 
     levels.push(new DenseBitVec(bits, rank1SamplesPow2, selectSamplesPow2));
   }
@@ -677,6 +1912,7 @@ function buildBitVecsLargeAlphabet(data, numLevels) {
     const bits = new BitBuf(data.length);
     const levelBit = 1;
     for (let i = 0; i < data.length; i++) {
+      if (report !== null && (i & ProgressIntervalMask) === 0) report(maxLevel, i);
       const value = data[i];
       if ((value & levelBit) !== 0) {
         bits.setOne(i);
@@ -685,5 +1921,60 @@ function buildBitVecsLargeAlphabet(data, numLevels) {
     levels.push(new DenseBitVec(bits.maybePadded(), rank1SamplesPow2, selectSamplesPow2));
   }
 
+  report?.(numLevels, 0);
   return levels;
+}
+
+/**
+ * Number of elements between progress reports within a level. Each report costs a function call,
+ * which is negligible next to the work done on this many elements.
+ */
+export const ProgressInterval = 2 ** 16;
+const ProgressIntervalMask = ProgressInterval - 1;
+
+/**
+ * Reports that `levelsDone` levels have been built and `elementsDone` elements of the next level
+ * have been processed.
+ * @typedef {(levelsDone: number, elementsDone: number) => void} ProgressReporter
+ */
+
+/**
+ * @param {((progress: Progress) => void) | null} onProgress
+ * @param {Progress['phase']} phase
+ * @param {number} length
+ * @param {number} numLevels
+ * @returns {ProgressReporter | null}
+ */
+function progressReporter(onProgress, phase, length, numLevels) {
+  if (onProgress === null) {
+    return null;
+  }
+  const elementsTotal = length * numLevels;
+  return (levelsDone, elementsDone) => onProgress({
+    phase,
+    levelsDone,
+    levelsTotal: numLevels,
+    elementsDone: levelsDone * length + elementsDone,
+    elementsTotal,
+  });
+}
+
+/**
+ * Scale of the progress values written by `progressToSlot`.
+ */
+export const ProgressSlotScale = 2 ** 16;
+
+/**
+ * Returns an `onProgress` callback that writes the fraction of construction completed, scaled to
+ * `[0, ProgressSlotScale]`, into `slot[index]`. This lets a host poll the progress of a
+ * construction running in a worker without a callback across threads, by passing an array
+ * backed by a `SharedArrayBuffer` to both sides.
+ * @param {Uint32Array} slot
+ * @param {number} [index]
+ */
+export function progressToSlot(slot, index = 0) {
+  return (/** @type {Progress} */ { elementsDone, elementsTotal }) => {
+    const value = elementsTotal === 0 ? ProgressSlotScale : Math.floor(ProgressSlotScale * elementsDone / elementsTotal);
+    Atomics.store(slot, index, value);
+  };
 }
\ No newline at end of file
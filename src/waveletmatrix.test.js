@@ -1,13 +1,60 @@
+import fc from 'fast-check';
 import { describe, expect, it, test } from 'vitest';
 import * as bits from './bits.js';
+import { DenseBitVecBuilder } from './densebitvec.js';
 import './debug.js';
-import { WaveletMatrix } from './waveletmatrix.js';
+import { SparseBitVecBuilder } from './sparsebitvec.js';
+import { encode2, encode3 } from '../morton.js';
+import { coalesceRanges, constructionStrategy, joinCounts, mortonLevelMasks, progressToSlot, ProgressInterval, ProgressSlotScale, WaveletMatrix } from './waveletmatrix.js';
+
+/**
+ * Instrument the levels of the wavelet matrix to count the rank operations done on them.
+ * @param {WaveletMatrix} wm
+ */
+function countRanks(wm) {
+  const counter = { count: 0 };
+  for (const level of wm.levels) {
+    const bv = level.bv;
+    const rank1 = bv.rank1.bind(bv);
+    level.bv = Object.assign(Object.create(bv), { rank1: (/** @type {number} */ i) => (counter.count++, rank1(i)) });
+  }
+  return counter;
+}
 
 describe('WaveletMatrix', () => {
   // todo: add a 1 to punt to the large construction algorithm
   const symbols = [0, 1, 2, 1, 0, 1, 4, 1];
   const wm = new WaveletMatrix(symbols);
 
+  it('verify', () => {
+    wm.verifyAgainst(symbols);
+    for (const data of [[], [0], [3, 3, 3], Array.from({ length: 1000 }, (_, i) => (i * 7919) % 1000)]) {
+      for (const retainData of [false, true]) {
+        const wm = new WaveletMatrix(data, undefined, { retainData });
+        wm.verify();
+        wm.verify({ sampleSize: Infinity });
+        wm.verifyAgainst(data);
+      }
+    }
+    expect(() => wm.verifyAgainst([0, 1, 2, 1, 0, 1, 4, 2])).toThrow('element 7 has symbol 1, expected 2');
+    expect(() => wm.verifyAgainst([0])).toThrow('expected data of length 8, got 1');
+
+    // a bit vector whose select1 is off by one
+    const broken = new WaveletMatrix(symbols);
+    const bv = broken.levels[1].bv;
+    broken.levels[1].bv = Object.assign(Object.create(bv), { select1: (/** @type {number} */ n) => bv.select1(n) + 1 });
+    expect(() => broken.verify()).toThrow('which selectUpwards maps to');
+    // a level whose zero count is out of date
+    const stale = new WaveletMatrix(symbols);
+    stale.levels[2].nz++;
+    expect(() => stale.verify()).toThrow('level 2: expected nz (5) to equal numZeros (4)');
+
+    // retained data that disagrees with the levels
+    const retained = new WaveletMatrix(symbols, undefined, { retainData: true });
+    retained.data?.set([2], 3);
+    expect(() => retained.verify()).toThrow('element 3 has symbol 1 on the levels, but get returns 2');
+  });
+
   it('precedingCount', () => {
     expect(wm.precedingCount(0)).toBe(0);
     expect(wm.precedingCount(1)).toBe(2);
@@ -37,6 +84,19 @@ describe('WaveletMatrix', () => {
     expect(wm.count(0, { range: { start: wm.length, end: wm.length } })).toBe(0);
   });
 
+  it('countLessThanOrEqual', () => {
+    for (let start = 0; start <= wm.length; start++) {
+      for (let end = start; end <= wm.length; end++) {
+        const range = { start, end };
+        for (let symbol = -1; symbol <= wm.alphabetSize + 1; symbol++) {
+          const expected = symbols.slice(start, end).filter(s => s <= symbol).length;
+          expect(wm.countLessThanOrEqual(symbol, { range })).toBe(expected);
+        }
+      }
+    }
+    expect(wm.countLessThanOrEqual(2 ** 32 - 1)).toBe(wm.length);
+  });
+
   it('quantile', () => {
     // [0, 1, 2, 1, 0, 1, 4, 1] sorted is
     // [0, 0, 1, 1, 1, 1, 2, 4]
@@ -48,12 +108,42 @@ describe('WaveletMatrix', () => {
     expect(wm.quantile(5)).toEqual({ symbol: 1, count: 4 });
     expect(wm.quantile(6)).toEqual({ symbol: 2, count: 1 });
     expect(wm.quantile(7)).toEqual({ symbol: 4, count: 1 });
-    expect(() => wm.quantile(8)).toThrow();
+    expect(() => wm.quantile(8)).toThrow('k (8) must be in [0, 8)');
 
     const options = { range: { start: 3, end: 6 } };
     expect(wm.quantile(0, options)).toEqual({ symbol: 0, count: 1 });
     expect(wm.quantile(1, options)).toEqual({ symbol: 1, count: 2 });
     expect(wm.quantile(2, options)).toEqual({ symbol: 1, count: 2 });
+    // k is bounded by the length of the range rather than of the whole matrix
+    expect(() => wm.quantile(3, options)).toThrow('k (3) must be in [0, 3)');
+  });
+
+  it('quantileWithOffset and quantileSelect', () => {
+    expect(Array.from({ length: 8 }, (_, k) => wm.quantileWithOffset(k).offset)).toEqual([0, 1, 0, 1, 2, 3, 0, 0]);
+    fc.assert(fc.property(
+      fc.record({
+        data: fc.array(fc.nat(12), { minLength: 1, maxLength: 80 }),
+        bounds: fc.tuple(fc.nat(80), fc.nat(80)),
+      }),
+      ({ data, bounds }) => {
+        const wm = new WaveletMatrix(data);
+        wm.verifyAgainst(data);
+        const [a, b] = bounds.map(x => Math.min(x, data.length));
+        const range = { start: Math.min(a, b), end: Math.max(a, b) };
+        // stably sort the (symbol, index) pairs in the range by symbol
+        const sorted = data.map((symbol, index) => ({ symbol, index }))
+          .slice(range.start, range.end)
+          .sort((x, y) => x.symbol - y.symbol);
+        for (let k = 0; k < sorted.length; k++) {
+          const { symbol, count, offset } = wm.quantileWithOffset(k, { range });
+          expect(symbol).toBe(sorted[k].symbol);
+          expect(count).toBe(wm.count(symbol, { range }));
+          expect(offset).toBe(k - wm.precedingCount(symbol, { range }));
+          expect(wm.quantileSelect(k, { range })).toBe(sorted[k].index);
+        }
+        expect(() => wm.quantileSelect(sorted.length, { range })).toThrow('must be in');
+      }
+    ), { numRuns: 200 });
   });
 
   it('select', () => {
@@ -142,6 +232,33 @@ describe('WaveletMatrix', () => {
     }
   });
 
+  it('trySelectUpwards', () => {
+    // selecting upwards from the bottom level recovers the original indices.
+    // the bottom level orders symbols by their bit-reversed value (see the `counts` test):
+    // [0, 0, 4, 2, 1, 1, 1, 1]
+    expect(wm.trySelectUpwards(0)).toBe(0);
+    expect(wm.trySelectUpwards(1)).toBe(4);
+    expect(wm.trySelectUpwards(2)).toBe(6);
+    expect(wm.trySelectUpwards(3)).toBe(2);
+    expect(wm.trySelectUpwards(7)).toBe(7);
+    for (let index = 0; index < wm.length; index++) {
+      for (let ignoreBits = 0; ignoreBits <= wm.numLevels; ignoreBits++) {
+        expect(wm.trySelectUpwards(index, { ignoreBits })).toBe(wm.selectUpwards(index, { ignoreBits }));
+      }
+    }
+    // ignoring all levels leaves the index unchanged
+    expect(wm.trySelectUpwards(3, { ignoreBits: wm.numLevels })).toBe(3);
+
+    // out-of-range indices
+    expect(wm.trySelectUpwards(-1)).toBe(null);
+    expect(wm.trySelectUpwards(wm.length)).toBe(null);
+    expect(wm.trySelectUpwards(wm.length + 10, { ignoreBits: 1 })).toBe(null);
+
+    // excessive or invalid ignoreBits
+    expect(wm.trySelectUpwards(0, { ignoreBits: wm.numLevels + 1 })).toBe(null);
+    expect(wm.trySelectUpwards(0, { ignoreBits: -1 })).toBe(null);
+  });
+
   it('simpleMajority', () => {
     expect(wm.simpleMajority({ start: 0, end: wm.length })).toBe(null);
     expect(wm.simpleMajority({ start: 0, end: wm.length - 1 })).toBe(null);
@@ -159,6 +276,12 @@ describe('WaveletMatrix', () => {
     expect(wm.get(5)).toBe(1);
     expect(wm.get(6)).toBe(4);
     expect(wm.get(7)).toBe(1);
+    expect(() => wm.get(-1)).toThrow('out of bounds');
+    expect(() => wm.get(8)).toThrow('out of bounds');
+    // the first level of this matrix consists of 0-bits only, so without an explicit
+    // bounds check the index would not be checked until the second level
+    const degenerate = new WaveletMatrix([0, 1, 0], 3);
+    expect(() => degenerate.get(3)).toThrow('out of bounds');
   });
 
   it('counts', () => {
@@ -199,6 +322,1019 @@ describe('WaveletMatrix', () => {
     ]);
   });
   
+  it('counts with ignoreBits aggregates adjacent symbols', () => {
+    const symbols = [0, 1, 2, 3, 3, 5, 6, 7, 7, 7, 4, 1, 0, 0];
+    const wm = new WaveletMatrix(symbols, 7);
+    const fine = wm.counts();
+    const coarse = wm.counts({ ignoreBits: 1 });
+    // each coarse node covers a symbol pair {2k, 2k + 1} and is labeled with its leftmost symbol
+    for (const { symbol, start, end } of coarse) {
+      expect(symbol % 2).toBe(0);
+      const pairCount = fine
+        .filter(x => x.symbol === symbol || x.symbol === symbol + 1)
+        .reduce((acc, x) => acc + x.end - x.start, 0);
+      expect(end - start).toBe(pairCount);
+    }
+    const total = (/** @type {{ start: number, end: number }[]} */ xs) => xs.reduce((acc, x) => acc + x.end - x.start, 0);
+    expect(total(coarse)).toBe(total(fine));
+    expect(coarse.length).toBe(4);
+  });
+
+  it('countsUnion merges ranges that become adjacent', () => {
+    const wm = new WaveletMatrix([0, 1, 2, 0, 1, 3], 3);
+    // The ranges are separated by the symbol 2, which goes right on the first level,
+    // so their left children are adjacent and are merged into a single node.
+    const bottom = wm.levels[1].bv;
+    const rank1 = bottom.rank1.bind(bottom);
+    let numRanks = 0;
+    bottom.rank1 = (/** @type {number} */ index) => (numRanks++, rank1(index));
+    const result = wm.countsUnion([{ start: 0, end: 2 }, { start: 3, end: 5 }]);
+    bottom.rank1 = rank1;
+    expect(result).toEqual([{ symbol: 0, count: 2 }, { symbol: 1, count: 2 }]);
+    // one rank for the merged node's start and one for its end, rather than
+    // additional ones for the endpoints of a second node
+    expect(numRanks).toBe(2);
+  });
+
+  it('selectInSymbolRange', () => {
+    const symbols = [3, 9, 0, 3, 14, 7, 7, 1, 3, 12, 0, 9, 5];
+    const wm = new WaveletMatrix(symbols, 15);
+    const symbolRanges = [{ start: 0, end: 16 }, { start: 3, end: 8 }, { start: 7, end: 8 }, { start: 10, end: 12 }, { start: 4, end: 4 }];
+    for (let start = 0; start <= symbols.length; start++) {
+      for (let end = start; end <= symbols.length; end++) {
+        for (const symbolRange of symbolRanges) {
+          // brute force: enumerate the matching positions in the range
+          const matches = [];
+          for (let i = start; i < end; i++) {
+            if (symbolRange.start <= symbols[i] && symbols[i] < symbolRange.end) matches.push(i);
+          }
+          for (let k = 0; k <= matches.length; k++) {
+            expect(wm.selectInSymbolRange(symbolRange, { k, range: { start, end } })).toBe(matches[k] ?? null);
+          }
+        }
+      }
+    }
+    expect(() => wm.selectInSymbolRange({ start: 0, end: 16 }, { k: -1 })).toThrow('non-negative');
+  });
+
+  it('countFrequencyHistogram', () => {
+    const bounds = [2, 5, 20];
+    /** 
+     * @param {number[]} symbols
+     * @param {{ start: number, end: number }} range
+     */
+    function bruteForce(symbols, range) {
+      /** @type {Map<number, number>} */
+      const counts = new Map();
+      for (const s of symbols.slice(range.start, range.end)) counts.set(s, (counts.get(s) ?? 0) + 1);
+      const histogram = new Array(bounds.length + 1).fill(0);
+      for (const count of counts.values()) {
+        histogram[bounds.filter(b => b <= count).length]++;
+      }
+      return histogram;
+    }
+
+    // Zipf-distributed data: symbol s occurs roughly 60 / (s + 1) times
+    const zipf = [];
+    for (let s = 0; s < 60; s++) {
+      for (let i = 0; i < Math.floor(60 / (s + 1)); i++) zipf.push(s);
+    }
+    zipf.sort((a, b) => ((a * 7919 + zipf.length) % 31) - ((b * 7919 + zipf.length) % 31));
+    const wm = new WaveletMatrix(zipf, 63);
+    for (const range of [{ start: 0, end: zipf.length }, { start: 10, end: 100 }, { start: 5, end: 6 }, { start: 7, end: 7 }]) {
+      expect(wm.countFrequencyHistogram(bounds, { range })).toEqual(bruteForce(zipf, range));
+    }
+
+    // all-distinct data lands in the first bucket, and the traversal stops at single-element nodes
+    const n = 1024;
+    const distinct = Array.from({ length: n }, (_, i) => (i * 2654435761) % 2 ** 20);
+    const wm2 = new WaveletMatrix(distinct, 2 ** 20 - 1);
+    let numRanks = 0;
+    for (const level of wm2.levels) {
+      const bv = level.bv;
+      const rank1 = bv.rank1.bind(bv);
+      bv.rank1 = (/** @type {number} */ index) => (numRanks++, rank1(index));
+    }
+    expect(wm2.countFrequencyHistogram(bounds)).toEqual([n, 0, 0, 0]);
+    // a full descent would take two ranks per node on each of the 20 levels
+    expect(numRanks).toBeLessThan(2 * n * 2);
+
+    expect(() => wm.countFrequencyHistogram([1, 5])).toThrow('strictly increasing');
+    expect(() => wm.countFrequencyHistogram([5, 5])).toThrow('strictly increasing');
+  });
+
+  it('mapSymbols', () => {
+    const data = Array.from({ length: 300 }, (_, i) => (i * 37 + (i >> 3)) % 100);
+    for (const retainData of [false, true]) {
+      const wm = new WaveletMatrix(data, 99, { retainData });
+      const quantize = (/** @type {number} */ s) => Math.floor(s / 10);
+      const mapped = wm.mapSymbols(quantize, 9);
+      const expected = new WaveletMatrix(data.map(quantize), 9);
+      expect(mapped.maxSymbol).toBe(9);
+      expect(mapped.toArray()).toEqual(expected.toArray());
+      mapped.verifyAgainst(expected.toArray());
+      expect(mapped.counts()).toEqual(expected.counts());
+
+      for (let k = 0; k <= wm.numLevels + 1; k++) {
+        const shifted = wm.mapSymbolsShift(k);
+        const expected = new WaveletMatrix(data.map(d => d >>> k), 99 >>> k);
+        expect(shifted.numLevels).toBe(expected.numLevels);
+        expect(shifted.maxSymbol).toBe(expected.maxSymbol);
+        expect(shifted.toArray()).toEqual(expected.toArray());
+        shifted.verifyAgainst(expected.toArray());
+        expect(shifted.data === null).toBe(!retainData);
+        expect(shifted.mapSymbols(s => s, shifted.maxSymbol).toArray()).toEqual(expected.toArray());
+        for (const range of [{ start: 0, end: 300 }, { start: 17, end: 123 }]) {
+          expect(shifted.counts({ range })).toEqual(expected.counts({ range }));
+          if (k <= wm.numLevels) {
+            // shifting agrees with ignoreBits up to the shifted symbol labels
+            const coarse = wm.counts({ range, ignoreBits: k }).map(({ symbol, start, end }) => ({ symbol: symbol >>> k, start, end }));
+            expect(shifted.counts({ range })).toEqual(coarse);
+          }
+        }
+      }
+    }
+    const wm = new WaveletMatrix(data, 99);
+    expect(() => wm.mapSymbols(s => 99 - s, 99)).toThrow('monotone');
+    expect(() => wm.mapSymbols(s => s + 1, 99)).toThrow('cannot exceed newMaxSymbol');
+
+    // mapping a decimated matrix keeps its sampling step
+    const decimated = new WaveletMatrix(Array.from({ length: 64 }, (_, i) => i % 8), 7).decimate(2);
+    const ranges = [{ start: 0, end: 64 }];
+    for (const mapped of [decimated.mapSymbols(s => s >>> 1, 3), decimated.mapSymbolsShift(1), decimated.mapSymbolsShift(5)]) {
+      expect(mapped.sampleShift).toBe(2);
+      const total = mapped.approximateCounts(ranges)[0].reduce((acc, x) => acc + x.count, 0);
+      expect(total).toBe(64);
+    }
+    expect(decimated.mapSymbolsShift(1).approximateCounts(ranges)).toEqual([[{ symbol: 0, count: 32 }, { symbol: 2, count: 32 }]]);
+  });
+
+  it('inverseSelect', () => {
+    for (const [data, maxSymbol] of [[symbols, 4], [[5, 5, 5, 5], 5], [[0, 2, 0, 2, 3, 2], 2 ** 20]]) {
+      const wm = new WaveletMatrix(data, maxSymbol);
+      for (let index = 0; index < data.length; index++) {
+        const { symbol, k } = wm.inverseSelect(index);
+        expect(symbol).toBe(data[index]);
+        expect(k).toBe(data.slice(0, index).filter(d => d === symbol).length);
+        expect(wm.select(symbol, { k })).toBe(index);
+      }
+    }
+    expect(() => wm.inverseSelect(wm.length)).toThrow('out of bounds');
+  });
+
+  it('joinCounts', () => {
+    // items sorted by id, each with an attribute code
+    const ids = [0, 0, 1, 3, 3, 3, 4, 7, 7, 9];
+    const codes = [5, 2, 5, 1, 1, 8, 2, 5, 0, 1];
+    const primary = new WaveletMatrix(ids);
+    const secondary = new WaveletMatrix(codes);
+    for (const symbols of [[], [0], [3, 7], [7, 3, 3], [2, 5, 6], [0, 1, 3, 4, 7, 9], [100]]) {
+      /** @type {Map<number, number>} */
+      const expected = new Map();
+      const sortedCodes = codes.filter((_, i) => symbols.includes(ids[i])).sort((a, b) => a - b);
+      for (const code of sortedCodes) expected.set(code, (expected.get(code) ?? 0) + 1);
+      const actual = joinCounts(primary, secondary, symbols);
+      expect(actual).toEqual(expected);
+      expect(Array.from(actual.keys())).toEqual(Array.from(expected.keys()));
+    }
+    expect(() => joinCounts(primary, new WaveletMatrix([1, 2]), [0])).toThrow('co-indexed');
+  });
+
+  it('reports construction progress', () => {
+    const length = 3 * ProgressInterval + 5;
+    const data = Array.from({ length }, (_, i) => (i * 2654435761) % 1000);
+    // a small alphabet is built with the histogram and a large one by sorting
+    for (const [maxSymbol, phase] of [[999, 'histogram'], [2 ** 30, 'sort']]) {
+      /** @type {import('./waveletmatrix.js').Progress[]} */
+      const reports = [];
+      const wm = new WaveletMatrix(data, maxSymbol, { onProgress: p => reports.push(p) });
+      expect(reports.length).toBeGreaterThan(wm.numLevels * 4);
+      for (let i = 1; i < reports.length; i++) {
+        expect(reports[i].elementsDone).toBeGreaterThanOrEqual(reports[i - 1].elementsDone);
+        expect(reports[i].levelsDone).toBeGreaterThanOrEqual(reports[i - 1].levelsDone);
+      }
+      for (const p of reports) {
+        expect(p.phase).toBe(phase);
+        expect(p.levelsTotal).toBe(wm.numLevels);
+        expect(p.elementsTotal).toBe(length * wm.numLevels);
+      }
+      const last = reports[reports.length - 1];
+      expect(last.levelsDone).toBe(wm.numLevels);
+      expect(last.elementsDone).toBe(last.elementsTotal);
+      // progress reporting does not affect the result
+      expect(wm.toArray()).toEqual(new WaveletMatrix(data, maxSymbol).toArray());
+
+      const slot = new Uint32Array(new SharedArrayBuffer(8));
+      new WaveletMatrix(data, maxSymbol, { onProgress: progressToSlot(slot, 1) });
+      expect(slot[0]).toBe(0);
+      expect(slot[1]).toBe(ProgressSlotScale);
+    }
+    /** @type {import('./waveletmatrix.js').Progress[]} */
+    const reports = [];
+    new WaveletMatrix([], 0, { onProgress: p => reports.push(p) });
+    expect(reports).toEqual([{ phase: 'histogram', levelsDone: 1, levelsTotal: 1, elementsDone: 0, elementsTotal: 0 }]);
+  });
+
+  it('counts with level masks partitions the symbol space', () => {
+    // Symbols interleave the bits of several dimensions, and each level is masked by the bits of
+    // the dimension its bit belongs to, so that a symbol range [encode(lo), encode(hi - 1) + 1)
+    // selects the box lo <= coordinates < hi.
+    fc.assert(fc.property(
+      fc.record({
+        dims: fc.array(fc.integer({ min: 0, max: 2 }), { minLength: 1, maxLength: 12 }),
+        values: fc.array(fc.nat(2 ** 12 - 1), { minLength: 1, maxLength: 100 }),
+        splitAt: fc.double({ min: 0, max: 1, noNaN: true }),
+        splitDim: fc.integer({ min: 0, max: 2 }),
+      }),
+      ({ dims, values, splitAt, splitDim }) => {
+        const data = values.map(v => v & bits.oneMask(dims.length));
+        // the maximum symbol is inferred from the data, so it need not fill the space of its levels
+        const wm = new WaveletMatrix(data);
+        wm.verifyAgainst(data);
+        // dims[b] is the dimension of bit b, counting from the least significant bit
+        dims = dims.slice(0, wm.numLevels);
+        if (dims.length < wm.numLevels) return; // the matrix always has at least one level
+        const dimMasks = [0, 1, 2].map(dim => dims.reduce((mask, d, b) => d === dim ? mask | (1 << b) : mask, 0) >>> 0);
+        const extents = dimMasks.map(mask => 2 ** bits.popcount(mask));
+        const masks = wm.levels.map(level => dimMasks.find(mask => (mask & level.bit) !== 0) ?? 0);
+
+        // scatter the coordinate bits of each dimension into the positions given by its mask
+        const encode = (/** @type {number[]} */ coords) => coords.reduce((symbol, c, dim) => {
+          let mask = dimMasks[dim];
+          for (let b = 0; mask !== 0; b++) {
+            const low = mask & -mask;
+            if ((c >>> b) & 1) symbol |= low;
+            mask ^= low;
+          }
+          return symbol >>> 0;
+        }, 0);
+        const decode = (/** @type {number} */ symbol) => dimMasks.map(mask => {
+          let c = 0;
+          for (let b = 0; mask !== 0; b++) {
+            const low = mask & -mask;
+            if (symbol & low) c |= 1 << b;
+            mask ^= low;
+          }
+          return c;
+        });
+
+        /**
+         * @param {number[]} lo
+         * @param {number[]} hi
+         */
+        const boxCount = (lo, hi) => {
+          const symbolRange = { start: encode(lo), end: encode(hi.map(h => h - 1)) + 1 };
+          return wm.counts({ symbolRange, ignoreBits: masks }).reduce((sum, x) => sum + x.end - x.start, 0);
+        };
+        /**
+         * @param {number[]} lo
+         * @param {number[]} hi
+         */
+        const bruteForce = (lo, hi) => data.filter(d => decode(d).every((c, dim) => lo[dim] <= c && c < hi[dim])).length;
+
+        // the whole space
+        const lo = [0, 0, 0];
+        expect(boxCount(lo, extents)).toBe(data.length);
+
+        // split one dimension in two, which partitions the space
+        const split = Math.floor(splitAt * extents[splitDim]);
+        const loHi = extents.slice();
+        loHi[splitDim] = split;
+        const hiLo = lo.slice();
+        hiLo[splitDim] = split;
+        const a = split === 0 ? 0 : boxCount(lo, loHi);
+        const b = split === extents[splitDim] ? 0 : boxCount(hiLo, extents);
+        expect(a).toBe(bruteForce(lo, loHi));
+        expect(b).toBe(bruteForce(hiLo, extents));
+        expect(a + b).toBe(data.length);
+      }
+    ));
+  });
+
+  it('rankAndCount', () => {
+    for (const range of [{ start: 0, end: wm.length }, { start: 1, end: 4 }, { start: 3, end: 3 }]) {
+      for (let symbol = 0; symbol <= 5; symbol++) {
+        expect(wm.rankAndCount(symbol, { range })).toEqual({
+          precedingCount: wm.precedingCount(symbol, { range }),
+          count: wm.count(symbol, { range }),
+        });
+      }
+    }
+  });
+
+  it('symbolCounts', () => {
+    const data = Array.from({ length: 200 }, (_, i) => (i * 31 + (i >> 2)) % 45);
+    const wm = new WaveletMatrix(data, 44);
+    for (const range of [{ start: 0, end: 200 }, { start: 13, end: 77 }, { start: 5, end: 5 }]) {
+      for (const symbolRange of [{ start: 0, end: 45 }, { start: 10, end: 20 }, { start: 44, end: 100 }, { start: 3, end: 3 }]) {
+        /** @type {Map<number, number>} */
+        const histogram = new Map();
+        for (const d of data.slice(range.start, range.end)) {
+          if (symbolRange.start <= d && d < symbolRange.end) histogram.set(d, (histogram.get(d) ?? 0) + 1);
+        }
+        const expected = Array.from(histogram).sort((a, b) => a[0] - b[0]);
+        expect(wm.symbolCounts({ range, symbolRange })).toEqual(expected);
+      }
+    }
+  });
+
+  it('symbolFractions', () => {
+    const data = Array.from({ length: 200 }, (_, i) => (i * 31 + (i >> 2)) % 45);
+    const wm = new WaveletMatrix(data, 44);
+    for (const range of [{ start: 0, end: 200 }, { start: 13, end: 77 }, { start: 199, end: 200 }]) {
+      const fractions = wm.symbolFractions({ range });
+      expect(fractions.map(([symbol]) => symbol)).toEqual(wm.symbolCounts({ range }).map(([symbol]) => symbol));
+      expect(Math.abs(fractions.reduce((sum, [, f]) => sum + f, 0) - 1)).toBeLessThan(1e-12);
+    }
+    const range = { start: 13, end: 77 };
+    const symbolRange = { start: 10, end: 20 };
+    expect(wm.symbolFractions({ range, symbolRange })).toEqual(wm.symbolCounts({ range, symbolRange }).map(([s, c]) => [s, c / 64]));
+    expect(wm.symbolFractions({ range: { start: 5, end: 5 } })).toEqual([]);
+    expect(new WaveletMatrix([]).symbolFractions()).toEqual([]);
+  });
+
+  it('empty matrix', () => {
+    for (const wm of [new WaveletMatrix([]), new WaveletMatrix([], 5), new WaveletMatrix([], 1000, { retainData: true })]) {
+      const empty = { start: 0, end: 0 };
+      expect(wm.length).toBe(0);
+      for (const level of wm.levels) {
+        expect(level.bv.universeSize).toBe(0);
+        expect(level.nz).toBe(0);
+      }
+      wm.verify();
+      wm.verifyAgainst([]);
+      expect(Array.from(wm.toArray())).toEqual([]);
+      expect(() => wm.get(0)).toThrow('index (0) is out of bounds for length (0)');
+      expect(() => wm.inverseSelect(0)).toThrow('index (0) is out of bounds for length (0)');
+      for (const symbol of [0, 1, wm.maxSymbol]) {
+        expect(wm.count(symbol)).toBe(0);
+        expect(wm.precedingCount(symbol)).toBe(0);
+        expect(wm.countLessThanOrEqual(symbol)).toBe(0);
+        expect(wm.locate(symbol)).toEqual({ precedingCount: 0, range: empty });
+        expect(wm.select(symbol)).toBe(null);
+        expect(wm.selectFromEnd(symbol)).toBe(null);
+      }
+      expect(() => wm.quantile(0)).toThrow('k (0) must be in [0, 0)');
+      expect(wm.counts()).toEqual([]);
+      expect(wm.symbolCounts()).toEqual([]);
+      expect(wm.countsByRange([empty])).toEqual([[]]);
+      expect(wm.countsUnion([empty])).toEqual([]);
+      expect(wm.countExcluding([])).toBe(0);
+      expect(Array.from(wm.matches(empty, { start: 0, end: 1 }))).toEqual([]);
+      expect(wm.sampleMatches(10, 0)).toEqual([]);
+      expect(wm.childRanges(0, empty)).toEqual({ left: empty, right: empty });
+      // matrices built directly from (empty) levels
+      for (const shift of [1, wm.numLevels]) {
+        const shifted = wm.mapSymbolsShift(shift);
+        expect(shifted.length).toBe(0);
+        shifted.verify();
+        expect(shifted.counts()).toEqual([]);
+      }
+    }
+  });
+
+  it('countAlive', () => {
+    fc.assert(fc.property(fc.array(fc.tuple(fc.nat(10), fc.nat(99)), { maxLength: 200 }), fc.nat(99), fc.nat(200), fc.nat(200), (rows, deadPercent, a, b) => {
+      const data = rows.map(([symbol]) => symbol);
+      const isAlive = rows.map(([, x]) => x >= deadPercent);
+      const wm = new WaveletMatrix(data, 10);
+      const builder = new DenseBitVecBuilder(data.length);
+      isAlive.forEach((x, i) => x && builder.one(i));
+      const alive = builder.build();
+      const range = { start: Math.min(a, b, data.length), end: Math.min(Math.max(a, b), data.length) };
+      for (let symbol = 0; symbol <= 11; symbol++) {
+        let expected = 0;
+        for (let i = range.start; i < range.end; i++) {
+          if (data[i] === symbol && isAlive[i]) expected++;
+        }
+        const count = wm.countAlive(symbol, alive, { range });
+        expect(count).toBe(expected);
+        // marking elements dead only reduces the count
+        expect(count).toBeLessThanOrEqual(wm.count(symbol, { range }));
+      }
+    }));
+    const wm = new WaveletMatrix([3, 1, 3, 3]);
+    const allAlive = DenseBitVecBuilder.fromSortedOnes(4, [0, 1, 2, 3]);
+    expect(wm.countAlive(3, allAlive)).toBe(wm.count(3));
+    expect(wm.countAlive(3, DenseBitVecBuilder.fromSortedOnes(4, [1, 2]))).toBe(1);
+    expect(() => wm.countAlive(3, DenseBitVecBuilder.fromSortedOnes(5, []))).toThrow('expected the alive bit vector to have universe size 4, got 5');
+    const builder = new SparseBitVecBuilder(4);
+    builder.one(0, 2);
+    expect(() => wm.countAlive(3, builder.build())).toThrow('the alive bit vector cannot have multiplicity');
+  });
+
+  it('positionsBySymbol', () => {
+    fc.assert(fc.property(fc.array(fc.nat(40), { maxLength: 200 }), fc.nat(200), fc.nat(200), fc.nat(45), fc.nat(45), (data, a, b, c, d) => {
+      const wm = new WaveletMatrix(data, 40);
+      const range = { start: Math.min(a, b, data.length), end: Math.min(Math.max(a, b), data.length) };
+      const symbolRange = { start: Math.min(c, d), end: Math.max(c, d) };
+      /** @type {Map<number, number[]>} */
+      const expected = new Map();
+      for (let i = range.start; i < range.end; i++) {
+        const symbol = wm.get(i);
+        if (symbol < symbolRange.start || symbol >= symbolRange.end) continue;
+        expected.set(symbol, [...expected.get(symbol) ?? [], i]);
+      }
+      const positions = wm.positionsBySymbol({ range, symbolRange });
+      expect(positions.map(([symbol, indices]) => [symbol, Array.from(indices)])).toEqual(Array.from(expected).sort((x, y) => x[0] - y[0]));
+    }));
+    expect(new WaveletMatrix([]).positionsBySymbol()).toEqual([]);
+  });
+
+  it('countsColumnar', () => {
+    const data = Array.from({ length: 200 }, (_, i) => (i * 31 + (i >> 2)) % 45);
+    const wm = new WaveletMatrix(data, 44);
+    const ranges = [{ start: 0, end: 50 }, { start: 50, end: 120 }, { start: 30, end: 60 }, { start: 7, end: 7 }];
+    for (const symbolRange of [undefined, { start: 10, end: 30 }]) {
+      const expected = wm.countsByRange(ranges, { symbolRange, sortBySymbol: true })
+        .flatMap((xs, key) => xs.map(x => [key, x.symbol, x.end - x.start]));
+      const ordered = wm.countsColumnar(ranges, { symbolRange, ordered: true });
+      const rows = Array.from({ length: ordered.length }, (_, i) => ['key', 'symbol', 'count'].map(name => ordered.column(name)[i]));
+      expect(rows).toEqual(expected);
+
+      const unordered = wm.countsColumnar(ranges, { symbolRange });
+      unordered.sortBy('key', 'symbol');
+      for (const name of ['key', 'symbol', 'count']) {
+        expect(unordered.column(name)).toEqual(ordered.column(name));
+      }
+    }
+  });
+
+  it('cdf', () => {
+    const symbols = [3, 9, 0, 3, 14, 7, 7, 1, 3, 12, 0, 9];
+    const wm = new WaveletMatrix(symbols, 15);
+    for (let start = 0; start <= symbols.length; start++) {
+      for (let end = start; end <= symbols.length; end++) {
+        for (const symbolRange of [{ start: 0, end: 16 }, { start: 2, end: 10 }, { start: 4, end: 7 }, { start: 8, end: 8 }]) {
+          // brute force: sort the reconstructed range and accumulate
+          const sorted = symbols.slice(start, end)
+            .filter(s => s >= symbolRange.start && s < symbolRange.end)
+            .sort((a, b) => a - b);
+          /** @type {{ symbol: number, cumulativeCount: number }[]} */
+          const expected = [];
+          sorted.forEach((symbol, i) => {
+            if (sorted[i + 1] !== symbol) expected.push({ symbol, cumulativeCount: i + 1 });
+          });
+          expect(wm.cdf({ range: { start, end }, symbolRange })).toEqual(expected);
+        }
+        // the final cumulative count agrees with the scalar version
+        const cdf = wm.cdf({ range: { start, end } });
+        if (cdf.length > 0) {
+          const last = cdf[cdf.length - 1];
+          expect(last.cumulativeCount).toBe(wm.countLessThanOrEqual(last.symbol, { range: { start, end } }));
+        }
+      }
+    }
+  });
+
+  it('countsUnion', () => {
+    /**
+     * Brute-force counts over the union of the given ranges, in symbol order
+     * @param {{ start: number; end: number; }[]} ranges
+     */
+    const countsOverUnion = (ranges) => {
+      const indices = new Set(ranges.flatMap(r => Array.from({ length: Math.max(0, r.end - r.start) }, (_, i) => r.start + i)));
+      /** @type {Map<number, number>} */
+      const counts = new Map();
+      for (const i of indices) {
+        counts.set(symbols[i], (counts.get(symbols[i]) ?? 0) + 1);
+      }
+      return Array.from(counts, ([symbol, count]) => ({ symbol, count })).sort((a, b) => a.symbol - b.symbol);
+    };
+    /**
+     * @param {{ symbol: number; count: number; }[]} xs
+     */
+    const bySymbol = (xs) => xs.slice().sort((a, b) => a.symbol - b.symbol);
+
+    // deliberately overlapping ranges
+    const ranges = [{ start: 0, end: 3 }, { start: 1, end: 5 }, { start: 4, end: 6 }];
+    expect(bySymbol(wm.countsUnion(ranges))).toEqual(countsOverUnion(ranges));
+    expect(wm.countsUnion(ranges)).toEqual([
+      { symbol: 0, count: 2 },
+      { symbol: 2, count: 1 },
+      { symbol: 1, count: 3 },
+    ]);
+
+    // the result matches counting the coalesced union with `counts`
+    expect(wm.countsUnion(ranges)).toEqual(
+      wm.counts({ range: { start: 0, end: 6 } }).map(x => ({ symbol: x.symbol, count: x.end - x.start }))
+    );
+
+    // exhaustively test pairs of ranges
+    for (let a = 0; a <= wm.length; a++) {
+      for (let b = a; b <= wm.length; b++) {
+        for (let c = 0; c <= wm.length; c++) {
+          for (let d = c; d <= wm.length; d++) {
+            const ranges = [{ start: a, end: b }, { start: c, end: d }];
+            expect(bySymbol(wm.countsUnion(ranges))).toEqual(countsOverUnion(ranges));
+          }
+        }
+      }
+    }
+
+    // no ranges, empty ranges, and identical ranges
+    expect(wm.countsUnion([])).toEqual([]);
+    expect(wm.countsUnion([{ start: 3, end: 3 }])).toEqual([]);
+    expect(wm.countsUnion([{ start: 1, end: 2 }, { start: 1, end: 2 }])).toEqual([{ symbol: 1, count: 1 }]);
+  });
+
+  it('skips rank and select on levels of all 0-bits or all 1-bits', () => {
+    // 12 levels, where the top 8 levels are degenerate
+    const maxSymbol = 2 ** 12 - 1;
+    const numDegenerateLevels = 8;
+    const low = [3, 0, 15, 7, 7, 1, 12, 3, 3, 9, 0, 15];
+    const high = low.map(d => maxSymbol - d);
+    for (const symbols of [low, high]) {
+      const wm = new WaveletMatrix(symbols.slice(), maxSymbol);
+      expect(wm.numLevels).toBe(12);
+
+      // Replace the degenerate levels' bit vectors with ones that throw if used
+      for (let i = 0; i < numDegenerateLevels; i++) {
+        const level = wm.levels[i];
+        expect(level.allZeros || level.allOnes).toBe(true);
+        const fail = () => { throw new Error(`level ${i} should have been skipped`); };
+        // @ts-ignore
+        level.bv = { rank1: fail, rank0: fail, select1: fail, select0: fail, get: fail };
+      }
+
+      // compare against brute force
+      const sorted = symbols.slice().sort((a, b) => a - b);
+      for (let i = 0; i < symbols.length; i++) {
+        expect(wm.get(i)).toBe(symbols[i]);
+        expect(wm.quantile(i).symbol).toBe(sorted[i]);
+      }
+      for (const symbol of new Set(symbols)) {
+        const indices = symbols.flatMap((d, i) => d === symbol ? [i] : []);
+        expect(wm.count(symbol)).toBe(indices.length);
+        expect(wm.precedingCount(symbol)).toBe(symbols.filter(d => d < symbol).length);
+        indices.forEach((index, k) => expect(wm.select(symbol, { k })).toBe(index));
+      }
+      const counts = wm.counts();
+      expect(counts.reduce((sum, x) => sum + x.end - x.start, 0)).toBe(symbols.length);
+      for (const x of counts) {
+        expect(x.end - x.start).toBe(symbols.filter(d => d === x.symbol).length);
+      }
+    }
+  });
+
+  it('countsByRange', () => {
+    const ranges = [
+      { start: 0, end: 3 },
+      { start: 3, end: 8 }, // shares an endpoint with the previous range
+      { start: 1, end: 6 }, // overlaps the previous ranges
+      { start: 4, end: 4 }, // empty
+      { start: 0, end: 3 }, // same as the first range
+    ];
+    const options = [
+      {},
+      { symbolRange: { start: 1, end: 3 } },
+      { ignoreBits: 1 },
+      { ignoreBits: wm.defaultLevelMasks.slice(0, 2) },
+    ];
+    for (const opts of options) {
+      const result = wm.countsByRange(ranges, opts);
+      expect(result.length).toBe(ranges.length);
+      ranges.forEach((range, i) => {
+        expect(result[i]).toEqual(wm.counts({ range, ...opts }));
+      });
+    }
+    expect(wm.countsByRange([])).toEqual([]);
+  });
+
+  it('counts clamps the symbol range to the alphabet', () => {
+    const options = [
+      {},
+      { range: { start: 1, end: wm.length - 1 } },
+      { ignoreBits: 1 },
+    ];
+    for (const opts of options) {
+      const expected = wm.counts(opts);
+      expect(wm.counts({ ...opts, symbolRange: { start: 0, end: 2 ** 32 } })).toEqual(expected);
+      expect(wm.counts({ ...opts, symbolRange: { start: 0, end: 2 ** 32 + 2 } })).toEqual(expected);
+      expect(wm.counts({ ...opts, symbolRange: { start: -3, end: 2 ** 40 } })).toEqual(expected);
+    }
+    expect(wm.counts({ symbolRange: { start: 2, end: 2 ** 32 + 2 } })).toEqual([
+      { symbol: 4, start: 2, end: 3 },
+      { symbol: 2, start: 3, end: 4 },
+    ]);
+    // symbol ranges entirely beyond the alphabet are empty
+    expect(wm.counts({ symbolRange: { start: wm.maxSymbol + 1, end: 2 ** 32 + 2 } })).toEqual([]);
+    expect(wm.countsByRange([{ start: 0, end: 3 }], { symbolRange: { start: 5, end: 6 } })).toEqual([[]]);
+
+    // A symbol range extending to 2^32 does no more work than one that ends right after maxSymbol,
+    // including when maxSymbol is not one less than a power of two. (For [maxSymbol, 2^32) it can
+    // do less, since the traversal stops at empty nodes while the single-symbol lookup does not.)
+    const wide = new WaveletMatrix(Array.from({ length: 500 }, (_, i) => (i * 7) % 1001), 1000);
+    const ranges = [{ start: 0, end: 500 }, { start: 100, end: 300 }, { start: 250, end: 260 }];
+    for (const start of [0, 500, 999, 1000]) {
+      let counter = countRanks(wide);
+      const expected = wide.countsByRange(ranges, { symbolRange: { start, end: wide.maxSymbol + 1 } });
+      const expectedRanks = counter.count;
+      wide.levels.forEach((level) => { level.bv = Object.getPrototypeOf(level.bv); });
+      counter = countRanks(wide);
+      expect(wide.countsByRange(ranges, { symbolRange: { start, end: 2 ** 32 } })).toEqual(expected);
+      expect(counter.count).toBeLessThanOrEqual(expectedRanks);
+      wide.levels.forEach((level) => { level.bv = Object.getPrototypeOf(level.bv); });
+    }
+
+    // With level masks, the end of the symbol range encodes the upper corner of a box, so it is
+    // clamped to the alphabet size rather than to maxSymbol + 1. Here the top bit is x and the
+    // low two bits are y, and the box 0 <= x <= 1, 1 <= y <= 3 contains both symbols 2 and 5.
+    const boxed = new WaveletMatrix([2, 5]);
+    const masks = [0b100, 0b011, 0b011];
+    const box = boxed.counts({ symbolRange: { start: 0b001, end: 0b111 + 1 }, ignoreBits: masks });
+    expect(box.reduce((sum, x) => sum + x.end - x.start, 0)).toBe(2);
+  });
+
+  it('decimate', () => {
+    for (const shift of [0, 1, 2, 3]) {
+      const decimated = wm.decimate(shift);
+      const expected = symbols.filter((_, i) => i % 2 ** shift === 0);
+      expect(decimated.length).toBe(expected.length);
+      expect(decimated.maxSymbol).toBe(wm.maxSymbol);
+      expect(decimated.sampleShift).toBe(shift);
+      expect(expected.map((_, i) => decimated.get(i))).toEqual(expected);
+    }
+    expect(wm.sampleShift).toBe(0);
+    expect(wm.decimate(1).decimate(2).sampleShift).toBe(3);
+    expect(new WaveletMatrix([]).decimate(2).length).toBe(0);
+  });
+
+  it('approximateCounts', () => {
+    // a smoothly-varying sequence of symbols in [0, 16)
+    const data = Array.from({ length: 50000 }, (_, i) => Math.floor(8 * (1 + Math.sin(i / 300))) & 15);
+    const wm = new WaveletMatrix(data);
+    const sampleShift = 4;
+    const overview = wm.decimate(sampleShift);
+
+    // with a sample shift of zero, the approximate counts are exact
+    expect(wm.approximateCounts([{ start: 0, end: data.length }])).toEqual([
+      wm.counts().map(x => ({ symbol: x.symbol, count: x.end - x.start }))
+    ]);
+
+    const ranges = [{ start: 0, end: data.length }, { start: 1234, end: 40001 }];
+    const approxByRange = overview.approximateCounts(ranges);
+    expect(approxByRange.length).toBe(ranges.length);
+    for (const [i, range] of ranges.entries()) {
+      const exact = new Map(wm.counts({ range }).map(x => [x.symbol, x.end - x.start]));
+      const approx = approxByRange[i];
+      const total = approx.reduce((acc, x) => acc + x.count, 0);
+      expect(Math.abs(total - (range.end - range.start))).toBeLessThanOrEqual(2 ** sampleShift);
+      for (const { symbol, count } of approx) {
+        const expected = exact.get(symbol) ?? 0;
+        expect(Math.abs(count - expected) / expected).toBeLessThan(0.05);
+      }
+    }
+
+    // decimating twice samples every 2^sampleShift-th element of the original matrix
+    expect(wm.decimate(1).decimate(sampleShift - 1).approximateCounts(ranges)).toEqual(approxByRange);
+    expect(overview.approximateCounts([])).toEqual([]);
+  });
+
+  it('counts with a single-symbol range', () => {
+    const ranges = [
+      { start: 0, end: wm.length },
+      { start: 1, end: 4 },
+      { start: 2, end: 2 },
+      { start: 3, end: 8 },
+      { start: 5, end: 6 },
+    ];
+    for (let symbol = 0; symbol <= wm.maxSymbol + 1; symbol++) {
+      const symbolRange = { start: symbol, end: symbol + 1 };
+      for (const range of ranges) {
+        const actual = wm.counts({ range, symbolRange });
+        // compare against the general path, which we force by using an explicit mask array
+        const expected = wm.counts({ range, symbolRange, ignoreBits: wm.defaultLevelMasks });
+        expect(actual).toEqual(expected);
+        const count = symbols.slice(range.start, range.end).filter(s => s === symbol).length;
+        expect(actual.length).toBe(count === 0 ? 0 : 1);
+        if (count > 0) {
+          expect(actual[0].symbol).toBe(symbol);
+          expect(actual[0].end - actual[0].start).toBe(count);
+        }
+      }
+      expect(wm.countsByRange(ranges, { symbolRange })).toEqual(
+        wm.countsByRange(ranges, { symbolRange, ignoreBits: wm.defaultLevelMasks })
+      );
+    }
+  });
+
+  it('validates ignoreBits', () => {
+    const n = wm.numLevels;
+    for (const ignoreBits of [0, n]) {
+      expect(() => wm.locate(1, { ignoreBits })).not.toThrow();
+      expect(() => wm.select(1, { ignoreBits })).not.toThrow();
+      expect(() => wm.selectFromEnd(1, { ignoreBits })).not.toThrow();
+      expect(() => wm.selectUpwards(0, { ignoreBits })).not.toThrow();
+      expect(wm.trySelectUpwards(0, { ignoreBits })).not.toBeNull();
+      expect(() => wm.counts({ ignoreBits })).not.toThrow();
+      expect(() => wm.countsUnion([{ start: 0, end: 2 }], { ignoreBits })).not.toThrow();
+      expect(() => wm.countsByRange([{ start: 0, end: 2 }], { ignoreBits })).not.toThrow();
+    }
+
+    // ignoring every level treats all symbols as equal
+    expect(wm.locate(2, { ignoreBits: n })).toEqual({ precedingCount: 0, range: { start: 0, end: wm.length } });
+    expect(wm.select(2, { k: 3, ignoreBits: n })).toBe(3);
+    expect(wm.selectFromEnd(2, { k: 0, ignoreBits: n })).toBe(wm.length - 1);
+    expect(wm.counts({ ignoreBits: n })).toEqual([{ symbol: 0, start: 0, end: wm.length }]);
+
+    for (const ignoreBits of [n + 1, -1, 0.5]) {
+      const message = `ignoreBits (${ignoreBits}) must be an integer in [0, ${n}]`;
+      expect(() => wm.locate(1, { ignoreBits })).toThrow(message);
+      expect(() => wm.select(1, { ignoreBits })).toThrow(message);
+      expect(() => wm.selectFromEnd(1, { ignoreBits })).toThrow(message);
+      if (DEBUG) {
+        expect(() => wm.selectUpwards(0, { ignoreBits })).toThrow(message);
+      }
+      expect(wm.trySelectUpwards(0, { ignoreBits })).toBeNull();
+      expect(() => wm.counts({ ignoreBits })).toThrow(message);
+      expect(() => wm.countsUnion([{ start: 0, end: 2 }], { ignoreBits })).toThrow(message);
+      expect(() => wm.countsByRange([{ start: 0, end: 2 }], { ignoreBits })).toThrow(message);
+      // invalid arguments are rejected even when the query is trivially empty or a single symbol
+      for (const symbolRange of [{ start: 2, end: 2 }, { start: 2, end: 3 }, { start: 2 ** 32, end: 2 ** 32 + 1 }]) {
+        expect(() => wm.counts({ symbolRange, ignoreBits })).toThrow(message);
+      }
+      expect(() => wm.countsByRange([], { ignoreBits })).toThrow(message);
+    }
+    expect(() => wm.counts({ ignoreBits: Array(n + 1).fill(bits.oneMask(32)) })).toThrow();
+    expect(() => wm.counts({ symbolRange: { start: 2, end: 2 }, ignoreBits: Array(n + 1).fill(0) })).toThrow();
+  });
+
+  it('selectFirstLessThanOrEqual on a single-level matrix', () => {
+    // every sequence of 0s and 1s of length 1 through 4
+    for (let length = 1; length <= 4; length++) {
+      for (let mask = 0; mask < 2 ** length; mask++) {
+        const symbols = Array.from({ length }, (_, i) => (mask >>> i) & 1);
+        const wm = new WaveletMatrix(symbols, 1);
+        expect(wm.numLevels).toBe(1);
+        for (let start = 0; start <= length; start++) {
+          for (let end = start; end <= length; end++) {
+            for (const symbol of [0, 1]) {
+              const i = symbols.slice(start, end).findIndex(x => x <= symbol);
+              const expected = i === -1 ? null : start + i;
+              expect(wm.selectFirstLessThanOrEqual(symbol, { range: { start, end } })).toBe(expected);
+            }
+          }
+        }
+      }
+    }
+  });
+
+  it('retainData', () => {
+    const retained = new WaveletMatrix(symbols, undefined, { retainData: true });
+    expect(wm.data).toBeNull();
+    expect(retained.data).not.toBeNull();
+    symbols.forEach((symbol, i) => {
+      expect(retained.get(i)).toBe(symbol);
+      expect(wm.get(i)).toBe(symbol);
+    });
+    expect(Array.from(retained.toArray())).toEqual(symbols);
+    expect(Array.from(wm.toArray())).toEqual(symbols);
+
+    // `get` reads from the retained copy rather than the levels
+    // @ts-ignore because data is non-null
+    retained.data[0] = 3;
+    expect(retained.get(0)).toBe(3);
+    expect(wm.get(0)).toBe(0);
+
+    // the retained copy is independent of the input array
+    const data = [5, 6, 7];
+    const wm2 = new WaveletMatrix(data, undefined, { retainData: true });
+    data[0] = 0;
+    expect(wm2.get(0)).toBe(5);
+
+    // the retained copy is in sequence order when the large-alphabet construction is used
+    const sparse = [5, 1000, 3, 7];
+    const wm3 = new WaveletMatrix(sparse.slice(), 1000, { retainData: true });
+    expect(Array.from(wm3.toArray())).toEqual(sparse);
+  });
+
+  it('construction strategy', () => {
+    // the histogram needs 4 * 2^numLevels bytes and the sort needs 8 * length bytes
+    expect(constructionStrategy(2 ** 25, 26)).toBe('histogram');
+    expect(constructionStrategy(2 ** 25 - 1, 26)).toBe('sort');
+    expect(constructionStrategy(8, 4)).toBe('histogram');
+    expect(constructionStrategy(7, 4)).toBe('sort');
+
+    // both strategies produce the matrix for their input, and leave the input unmodified.
+    // since a wavelet matrix is determined by its data and number of levels, this means
+    // that the two construction algorithms produce identical levels.
+    for (let length = 1; length <= 40; length++) {
+      const data = Array.from({ length }, (_, i) => (i * 2654435761 >>> 5) % 16);
+      const copy = data.slice();
+      const wm = new WaveletMatrix(data, 15);
+      expect(data).toEqual(copy);
+      expect(Array.from(wm.toArray())).toEqual(data);
+      const numOnes = wm.levels.map(level => level.bv.numOnes);
+      const expected = [8, 4, 2, 1].map(bit => data.filter(d => d & bit).length);
+      expect(numOnes).toEqual(expected);
+    }
+  });
+
+  it('construction from views into a shared buffer', () => {
+    const buffer = Uint32Array.from({ length: 64 }, (_, i) => (i * 2654435761 >>> 3) % 1000);
+    const snapshot = buffer.slice();
+    // with large alphabets, every shard is built by sorting a copy
+    for (const [start, end] of [[0, 64], [5, 20], [10, 11], [30, 64]]) {
+      for (const maxSymbol of [999, 2 ** 20]) {
+        const view = buffer.subarray(start, end);
+        expect(constructionStrategy(view.length, Math.ceil(Math.log2(maxSymbol + 1)))).toBe('sort');
+        const fromView = new WaveletMatrix(view, maxSymbol);
+        const fromCopy = new WaveletMatrix(Array.from(view), maxSymbol);
+        expect(fromView.levels.map(l => l.bv.numOnes)).toEqual(fromCopy.levels.map(l => l.bv.numOnes));
+        expect(Array.from(fromView.toArray())).toEqual(Array.from(fromCopy.toArray()));
+        expect(Array.from(fromView.toArray())).toEqual(Array.from(view));
+      }
+    }
+    expect(buffer).toEqual(snapshot);
+
+    // with a small alphabet, long enough shards are built from a histogram that reads the view in place
+    const small = buffer.map(x => x % 16);
+    const smallSnapshot = small.slice();
+    for (const [start, end, strategy] of /** @type {[number, number, string][]} */ ([[0, 64, 'histogram'], [30, 64, 'histogram'], [10, 11, 'sort']])) {
+      const view = small.subarray(start, end);
+      expect(constructionStrategy(view.length, 4)).toBe(strategy);
+      const fromView = new WaveletMatrix(view, 15);
+      const fromCopy = new WaveletMatrix(Array.from(view), 15);
+      expect(fromView.levels.map(l => l.bv.numOnes)).toEqual(fromCopy.levels.map(l => l.bv.numOnes));
+      expect(Array.from(fromView.toArray())).toEqual(Array.from(view));
+    }
+    expect(small).toEqual(smallSnapshot);
+  });
+
+  it('counts rejects inverted symbol ranges', () => {
+    const message = 'symbolRange start (5) must not exceed its end (2)';
+    const symbolRange = { start: 5, end: 2 };
+    expect(() => wm.counts({ symbolRange })).toThrow(message);
+    expect(() => wm.countsUnion([{ start: 0, end: 3 }], { symbolRange })).toThrow(message);
+    expect(() => wm.countsByRange([{ start: 0, end: 3 }], { symbolRange })).toThrow(message);
+    // empty symbol ranges are fine
+    expect(wm.counts({ symbolRange: { start: 2, end: 2 } })).toEqual([]);
+    // a symbol range extending past maxSymbol is clamped
+    expect(wm.counts({ symbolRange: { start: 1, end: wm.maxSymbol + 100 } })).toEqual(
+      wm.counts({ symbolRange: { start: 1, end: wm.maxSymbol + 1 } })
+    );
+  });
+
+  it('counts sorted by symbol', () => {
+    const ranges = [{ start: 0, end: wm.length }, { start: 2, end: 7 }, { start: 4, end: 4 }];
+    for (const ignoreBits of [0, 1, 2]) {
+      for (const range of ranges) {
+        const unsorted = wm.counts({ range, ignoreBits });
+        const sorted = wm.counts({ range, ignoreBits, sortBySymbol: true });
+        expect(sorted).toEqual(unsorted.slice().sort((a, b) => a.symbol - b.symbol));
+        for (let i = 1; i < sorted.length; i++) {
+          expect(sorted[i - 1].symbol).toBeLessThan(sorted[i].symbol);
+        }
+      }
+      // results are ascending by (range index, symbol)
+      const byRange = wm.countsByRange(ranges, { ignoreBits, sortBySymbol: true });
+      const flattened = byRange.flatMap((nodes, i) => nodes.map(x => ({ i, symbol: x.symbol })));
+      for (let j = 1; j < flattened.length; j++) {
+        const a = flattened[j - 1];
+        const b = flattened[j];
+        expect(a.i < b.i || (a.i === b.i && a.symbol < b.symbol)).toBe(true);
+      }
+      byRange.forEach((nodes, i) => {
+        expect(nodes).toEqual(wm.counts({ range: ranges[i], ignoreBits, sortBySymbol: true }));
+      });
+    }
+    // the default order is the bit-reversed order of the bottom level
+    expect(wm.counts().map(x => x.symbol)).toEqual([0, 4, 2, 1]);
+  });
+
+  it('coalesceRanges', () => {
+    expect(coalesceRanges([])).toEqual([]);
+    expect(coalesceRanges([
+      { start: 10, end: 12 },
+      { start: 3, end: 3 }, // empty
+      { start: 0, end: 2 },
+      { start: 2, end: 4 }, // adjacent
+      { start: 11, end: 15 }, // overlapping
+      { start: 12, end: 13 }, // contained
+      { start: 7, end: 4 }, // inverted, so empty
+    ])).toEqual([{ start: 0, end: 4 }, { start: 10, end: 15 }]);
+  });
+
+  describe('exclusion ranges', () => {
+    // deterministic pseudo-random symbols in [0, 40)
+    const symbols = Array.from({ length: 200 }, (_, i) => (i * 7919 + ((i * i) % 13)) % 40);
+    const wm = new WaveletMatrix(symbols);
+    const exclusions = [
+      [],
+      [{ start: 0, end: 0 }], // empty
+      [{ start: 3, end: 4 }],
+      [{ start: 3, end: 10 }, { start: 5, end: 12 }], // overlapping
+      [{ start: 3, end: 10 }, { start: 10, end: 12 }], // adjacent
+      [{ start: 30, end: 35 }, { start: 0, end: 8 }, { start: 16, end: 24 }], // unsorted, aligned to nodes
+      [{ start: 0, end: 40 }], // the whole alphabet
+      [{ start: 0, end: 2 ** 32 }], // beyond the alphabet
+      [{ start: 39, end: 2 ** 32 }],
+    ];
+    const ranges = [{ start: 0, end: symbols.length }, { start: 17, end: 123 }, { start: 50, end: 50 }];
+
+    /**
+     * @param {number} symbol
+     * @param {{ start: number; end: number; }[]} excluded
+     */
+    const isExcluded = (symbol, excluded) => excluded.some(r => r.start <= symbol && symbol < r.end);
+
+    it('countExcluding', () => {
+      for (const excluded of exclusions) {
+        for (const range of ranges) {
+          const expected = symbols.slice(range.start, range.end).filter(s => !isExcluded(s, excluded)).length;
+          expect(wm.countExcluding(excluded, { range })).toBe(expected);
+        }
+      }
+    });
+
+    it('counts with excludedSymbolRanges', () => {
+      for (const excludedSymbolRanges of exclusions) {
+        for (const range of ranges) {
+          for (const symbolRange of [{ start: 0, end: 40 }, { start: 5, end: 31 }, { start: 9, end: 10 }]) {
+            const actual = wm.counts({ range, symbolRange, excludedSymbolRanges });
+            /** @type {Map<number, number>} */
+            const expected = new Map();
+            for (const s of symbols.slice(range.start, range.end)) {
+              if (s >= symbolRange.start && s < symbolRange.end && !isExcluded(s, excludedSymbolRanges)) {
+                expected.set(s, (expected.get(s) ?? 0) + 1);
+              }
+            }
+            expect(new Map(actual.map(x => [x.symbol, x.end - x.start]))).toEqual(expected);
+          }
+        }
+      }
+
+      // with ignored bits, nodes are only skipped if all of their symbols are excluded
+      const ignoreBits = 3; // each bottom-level node spans 8 symbols
+      const expected = wm.counts({ ignoreBits }).filter(x => x.symbol !== 16);
+      expect(wm.counts({ ignoreBits, excludedSymbolRanges: [{ start: 16, end: 24 }, { start: 25, end: 31 }] })).toEqual(expected);
+    });
+  });
+
+  it('cancellation', () => {
+    const symbols = Array.from({ length: 4096 }, (_, i) => (i * 2654435761) % 4096);
+    const wm = new WaveletMatrix(symbols, 4095);
+
+    // a signal that is never aborted does not affect the result
+    const controller = new AbortController();
+    expect(wm.counts({ signal: controller.signal })).toEqual(wm.counts());
+    expect(wm.countsUnion([{ start: 0, end: 10 }], { signal: controller.signal })).toEqual(wm.countsUnion([{ start: 0, end: 10 }]));
+
+    // an already-aborted signal throws its reason before doing any work
+    controller.abort(new Error('stop'));
+    expect(() => wm.counts({ signal: controller.signal })).toThrow('stop');
+    expect(() => wm.countsByRange([{ start: 0, end: 10 }], { signal: controller.signal })).toThrow('stop');
+    expect(() => wm.counts({ signal: { aborted: true } })).toThrow('cancelled');
+
+    // the signal is checked once up front, once per level, and every 1024 nodes within a level
+    let reads = 0;
+    let abortAfter = Infinity;
+    const signal = { get aborted() { return ++reads > abortAfter; } };
+    wm.counts({ signal });
+    expect(reads).toBe(1 + 12 + 1 + 2);
+
+    // a signal that becomes aborted during the query, as if set by another thread,
+    // is noticed partway through a wide level (here, the 1024-node level)
+    reads = 0;
+    abortAfter = 1 + 11;
+    expect(() => wm.counts({ signal })).toThrow('cancelled');
+    expect(reads).toBe(abortAfter + 1);
+  });
+
+  it('boundaries', () => {
+    // piecewise-constant data with runs of varying lengths, including runs of length 1,
+    // and high-entropy data in which most adjacent pairs differ
+    const runs = [5, 5, 5, 1, 1, 7, 0, 0, 0, 0, 3, 5, 5, 2, 2, 2, 2, 2, 6, 1];
+    const noisy = Array.from({ length: 50 }, (_, i) => (i * 2654435761 >>> 7) % 5);
+    for (const symbols of [runs, noisy, [4], [3, 3, 3]]) {
+      const wm = new WaveletMatrix(symbols, Math.max(...symbols));
+      for (let start = 0; start <= symbols.length; start++) {
+        for (let end = start; end <= symbols.length; end++) {
+          const expected = [];
+          for (let i = start; i + 1 < end; i++) {
+            if (symbols[i] !== symbols[i + 1]) expected.push(i);
+          }
+          expect(wm.boundaries({ start, end })).toEqual(expected);
+          expect(wm.countBoundaries({ start, end })).toBe(expected.length);
+        }
+      }
+    }
+
+    // boundaries at the range edges are excluded
+    const wm = new WaveletMatrix(runs, 7);
+    expect(wm.boundaries({ start: 3, end: 5 })).toEqual([]);
+    expect(wm.boundaries({ start: 2, end: 6 })).toEqual([2, 4]);
+  });
+
+  it('handles symbol ranges starting at or above 2^31', () => {
+    const wm = new WaveletMatrix([0, 2 ** 32 - 1, 2 ** 31 + 5, 2 ** 31 - 1]);
+    expect(wm.counts({ symbolRange: { start: 2 ** 31 + 6, end: 2 ** 32 } })).toEqual([
+      { symbol: 2 ** 32 - 1, start: 3, end: 4 }
+    ]);
+    expect(wm.counts({ symbolRange: { start: 2 ** 31, end: 2 ** 31 + 1 } })).toEqual([]);
+  });
+
   it('handles extreme values', () => {
     const wm = new WaveletMatrix([0, 2 ** 32 - 1]);
     expect(wm.counts()).toEqual([
@@ -207,5 +1343,201 @@ describe('WaveletMatrix', () => {
     ]);
   });
 
+  it('counts stays within the symbol range and maxSymbol', () => {
+    fc.assert(fc.property(
+      fc.record({
+        maxSymbol: fc.constantFrom(1, 2, 5, 6, 9, 100, 1000),
+        values: fc.array(fc.nat(2 ** 16), { maxLength: 100 }),
+        rangeBounds: fc.tuple(fc.nat(100), fc.nat(100)),
+        symbolBounds: fc.tuple(fc.nat(1100), fc.nat(1100)),
+      }),
+      ({ maxSymbol, values, rangeBounds, symbolBounds }) => {
+        const data = values.map(v => v % (maxSymbol + 1));
+        const wm = new WaveletMatrix(data, maxSymbol);
+        wm.verifyAgainst(data);
+        const [a, b] = rangeBounds.map(x => Math.min(x, data.length));
+        const range = { start: Math.min(a, b), end: Math.max(a, b) };
+        const symbolRange = { start: Math.min(...symbolBounds), end: Math.max(...symbolBounds) };
+
+        // both with the default symbol range and with the requested one
+        for (const options of [{ range }, { range, symbolRange }]) {
+          const { start, end } = options.symbolRange ?? { start: 0, end: Infinity };
+          /** @type {Map<number, number>} */
+          const expected = new Map();
+          for (const symbol of data.slice(range.start, range.end).sort((a, b) => a - b)) {
+            if (symbol >= start && symbol < end) {
+              expected.set(symbol, (expected.get(symbol) ?? 0) + 1);
+            }
+          }
+          const xs = wm.counts({ ...options, sortBySymbol: true });
+          for (const x of xs) {
+            expect(x.symbol).toBeLessThanOrEqual(maxSymbol);
+          }
+          expect(xs.map(x => [x.symbol, x.end - x.start])).toEqual(Array.from(expected));
+        }
+      }
+    ), { numRuns: 200 });
+  });
+
+  it('rejects symbols above maxSymbol', () => {
+    expect(() => new WaveletMatrix([0, 5, 7], 5)).toThrow('expected symbols in [0, 5], got 7 at index 2');
+    expect(() => new WaveletMatrix([0, -1], 5)).toThrow('got -1 at index 1');
+  });
+
+  it('countsByRange shares ranks between adjacent ranges', () => {
+    const data = Array.from({ length: 1000 }, (_, i) => (i * 31 + (i >> 3)) % 50);
+    const wm = new WaveletMatrix(data);
+    const ranges = [{ start: 100, end: 350 }, { start: 350, end: 351 }, { start: 351, end: 900 }];
+    const ranks = countRanks(wm);
+    const separate = ranges.map(range => wm.counts({ range }));
+    const separateRanks = ranks.count;
+    ranks.count = 0;
+    const together = wm.countsByRange(ranges);
+    // each node's start rank is shared with the end rank of the preceding node from the adjacent range
+    expect(together).toEqual(separate);
+    expect(ranks.count).toBeLessThan(separateRanks * 0.8);
+
+    // the per-symbol totals reconcile with a single combined range
+    /** @type {Map<number, number>} */
+    const totals = new Map();
+    for (const x of together.flat()) totals.set(x.symbol, (totals.get(x.symbol) ?? 0) + x.end - x.start);
+    const combined = wm.counts({ range: { start: 100, end: 900 }, sortBySymbol: true });
+    expect(Array.from(totals).sort((a, b) => a[0] - b[0])).toEqual(combined.map(x => [x.symbol, x.end - x.start]));
+  });
+
+  it('countsByRange memoizes ranks at shared endpoints', () => {
+    const data = Array.from({ length: 1000 }, (_, i) => (i * 31 + (i >> 3)) % 50);
+    const wm = new WaveletMatrix(data);
+    // many ranges sharing a few endpoints, in no particular order
+    const ranges = Array.from({ length: 40 }, (_, i) => {
+      const start = [0, 100, 250, 600][i % 4];
+      const end = [1000, 700, 800, 250][(i * 7) % 4];
+      return start <= end ? { start, end } : { start: end, end: start };
+    });
+    const ranks = countRanks(wm);
+    const separate = ranges.map(range => wm.counts({ range }));
+    const separateRanks = ranks.count;
+    ranks.count = 0;
+    expect(wm.countsByRange(ranges)).toEqual(separate);
+    expect(ranks.count * 4).toBeLessThan(separateRanks);
+  });
+
+  it('matches', () => {
+    const data = Array.from({ length: 300 }, (_, i) => (i * 13 + (i >> 5)) % 20);
+    const wm = new WaveletMatrix(data);
+    const cases = [
+      [{ start: 0, end: 300 }, { start: 3, end: 8 }],
+      [{ start: 17, end: 250 }, { start: 0, end: 20 }], // matches everything
+      [{ start: 0, end: 300 }, { start: 5, end: 5 }], // empty filter
+      [{ start: 0, end: 300 }, { start: 20, end: 100 }], // no symbols in range
+      [{ start: 40, end: 40 }, { start: 0, end: 20 }],
+      [{ start: 299, end: 300 }, { start: 0, end: 2 ** 32 }],
+    ];
+    for (const [range, symbolRange] of cases) {
+      const expected = [];
+      for (let i = range.start; i < range.end; i++) {
+        if (symbolRange.start <= data[i] && data[i] < symbolRange.end) expected.push([i, data[i]]);
+      }
+      expect(Array.from(wm.matches(range, symbolRange))).toEqual(expected);
+    }
+    // abandoning the iterator early leaves the matrix usable
+    const it = wm.matches({ start: 0, end: 300 }, { start: 3, end: 8 });
+    it.next();
+    it.return(undefined);
+    expect(wm.matches({ start: 0, end: 300 }, { start: 0, end: 20 }).next().value).toEqual([0, data[0]]);
+  });
+
+  it('matches is lazy', () => {
+    const length = 2 ** 18;
+    const data = Array.from({ length }, (_, i) => (i * 7919) % 1000);
+    const wm = new WaveletMatrix(data);
+    const ranks = countRanks(wm);
+    const take = (/** @type {number} */ n) => {
+      ranks.count = 0;
+      const result = [];
+      for (const x of wm.matches({ start: 0, end: length }, { start: 0, end: 900 })) {
+        if (result.length === n) break;
+        result.push(x);
+      }
+      return { result, numRanks: ranks.count };
+    };
+    const first = take(100);
+    expect(first.result).toEqual(data.slice(0, 120).map((s, i) => [i, s]).filter(([, s]) => s < 900).slice(0, 100));
+    // the first 100 of ~236k matches take a small fraction of the work of taking 10000
+    const more = take(10000);
+    expect(first.numRanks * 20).toBeLessThan(more.numRanks);
+  });
+
+  it('sampleMatches', () => {
+    fc.assert(fc.property(fc.array(fc.nat(40), { maxLength: 200 }), fc.nat(40), fc.nat(40), fc.nat(50), fc.nat(), (data, a, b, k, seed) => {
+      const wm = new WaveletMatrix(data, 40);
+      const range = { start: Math.floor(data.length / 4), end: data.length };
+      const symbolRange = { start: Math.min(a, b), end: Math.max(a, b) };
+      const matches = Array.from(wm.matches(range, symbolRange));
+      const sample = wm.sampleMatches(k, seed, { range, symbolRange });
+      expect(sample.length).toBe(Math.min(k, matches.length));
+      expect(wm.sampleMatches(k, seed, { range, symbolRange })).toEqual(sample);
+      // the sample is an ascending subsequence of the matches, so it has no duplicates
+      const indices = new Map(matches.map(([i, s]) => [i, s]));
+      for (let n = 0; n < sample.length; n++) {
+        expect(indices.get(sample[n][0])).toBe(sample[n][1]);
+        if (n > 0) expect(sample[n - 1][0]).toBeLessThan(sample[n][0]);
+      }
+      expect(wm.sampleMatches(matches.length + 1, seed, { range, symbolRange })).toEqual(matches);
+    }));
+  });
+
+  it('sampleMatches is roughly uniform', () => {
+    // 16 matching elements, spread across the symbols 10, 11 and 12 and interleaved with non-matches
+    const data = Array.from({ length: 48 }, (_, i) => (i % 3 === 0 ? 10 + (i / 3) % 3 : i % 7));
+    const wm = new WaveletMatrix(data);
+    const symbolRange = { start: 10, end: 13 };
+    const numTrials = 2000;
+    const k = 4;
+    /** @type {Map<number, number>} */
+    const counts = new Map();
+    for (let seed = 0; seed < numTrials; seed++) {
+      for (const [i] of wm.sampleMatches(k, seed, { symbolRange })) {
+        counts.set(i, (counts.get(i) ?? 0) + 1);
+      }
+    }
+    expect(counts.size).toBe(16);
+    const expected = numTrials * k / 16;
+    let chiSquare = 0;
+    for (const count of counts.values()) {
+      chiSquare += (count - expected) ** 2 / expected;
+    }
+    // 15 degrees of freedom; the 99.9th percentile of the chi-square distribution is about 37.7
+    expect(chiSquare).toBeLessThan(37.7);
+  });
+
+  it('fromPoints2d and fromPoints3d support box queries for any number of levels', () => {
+    const countBox = (/** @type {WaveletMatrix} */ wm, /** @type {number} */ numDims, /** @type {number} */ lo, /** @type {number} */ hi) =>
+      wm.counts({ symbolRange: { start: lo, end: hi + 1 }, ignoreBits: mortonLevelMasks(wm.numLevels, numDims) })
+        .reduce((sum, x) => sum + x.end - x.start, 0);
+    const points = Array.from({ length: 60 }, (_, i) => [(i * 5) % 8, (i * 3) % 7, (i * 7) % 6]);
+    // adding a far corner point straddles a power of two in each dimension, which changes the
+    // number of levels without changing the answers to queries for boxes that exclude it
+    for (const extra of [[], [[8, 0, 0]], [[0, 8, 0]], [[8, 8, 0]], [[0, 0, 8]], [[31, 17, 9]]]) {
+      const all = points.concat(extra);
+      const wm2 = WaveletMatrix.fromPoints2d(all.map(p => p[0]), all.map(p => p[1]));
+      const wm3 = WaveletMatrix.fromPoints3d(all.map(p => p[0]), all.map(p => p[1]), all.map(p => p[2]));
+      for (const [x0, x1, y0, y1, z0, z1] of [[0, 8, 0, 7, 0, 6], [2, 5, 1, 4, 3, 6], [7, 8, 0, 7, 0, 1], [3, 4, 3, 4, 3, 4]]) {
+        const inBox = (/** @type {number[]} */ p, /** @type {number} */ n) =>
+          [[x0, x1], [y0, y1], [z0, z1]].slice(0, n).every(([lo, hi], d) => lo <= p[d] && p[d] < hi);
+        expect(countBox(wm2, 2, encode2(x0, y0), encode2(x1 - 1, y1 - 1))).toBe(all.filter(p => inBox(p, 2)).length);
+        expect(countBox(wm3, 3, encode3(x0, y0, z0), encode3(x1 - 1, y1 - 1, z1 - 1))).toBe(all.filter(p => inBox(p, 3)).length);
+      }
+    }
+    expect(() => WaveletMatrix.fromPoints2d([1, 2 ** 16], [0, 0])).toThrow('expected coordinates in [0, 2^16), got 65536 at index 1 in dimension 0');
+    expect(() => WaveletMatrix.fromPoints3d([1], [2], [])).toThrow('expected coordinate arrays of length 1, got 0 in dimension 2');
+  });
+
+  it('mortonLevelMasks are anchored at the bottom level', () => {
+    expect(mortonLevelMasks(3, 2)).toEqual([0x55555555, 0xaaaaaaaa, 0x55555555]);
+    expect(mortonLevelMasks(4, 2)).toEqual([0xaaaaaaaa, 0x55555555, 0xaaaaaaaa, 0x55555555]);
+    expect(mortonLevelMasks(2, 1)).toEqual([0xffffffff, 0xffffffff]);
+  });
+
   // todo: fails on implicitly- & explicitly-given symbols >= 2^32
 });